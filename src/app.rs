@@ -0,0 +1,2925 @@
+use crate::cli::{get_connect_options, get_filter, get_username};
+use crate::command_pane::{CommandPane, CommandPaneResult};
+use crate::dialog::{Dialog, DialogResult};
+use crate::diff_pane::{DiffPane, DiffPaneResult};
+use crate::edit::{EditResult, EditSession};
+use crate::log_pane::{LogPane, LogPaneResult};
+use crate::preview::{Preview, PreviewResult};
+use crate::prompt::{Prompt, PromptResult};
+use crate::properties::{parse_owner, PropertiesPane, PropertiesResult};
+use crate::shell::run_shell;
+use crate::user_message::{Severity, UserMessage};
+use rftp::compare::{compare_trees, CompareBy};
+use rftp::config::{expand_tilde, Config};
+use rftp::connect::{parse_port, split_destination, ConnectOptions};
+use rftp::connection::Connection;
+use rftp::file::*;
+use rftp::filesystem::{FileSystem, LocalFileSystem, Properties, Relabeled};
+use rftp::filter::Filter;
+use rftp::progress::{ProgressBar, ProgressBars, ProgressDirectory, ProgressFile};
+use rftp::transfer::{
+    apply_transfer, plan_transfer, transfer_verb, PlannedAction, TransferAction, TransferListener,
+};
+use rftp::utils::{
+    bytes_to_string, get_remote_home_dir, parse_mode, remote_copy, run_remote_command_output,
+    shell_quote, ErrorKind, Result,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::Spans,
+    widgets::Tabs,
+};
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The file system shown on the left, which is this machine unless testing.
+pub type LocalFs = dyn FileSystem<Entry = LocalFileEntry> + Send + Sync;
+
+/// The file system shown on the right, which is the sftp channel unless testing.
+pub type RemoteFs = dyn FileSystem<Entry = RemoteFileEntry> + Send + Sync;
+
+pub struct Rftp {
+    /// The title of the shown tab.
+    tab_title: String,
+    /// The tabs that are not shown, in order without the shown one.
+    tabs: Vec<Tab>,
+    /// The position of the shown tab among all tabs.
+    tab_index: usize,
+    /// The connection to the server on the right.
+    session: Option<Connection>,
+    /// The connection to the server on the left, if it does not show this machine.
+    local_session: Option<Connection>,
+    /// How to connect when a pane is connected to another host.
+    connect_options: ConnectOptions,
+    local: Arc<LocalFs>,
+    remote: Arc<RemoteFs>,
+    files: Arc<Mutex<FileList>>,
+    /// Held by each running transfer of the shown tab, which has some while it has more than
+    /// one reference.
+    transfers: Arc<()>,
+    is_alive: bool,
+    progress_bars: Arc<Mutex<ProgressBars>>,
+    show_hidden_files: Arc<AtomicBool>,
+    user_message: Arc<UserMessage>,
+    filter: Filter,
+    prompt: Option<(PromptAction, Prompt)>,
+    dialog: Option<(DialogAction, Dialog)>,
+    planned_transfers: (Sender<PlannedTransfer>, Receiver<PlannedTransfer>),
+    /// Where the file list was last drawn, for finding what the mouse points at.
+    file_list_rect: Cell<Rect>,
+    /// Where the progress bars were last drawn, for finding what the mouse points at.
+    progress_rect: Cell<Rect>,
+    /// When and where the left mouse button was last pressed.
+    last_click: Option<(Instant, u16, u16)>,
+    log_pane: Option<LogPane>,
+    preview: Option<Preview>,
+    command_pane: Option<CommandPane>,
+    diff_pane: Option<DiffPane>,
+    properties: Option<PropertiesPane>,
+    /// Work that waits for the terminal to be handed to it.
+    suspended_task: Option<SuspendedTask>,
+    /// Where the log pane was last drawn, for finding what the mouse points at.
+    log_rect: Cell<Rect>,
+}
+
+/// A connection in a tab that is not shown, with what the app swaps in when it is shown.
+///
+/// Transfers that were started in a tab keep running while it is hidden, but its planned
+/// transfers wait until it is shown again.
+struct Tab {
+    title: String,
+    session: Option<Connection>,
+    local_session: Option<Connection>,
+    local: Arc<LocalFs>,
+    remote: Arc<RemoteFs>,
+    files: Arc<Mutex<FileList>>,
+    transfers: Arc<()>,
+    planned_transfers: (Sender<PlannedTransfer>, Receiver<PlannedTransfer>),
+}
+
+impl Tab {
+    /// Create a tab titled `title` that shows `local_path` of `local` and `remote_path` of
+    /// `remote`.
+    fn new(
+        title: &str,
+        local: Arc<LocalFs>,
+        local_path: PathBuf,
+        remote: Arc<RemoteFs>,
+        remote_path: PathBuf,
+        show_hidden_files: bool,
+    ) -> Result<Self> {
+        let files = FileList::new(
+            &*local,
+            local_path,
+            &*remote,
+            remote_path,
+            show_hidden_files,
+        )?;
+        Ok(Tab {
+            title: title.to_string(),
+            session: None,
+            local_session: None,
+            local,
+            remote,
+            files: Arc::new(Mutex::new(files)),
+            transfers: Arc::new(()),
+            planned_transfers: unbounded(),
+        })
+    }
+}
+
+/// The most time between two clicks of a double-click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+
+/// What to do with the text of a prompt once the user submits it.
+enum PromptAction {
+    SetExcludes,
+    RunCommand,
+    /// Change the mode of the entry in the properties pane.
+    SetMode {
+        recursive: bool,
+    },
+    /// Change the owner of the entry in the properties pane.
+    SetOwner,
+    /// Copy or move the entry at `path` to the submitted path on the same side.
+    CopyWithin {
+        path: PathBuf,
+        is_remote: bool,
+        is_move: bool,
+    },
+    /// Connect the right pane if `is_remote`, or the left pane otherwise, to the submitted
+    /// host.
+    Connect {
+        is_remote: bool,
+    },
+    /// Open a tab connected to the submitted host.
+    OpenTab,
+    /// Diff the file at `path` with the file whose path is submitted.
+    DiffWith {
+        path: PathBuf,
+        is_remote: bool,
+    },
+}
+
+/// Work that needs the terminal while the interface is suspended.
+pub enum SuspendedTask {
+    /// Open a downloaded remote file in the editor.
+    Edit(EditSession),
+    /// Open an interactive shell on the server.
+    Shell,
+    /// Connect a pane to `destination`, which may ask for a password or to trust the host.
+    Connect {
+        destination: String,
+        is_remote: bool,
+    },
+    /// Open a tab connected to `destination`.
+    OpenTab(String),
+}
+
+/// What to do once the user confirms a dialog.
+enum DialogAction {
+    Transfer(PlannedTransfer),
+    Cancel(ProgressBar),
+    /// Overwrite a remote file that changed while it was edited.
+    OverwriteEdit(EditSession),
+}
+
+/// A download or upload that has been planned in the background but has not started yet.
+enum PlannedTransfer {
+    Upload(LocalFileEntry, Vec<TransferAction>),
+    Download(RemoteFileEntry, Vec<TransferAction>),
+}
+
+impl PlannedTransfer {
+    fn source_name(&self) -> Option<std::borrow::Cow<'_, str>> {
+        match self {
+            PlannedTransfer::Upload(source, _) => source.file_name_lossy(),
+            PlannedTransfer::Download(source, _) => source.file_name_lossy(),
+        }
+    }
+
+    fn actions(&self) -> &[TransferAction] {
+        match self {
+            PlannedTransfer::Upload(_, actions) | PlannedTransfer::Download(_, actions) => actions,
+        }
+    }
+
+    /// Return true if the user should see the plan before it starts.
+    ///
+    /// Directories are always confirmed because they might be big.
+    fn needs_confirmation(&self) -> bool {
+        let is_dir = match self {
+            PlannedTransfer::Upload(source, _) => source.is_dir(),
+            PlannedTransfer::Download(source, _) => source.is_dir(),
+        };
+        is_dir
+            || self
+                .actions()
+                .iter()
+                .any(|action| !matches!(action, TransferAction::Create { .. }))
+    }
+
+    /// Show the plan in a dialog, where `is_between_servers` is true if the left pane shows a
+    /// server as well.
+    fn to_dialog(&self, is_between_servers: bool) -> Dialog {
+        let (verb, name) = match self {
+            _ if is_between_servers => ("Copy", self.source_name()),
+            PlannedTransfer::Upload(..) => ("Upload", self.source_name()),
+            PlannedTransfer::Download(..) => ("Download", self.source_name()),
+        };
+        let title = format!("{} \"{}\"?", verb, name.unwrap_or_default());
+        let actions = self.actions();
+        let count = |name: &str| actions.iter().filter(|a| a.name() == name).count();
+        let summary = format!(
+            "{} to create, {} to overwrite, {} skipped, {} in total.",
+            count("create"),
+            count("overwrite"),
+            count("skip"),
+            bytes_to_string(actions.iter().map(PlannedAction::bytes).sum())
+        );
+        let lines = actions.iter().map(PlannedAction::describe).collect();
+        Dialog::new(&title, lines, &summary)
+    }
+}
+
+impl Rftp {
+    pub fn new(matches: &clap::ArgMatches, config: &Config) -> Result<Self> {
+        let destination = matches.value_of("destination").unwrap();
+        let username = get_username(matches)?;
+        let connect_options = get_connect_options(matches, &username, config)?;
+        let session = Connection::connect(destination, connect_options.clone())?;
+        let filter = get_filter(matches, config)?;
+
+        let mut rftp = match matches.value_of("second_destination") {
+            // The first host takes the place of this machine on the left.
+            Some(second_destination) => {
+                let second_session =
+                    Connection::connect(second_destination, connect_options.clone())?;
+                let mut rftp = Rftp::with_file_systems(
+                    Arc::new(Relabeled::new(session.clone())),
+                    get_remote_home_dir(&session.session()).unwrap_or_else(|_| PathBuf::from("./")),
+                    Arc::new(second_session.clone()),
+                    get_remote_home_dir(&second_session.session())
+                        .unwrap_or_else(|_| PathBuf::from("./")),
+                    filter,
+                )?;
+                let mut files = rftp.files.lock().unwrap();
+                files.set_local_title(destination);
+                files.set_remote_title(second_destination);
+                drop(files);
+                rftp.tab_title = format!("{} {}", destination, second_destination);
+                rftp.report_reconnects(&session);
+                rftp.report_reconnects(&second_session);
+                rftp.local_session = Some(session);
+                rftp.session = Some(second_session);
+                rftp
+            }
+            None => {
+                let mut rftp = Rftp::with_file_systems(
+                    Arc::new(LocalFileSystem),
+                    std::env::current_dir()?,
+                    Arc::new(session.clone()),
+                    get_remote_home_dir(&session.session()).unwrap_or_else(|_| PathBuf::from("./")),
+                    filter,
+                )?;
+                rftp.report_reconnects(&session);
+                rftp.session = Some(session);
+                rftp.tab_title = destination.to_string();
+                rftp
+            }
+        };
+        rftp.connect_options = connect_options;
+        let log_file = matches
+            .value_of("log_file")
+            .map(expand_tilde)
+            .or_else(|| config.log_file.clone());
+        if let Some(path) = log_file {
+            rftp.user_message.set_log_file(&path)?;
+        }
+        Ok(rftp)
+    }
+
+    /// Create the app for browsing `local_path` of `local` and `remote_path` of `remote`
+    /// without connecting to anything.
+    pub fn with_file_systems(
+        local: Arc<LocalFs>,
+        local_path: PathBuf,
+        remote: Arc<RemoteFs>,
+        remote_path: PathBuf,
+        filter: Filter,
+    ) -> Result<Self> {
+        let show_hidden_files = false;
+        let tab = Tab::new(
+            "Remote",
+            local,
+            local_path,
+            remote,
+            remote_path,
+            show_hidden_files,
+        )?;
+
+        let user_message = UserMessage::new();
+        user_message.report("Press \"?\" for help.");
+
+        Ok(Rftp {
+            tab_title: tab.title,
+            tabs: vec![],
+            tab_index: 0,
+            session: tab.session,
+            local_session: tab.local_session,
+            connect_options: ConnectOptions::default(),
+            local: tab.local,
+            remote: tab.remote,
+            files: tab.files,
+            transfers: tab.transfers,
+            is_alive: true,
+            progress_bars: Arc::new(Mutex::new(ProgressBars::new())),
+            show_hidden_files: Arc::new(AtomicBool::new(show_hidden_files)),
+            user_message: Arc::new(user_message),
+            filter,
+            prompt: None,
+            dialog: None,
+            planned_transfers: tab.planned_transfers,
+            file_list_rect: Cell::new(Rect::default()),
+            progress_rect: Cell::new(Rect::default()),
+            last_click: None,
+            log_pane: None,
+            preview: None,
+            command_pane: None,
+            diff_pane: None,
+            properties: None,
+            suspended_task: None,
+            log_rect: Cell::new(Rect::default()),
+        })
+    }
+
+    /// Work that is done on every "tick".
+    pub fn tick(&mut self) -> Result<()> {
+        self.progress_bars.lock().unwrap().retain_incomplete();
+        // Only one plan is shown at a time, the others wait in the channel.
+        if self.dialog.is_none() {
+            if let Ok(plan) = self.planned_transfers.1.try_recv() {
+                if plan.needs_confirmation() {
+                    let dialog = plan.to_dialog(self.local.is_remote());
+                    self.dialog = Some((DialogAction::Transfer(plan), dialog));
+                } else {
+                    self.spawn_transfer(plan);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Work that is done on every key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some((action, prompt)) = self.prompt.as_mut() {
+            match prompt.on_event(key) {
+                PromptResult::Pending => {}
+                PromptResult::Cancel => self.prompt = None,
+                PromptResult::Submit(input) => {
+                    match action {
+                        PromptAction::RunCommand => {
+                            let command = input.trim().to_string();
+                            if !command.is_empty() {
+                                self.run_command(&command);
+                            }
+                        }
+                        PromptAction::SetMode { recursive } => {
+                            let recursive = *recursive;
+                            self.change_mode(&input, recursive);
+                        }
+                        PromptAction::SetOwner => self.change_owner(&input),
+                        PromptAction::CopyWithin {
+                            path,
+                            is_remote,
+                            is_move,
+                        } => {
+                            let (path, is_remote, is_move) = (path.clone(), *is_remote, *is_move);
+                            self.copy_within(&path, is_remote, is_move, input.trim());
+                        }
+                        PromptAction::Connect { is_remote } => {
+                            let destination = input.trim().to_string();
+                            if !destination.is_empty() {
+                                self.suspended_task = Some(SuspendedTask::Connect {
+                                    destination,
+                                    is_remote: *is_remote,
+                                });
+                            }
+                        }
+                        PromptAction::OpenTab => {
+                            let destination = input.trim().to_string();
+                            if !destination.is_empty() {
+                                self.suspended_task = Some(SuspendedTask::OpenTab(destination));
+                            }
+                        }
+                        PromptAction::DiffWith { path, is_remote } => {
+                            let (path, is_remote) = (path.clone(), *is_remote);
+                            self.diff_with(&path, is_remote, input.trim());
+                        }
+                        PromptAction::SetExcludes => {
+                            let patterns: Vec<String> =
+                                input.split_whitespace().map(String::from).collect();
+                            self.user_message.report(&if patterns.is_empty() {
+                                "Do not exclude any files.".to_string()
+                            } else {
+                                format!("Exclude {}.", patterns.join(" "))
+                            });
+                            self.filter.set_excludes(patterns);
+                        }
+                    }
+                    self.prompt = None;
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some((_, dialog)) = self.dialog.as_mut() {
+            match dialog.on_event(key) {
+                DialogResult::Pending => {}
+                DialogResult::Cancel => match self.dialog.take().unwrap().0 {
+                    DialogAction::OverwriteEdit(edit) => {
+                        self.user_message.warn(&format!(
+                            "Did not upload {:?}, the edited copy is kept in {:?}.",
+                            edit.remote_path(),
+                            edit.local_path()
+                        ));
+                    }
+                    _ => self.user_message.report("Cancelled."),
+                },
+                DialogResult::Confirm => {
+                    let (action, _) = self.dialog.take().unwrap();
+                    match action {
+                        DialogAction::Transfer(plan) => self.spawn_transfer(plan),
+                        DialogAction::Cancel(progress) => progress.cancel(),
+                        DialogAction::OverwriteEdit(edit) => self.upload_edit(edit, true),
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(properties) = self.properties.as_mut() {
+            match properties.on_event(key) {
+                PropertiesResult::Pending => {}
+                PropertiesResult::Close => self.properties = None,
+                PropertiesResult::ChangeMode { recursive } => {
+                    let title = if recursive {
+                        format!("Mode of everything in {:?}, in octal", properties.path())
+                    } else {
+                        format!("Mode of {:?}, in octal", properties.path())
+                    };
+                    let mode = properties
+                        .properties()
+                        .mode
+                        .map(|mode| format!("{:o}", mode & 0o7777))
+                        .unwrap_or_default();
+                    self.prompt = Some((
+                        PromptAction::SetMode { recursive },
+                        Prompt::new(&title, &mode),
+                    ));
+                }
+                PropertiesResult::ChangeOwner => {
+                    let owner = match (properties.properties().uid, properties.properties().gid) {
+                        (Some(uid), Some(gid)) => format!("{}:{}", uid, gid),
+                        _ => String::new(),
+                    };
+                    self.prompt = Some((
+                        PromptAction::SetOwner,
+                        Prompt::new("Owner as uid:gid", &owner),
+                    ));
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(preview) = self.preview.as_mut() {
+            if preview.on_event(key) == PreviewResult::Close {
+                self.preview = None;
+            }
+            return Ok(());
+        }
+
+        if let Some(command_pane) = self.command_pane.as_mut() {
+            if command_pane.on_event(key) == CommandPaneResult::Close {
+                self.command_pane = None;
+            }
+            return Ok(());
+        }
+
+        if let Some(diff_pane) = self.diff_pane.as_mut() {
+            if diff_pane.on_event(key) == DiffPaneResult::Close {
+                self.diff_pane = None;
+            }
+            return Ok(());
+        }
+
+        if let Some(log_pane) = self.log_pane.as_mut() {
+            if log_pane.on_event(key, &self.user_message) == LogPaneResult::Close {
+                self.log_pane = None;
+            }
+            return Ok(());
+        }
+
+        match key {
+            KeyEvent {
+                code: KeyCode::Char('L'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                self.log_pane = Some(LogPane::new());
+            }
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.open_preview();
+            }
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.open_properties();
+            }
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.diff_selected();
+            }
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.open_copy_prompt(false);
+            }
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.prompt = Some((
+                    PromptAction::OpenTab,
+                    Prompt::new("Open a tab connected to [user@]host[:port]", ""),
+                ));
+            }
+            KeyEvent {
+                code: KeyCode::Tab, ..
+            } => {
+                self.show_tab((self.tab_index + 1) % (self.tabs.len() + 1));
+            }
+            KeyEvent {
+                code: KeyCode::BackTab,
+                ..
+            } => {
+                let count = self.tabs.len() + 1;
+                self.show_tab((self.tab_index + count - 1) % count);
+            }
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.close_tab();
+            }
+            KeyEvent {
+                code: KeyCode::Char('O'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                let is_remote = matches!(
+                    self.files.lock().unwrap().get_selected_entry(),
+                    SelectedFileEntry::Remote(_)
+                );
+                let title = format!(
+                    "Connect the {} pane to [user@]host[:port]",
+                    if is_remote { "right" } else { "left" }
+                );
+                self.prompt = Some((PromptAction::Connect { is_remote }, Prompt::new(&title, "")));
+            }
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.open_copy_prompt(true);
+            }
+            KeyEvent {
+                code: KeyCode::Char('D'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                self.open_diff_prompt();
+            }
+            KeyEvent {
+                code: KeyCode::Char('='),
+                ..
+            } => {
+                let mut files = self.files.lock().unwrap();
+                let compare_by = match files.get_compare() {
+                    None => Some(CompareBy::SizeAndTime),
+                    Some(CompareBy::SizeAndTime) => Some(CompareBy::Size),
+                    Some(CompareBy::Size) => None,
+                };
+                files.set_compare(compare_by);
+                drop(files);
+                self.user_message.report(match compare_by {
+                    Some(CompareBy::SizeAndTime) => {
+                        "Compare files with the other side by size and modification time."
+                    }
+                    Some(CompareBy::Size) => "Compare files with the other side by size.",
+                    None => "Do not compare files with the other side.",
+                });
+            }
+            KeyEvent {
+                code: KeyCode::Char('T'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                self.transfer_differences();
+            }
+            KeyEvent {
+                code: KeyCode::Char('C'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                self.spawn_compare_trees();
+            }
+            KeyEvent {
+                code: KeyCode::Char('!'),
+                ..
+            } => {
+                self.prompt = Some((
+                    PromptAction::RunCommand,
+                    Prompt::new("Run in the remote directory", ""),
+                ));
+            }
+            KeyEvent {
+                code: KeyCode::Char('S'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                if self.session.is_none() {
+                    self.user_message
+                        .error("A shell can only be opened when connected to a server.");
+                } else if !self.progress_bars.lock().unwrap().is_empty() {
+                    self.user_message
+                        .warn("Wait for the downloads/uploads to finish before opening a shell.");
+                } else {
+                    self.suspended_task = Some(SuspendedTask::Shell);
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                let files = self.files.lock().unwrap();
+                match files.get_selected_entry() {
+                    SelectedFileEntry::Remote(entry) if !entry.is_dir() => {
+                        drop(files);
+                        match EditSession::start(&*self.remote, entry.path()) {
+                            Ok(edit) => self.suspended_task = Some(SuspendedTask::Edit(edit)),
+                            Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+                        }
+                    }
+                    _ => {
+                        drop(files);
+                        self.user_message.error("No remote file selected.");
+                    }
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('Q'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                self.is_alive = false;
+            }
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                if self.progress_bars.lock().unwrap().is_empty() {
+                    self.is_alive = false;
+                } else {
+                    self.user_message.warn(
+                        "There are still downloads/uploads in progress. Press Q to force quit.",
+                    );
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.enter_selected()?;
+            }
+            KeyEvent {
+                code: KeyCode::Char(' '),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                let files = self.files.lock().unwrap();
+                match files.get_selected_entry() {
+                    SelectedFileEntry::Local(source) => {
+                        let dest = files.get_remote_working_path().to_path_buf();
+                        drop(files);
+                        let (local, remote) = (Arc::clone(&self.local), Arc::clone(&self.remote));
+                        self.spawn_plan(local, source, remote, dest, PlannedTransfer::Upload);
+                    }
+                    SelectedFileEntry::Remote(source) => {
+                        let dest = files.get_local_working_path().to_path_buf();
+                        drop(files);
+                        let (local, remote) = (Arc::clone(&self.local), Arc::clone(&self.remote));
+                        self.spawn_plan(remote, source, local, dest, PlannedTransfer::Download);
+                    }
+                    SelectedFileEntry::None => {
+                        drop(files);
+                        self.user_message.error("No file selected.");
+                    }
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('j'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Down,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.files.lock().unwrap().next_selected();
+            }
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Up,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.files.lock().unwrap().prev_selected();
+            }
+            KeyEvent {
+                code: KeyCode::Char('h'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::NONE,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.files.lock().unwrap().toggle_selected();
+            }
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                let show_hidden_files = !self.show_hidden_files.fetch_xor(true, Ordering::Relaxed);
+                self.user_message.report(&format!(
+                    "{} hidden files.",
+                    if show_hidden_files { "Show" } else { "Hide" }
+                ));
+                let mut files = self.files.lock().unwrap();
+                files.fetch_local_files(&*self.local, show_hidden_files)?;
+                files.fetch_remote_files(&*self.remote, show_hidden_files)?;
+            }
+            KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                let excludes = self.filter.get_excludes().join(" ");
+                self.prompt = Some((
+                    PromptAction::SetExcludes,
+                    Prompt::new("Exclude patterns", &excludes),
+                ));
+            }
+            KeyEvent {
+                code: KeyCode::Char('X'),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => {
+                let use_ignore_files = !self.filter.uses_ignore_files();
+                self.filter.set_use_ignore_files(use_ignore_files);
+                self.user_message.report(&format!(
+                    "{} .gitignore and .rftpignore files.",
+                    if use_ignore_files {
+                        "Respect"
+                    } else {
+                        "Ignore"
+                    }
+                ));
+            }
+            KeyEvent {
+                code: KeyCode::Char('?'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.user_message.report(&format!(
+                    "Controls for rftp version {}.
+                    ------------------------------------------------------------
+                    h/j/k/l       Navigate the files.
+                    Enter         Enter the selected directory.
+                    Spacebar      Download/Upload the selected file/directory.
+                                  Directories and overwrites are confirmed first.
+                    c/m           Copy/move the selected entry on the same side.
+                    O             Connect the selected pane to another host.
+                    t             Open a tab connected to another host.
+                    Tab           Show the next tab, Shift+Tab the previous one.
+                    w             Close the shown tab.
+                    Mouse         Click to select, double-click to enter a directory.
+                                  Click a progress bar to cancel its transfer.
+                    z             Show/hide hidden files.
+                    x             Edit the patterns of entries to exclude.
+                    X             Respect/ignore .gitignore and .rftpignore files.
+                    e             Edit the selected remote file in $EDITOR.
+                    !             Run a command in the remote directory.
+                    S             Open a shell in the remote directory.
+                    v             Preview the selected file.
+                                  x: hexdump  F: follow a growing file
+                    d             Diff the selected file with the other side.
+                    D             Diff the selected file with a chosen file.
+                    =             Compare the files with the other side.
+                    T             Copy what differs to the other side.
+                    C             Compare the directories recursively.
+                    p             Show/change the mode and owner of an entry.
+                    L             Show the log of all messages.
+                    q             Quit.
+                    Q             Force quit.
+                    ?             Print this help message.",
+                    clap::crate_version!()
+                ));
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    /// Work that is done on every mouse event.
+    pub fn on_mouse(&mut self, event: MouseEvent) -> Result<()> {
+        if self.prompt.is_some() || self.properties.is_some() {
+            return Ok(());
+        }
+        if let Some((_, dialog)) = self.dialog.as_mut() {
+            // The scroll wheel scrolls the dialog, everything else is left to the keys.
+            let code = match event.kind {
+                MouseEventKind::ScrollDown => KeyCode::Char('j'),
+                MouseEventKind::ScrollUp => KeyCode::Char('k'),
+                _ => return Ok(()),
+            };
+            dialog.on_event(KeyEvent::new(code, KeyModifiers::NONE));
+            return Ok(());
+        }
+
+        if let Some(preview) = self.preview.as_mut() {
+            match event.kind {
+                MouseEventKind::ScrollDown => preview.scroll(1),
+                MouseEventKind::ScrollUp => preview.scroll(-1),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if let Some(command_pane) = self.command_pane.as_mut() {
+            match event.kind {
+                MouseEventKind::ScrollDown => command_pane.scroll(1),
+                MouseEventKind::ScrollUp => command_pane.scroll(-1),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if let Some(diff_pane) = self.diff_pane.as_mut() {
+            match event.kind {
+                MouseEventKind::ScrollDown => diff_pane.scroll(1),
+                MouseEventKind::ScrollUp => diff_pane.scroll(-1),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        let (column, row) = (event.column, event.row);
+        if let Some(log_pane) = self.log_pane.as_mut() {
+            let rect = self.log_rect.get();
+            let is_inside =
+                rect.x <= column && column < rect.right() && rect.y <= row && row < rect.bottom();
+            if is_inside {
+                match event.kind {
+                    MouseEventKind::ScrollDown => log_pane.scroll(1, &self.user_message),
+                    MouseEventKind::ScrollUp => log_pane.scroll(-1, &self.user_message),
+                    _ => {}
+                }
+                return Ok(());
+            }
+        }
+
+        let file_list_rect = self.file_list_rect.get();
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let is_double_click = self.last_click.is_some_and(|(time, c, r)| {
+                    time.elapsed() < DOUBLE_CLICK_TIME && (c, r) == (column, row)
+                });
+                self.last_click = Some((Instant::now(), column, row));
+
+                if let Some(progress) = self
+                    .progress_bars
+                    .lock()
+                    .unwrap()
+                    .get_at(self.progress_rect.get(), row)
+                {
+                    let dialog = Dialog::new(
+                        &format!("Cancel {}?", progress.get_title()),
+                        vec![],
+                        "Files that were partially transferred are removed.",
+                    );
+                    self.dialog = Some((DialogAction::Cancel(progress), dialog));
+                    return Ok(());
+                }
+
+                let is_selected = self
+                    .files
+                    .lock()
+                    .unwrap()
+                    .select_at(file_list_rect, column, row);
+                if is_selected && is_double_click {
+                    self.last_click = None;
+                    self.enter_selected()?;
+                }
+            }
+            MouseEventKind::ScrollDown => {
+                let mut files = self.files.lock().unwrap();
+                files.scroll_at(file_list_rect, column, 1);
+            }
+            MouseEventKind::ScrollUp => {
+                let mut files = self.files.lock().unwrap();
+                files.scroll_at(file_list_rect, column, -1);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Return the work that needs the terminal, if any.
+    ///
+    /// The caller suspends the interface and then calls `run_suspended_task`.
+    pub fn take_suspended_task(&mut self) -> Option<SuspendedTask> {
+        self.suspended_task.take()
+    }
+
+    /// Do `task` with the terminal while the interface is suspended.
+    pub fn run_suspended_task(&mut self, task: SuspendedTask) {
+        match task {
+            SuspendedTask::Edit(edit) => {
+                let result = edit.run_editor();
+                self.finish_edit(edit, result);
+            }
+            SuspendedTask::Connect {
+                destination,
+                is_remote,
+            } => {
+                if let Err(error) = self.connect_pane(&destination, is_remote) {
+                    self.user_message.error(&format!("Error: {}.", error));
+                }
+            }
+            SuspendedTask::OpenTab(destination) => {
+                if let Err(error) = self.open_tab(&destination) {
+                    self.user_message.error(&format!("Error: {}.", error));
+                }
+            }
+            SuspendedTask::Shell => {
+                let session = self.session.as_ref().unwrap().session();
+                let cwd = self
+                    .files
+                    .lock()
+                    .unwrap()
+                    .get_remote_working_path()
+                    .to_path_buf();
+                match run_shell(&session, &cwd) {
+                    Ok(status) => self
+                        .user_message
+                        .report(&format!("The shell exited with status {}.", status)),
+                    Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+                }
+                // The shell may have changed the files of the working directory.
+                let result = self.files.lock().unwrap().fetch_remote_files(
+                    &*self.remote,
+                    self.show_hidden_files.load(Ordering::Relaxed),
+                );
+                if let Err(error) = result {
+                    self.user_message.error(&format!("Error: {}.", error));
+                }
+            }
+        }
+    }
+
+    /// Run `command` in the remote working directory and show its output in a pane.
+    fn run_command(&mut self, command: &str) {
+        let session = match &self.session {
+            Some(connection) => connection.session(),
+            None => {
+                self.user_message
+                    .error("Commands can only be run when connected to a server.");
+                return;
+            }
+        };
+        let cwd = self
+            .files
+            .lock()
+            .unwrap()
+            .get_remote_working_path()
+            .to_string_lossy()
+            .to_string();
+        let remote_command = format!("cd {} && {}", shell_quote(&cwd), command);
+        self.command_pane = Some(CommandPane::spawn(command, move || {
+            run_remote_command_output(&session, &remote_command)
+        }));
+    }
+
+    /// Connect to `destination` written as `[user@]host[:port]` and return the host with the
+    /// connection.
+    fn open_session(&self, destination: &str) -> Result<(String, Connection)> {
+        let mut options = self.connect_options.clone();
+        let host = parse_destination(destination, &mut options)?;
+        let connection = Connection::connect(&host, options)?;
+        self.report_reconnects(&connection);
+        Ok((host, connection))
+    }
+
+    /// Report when `connection` was lost and connected again, or could not be.
+    fn report_reconnects(&self, connection: &Connection) {
+        let user_message = Arc::clone(&self.user_message);
+        let host = connection.host().to_string();
+        connection.set_on_reconnect(move |result| match result {
+            Ok(()) => user_message.report(&format!(
+                "The connection to {} was lost and is restored.",
+                host
+            )),
+            Err(error) => user_message.error(&format!(
+                "Error: unable to connect to {} again: {}.",
+                host, error
+            )),
+        });
+    }
+
+    /// Open a tab that shows the current directory of this machine next to the home
+    /// directory of `destination`, written as `[user@]host[:port]`.
+    fn open_tab(&mut self, destination: &str) -> Result<()> {
+        let (host, session) = self.open_session(destination)?;
+        let home = get_remote_home_dir(&session.session()).unwrap_or_else(|_| PathBuf::from("./"));
+        let mut tab = Tab::new(
+            &host,
+            Arc::new(LocalFileSystem),
+            std::env::current_dir()?,
+            Arc::new(session.clone()),
+            home,
+            self.show_hidden_files.load(Ordering::Relaxed),
+        )?;
+        tab.session = Some(session);
+        self.add_tab(tab);
+        self.user_message
+            .report(&format!("Opened a tab connected to {}.", host));
+        Ok(())
+    }
+
+    /// Show `tab` after all other tabs.
+    fn add_tab(&mut self, mut tab: Tab) {
+        self.swap_tab(&mut tab);
+        self.tabs.insert(self.tab_index, tab);
+        self.tab_index = self.tabs.len();
+    }
+
+    /// Show the tab at `index` among all tabs instead of the shown one.
+    fn show_tab(&mut self, index: usize) {
+        if index == self.tab_index {
+            return;
+        }
+        let mut tab = self.tabs.remove(if index < self.tab_index {
+            index
+        } else {
+            index - 1
+        });
+        self.swap_tab(&mut tab);
+        // The hidden tab goes where it was among all tabs without the shown one.
+        self.tabs.insert(
+            if self.tab_index < index {
+                self.tab_index
+            } else {
+                self.tab_index - 1
+            },
+            tab,
+        );
+        self.tab_index = index;
+        // The files may have changed, or hidden files may have been toggled meanwhile.
+        self.refresh_side(false);
+        self.refresh_side(true);
+    }
+
+    /// Close the shown tab and show the one after it, or the one before if it was the last.
+    fn close_tab(&mut self) {
+        if self.tabs.is_empty() {
+            self.user_message
+                .error("This is the only tab. Press q to quit.");
+            return;
+        }
+        if Arc::strong_count(&self.transfers) > 1 {
+            self.user_message
+                .warn("Wait for the downloads/uploads of this tab to finish before closing it.");
+            return;
+        }
+        let index = self.tab_index.min(self.tabs.len() - 1);
+        let mut tab = self.tabs.remove(index);
+        self.swap_tab(&mut tab);
+        self.tab_index = index;
+        self.refresh_side(false);
+        self.refresh_side(true);
+        self.user_message
+            .report(&format!("Closed the tab of {}.", tab.title));
+    }
+
+    /// Exchange what is shown with the connection of `tab`.
+    fn swap_tab(&mut self, tab: &mut Tab) {
+        std::mem::swap(&mut self.tab_title, &mut tab.title);
+        std::mem::swap(&mut self.session, &mut tab.session);
+        std::mem::swap(&mut self.local_session, &mut tab.local_session);
+        std::mem::swap(&mut self.local, &mut tab.local);
+        std::mem::swap(&mut self.remote, &mut tab.remote);
+        std::mem::swap(&mut self.files, &mut tab.files);
+        std::mem::swap(&mut self.transfers, &mut tab.transfers);
+        std::mem::swap(&mut self.planned_transfers, &mut tab.planned_transfers);
+    }
+
+    /// Connect the right pane if `is_remote`, or the left pane otherwise, to `destination`
+    /// written as `[user@]host[:port]` and show its home directory.
+    fn connect_pane(&mut self, destination: &str, is_remote: bool) -> Result<()> {
+        let (host, session) = self.open_session(destination)?;
+        let host = host.as_str();
+        let home = get_remote_home_dir(&session.session()).unwrap_or_else(|_| PathBuf::from("./"));
+        let show_hidden_files = self.show_hidden_files.load(Ordering::Relaxed);
+
+        let mut files = self.files.lock().unwrap();
+        if is_remote {
+            let remote: Arc<RemoteFs> = Arc::new(session.clone());
+            files.set_remote_working_path(&home, &*remote, show_hidden_files)?;
+            files.set_remote_title(host);
+            self.remote = remote;
+            self.session = Some(session);
+            self.tab_title = host.to_string();
+        } else {
+            let local: Arc<LocalFs> = Arc::new(Relabeled::new(session.clone()));
+            files.set_local_working_path(&home, &*local, show_hidden_files)?;
+            files.set_local_title(host);
+            self.local = local;
+            self.local_session = Some(session);
+        }
+        drop(files);
+        self.user_message.report(&format!(
+            "Connected the {} pane to {}.",
+            if is_remote { "right" } else { "left" },
+            host
+        ));
+        Ok(())
+    }
+
+    /// Upload the file of `edit` if the editor exited with `result` and changed it.
+    pub fn finish_edit(&mut self, edit: EditSession, result: Result<()>) {
+        match result {
+            Ok(()) => self.upload_edit(edit, false),
+            Err(error) => {
+                self.user_message.error(&format!("Error: {}.", error));
+                edit.remove_local();
+            }
+        }
+    }
+
+    /// Replace the remote file of `edit` with the edited copy, unless the remote file
+    /// changed meanwhile and `force` is false.
+    fn upload_edit(&mut self, edit: EditSession, force: bool) {
+        match edit.finish(&*self.remote, force) {
+            Ok(EditResult::Unchanged) => {
+                self.user_message
+                    .report(&format!("{:?} was not changed.", edit.remote_path()));
+                edit.remove_local();
+            }
+            Ok(EditResult::Uploaded) => {
+                self.user_message
+                    .report(&format!("Uploaded {:?}.", edit.remote_path()));
+                edit.remove_local();
+                let result = self.files.lock().unwrap().fetch_remote_files(
+                    &*self.remote,
+                    self.show_hidden_files.load(Ordering::Relaxed),
+                );
+                if let Err(error) = result {
+                    self.user_message.error(&format!("Error: {}.", error));
+                }
+            }
+            Ok(EditResult::Conflict) => {
+                let dialog = Dialog::new(
+                    &format!("Overwrite {:?}?", edit.remote_path()),
+                    vec!["The file changed on the server while it was edited.".to_string()],
+                    "Replace it with the edited copy?",
+                );
+                self.dialog = Some((DialogAction::OverwriteEdit(edit), dialog));
+            }
+            Err(error) => {
+                self.user_message.error(&format!(
+                    "Error: {}. The edited copy is kept in {:?}.",
+                    error,
+                    edit.local_path()
+                ));
+            }
+        }
+    }
+
+    /// Show the selected file in a preview.
+    fn open_preview(&mut self) {
+        let files = self.files.lock().unwrap();
+        let entry_name = match files.get_selected_entry() {
+            SelectedFileEntry::Local(entry) if !entry.is_dir() => {
+                self.preview = Some(Preview::open(Arc::clone(&self.local), &entry));
+                return;
+            }
+            SelectedFileEntry::Remote(entry) if !entry.is_dir() => {
+                self.preview = Some(Preview::open(Arc::clone(&self.remote), &entry));
+                return;
+            }
+            SelectedFileEntry::Local(entry) => entry.file_name_lossy().unwrap().to_string(),
+            SelectedFileEntry::Remote(entry) => entry.file_name_lossy().unwrap().to_string(),
+            SelectedFileEntry::None => {
+                drop(files);
+                self.user_message.error("No file selected.");
+                return;
+            }
+        };
+        drop(files);
+        self.user_message.error(&format!(
+            "Error: Cannot preview \"{}\" because it is a directory!",
+            entry_name
+        ));
+    }
+
+    /// Return the path that the user wrote for the left pane, where `~` is only the home
+    /// directory if the pane shows this machine.
+    fn local_input_path(&self, input: &str) -> PathBuf {
+        if self.local.is_remote() {
+            PathBuf::from(input)
+        } else {
+            expand_tilde(input)
+        }
+    }
+
+    /// Ask where to copy or move the selected entry on its own side.
+    fn open_copy_prompt(&mut self, is_move: bool) {
+        let (path, is_remote) = match self.files.lock().unwrap().get_selected_entry() {
+            SelectedFileEntry::Local(entry) if !entry.is_parent() => {
+                (entry.path().to_path_buf(), false)
+            }
+            SelectedFileEntry::Remote(entry) if !entry.is_parent() => {
+                (entry.path().to_path_buf(), true)
+            }
+            _ => {
+                self.user_message.error("No file selected.");
+                return;
+            }
+        };
+        let title = format!("{} {:?} to", if is_move { "Move" } else { "Copy" }, path);
+        let text = path.to_string_lossy().to_string();
+        self.prompt = Some((
+            PromptAction::CopyWithin {
+                path,
+                is_remote,
+                is_move,
+            },
+            Prompt::new(&title, &text),
+        ));
+    }
+
+    /// Copy or move the entry at `path` to `dest` on the same side.
+    ///
+    /// Relative destinations are in the working directory, and entries that are copied or
+    /// moved into a directory keep their name. Remote copies are done on the server if it
+    /// can, and are streamed through this machine otherwise.
+    fn copy_within(&mut self, path: &Path, is_remote: bool, is_move: bool, dest: &str) {
+        if dest.is_empty() {
+            return;
+        }
+        let result = if is_remote {
+            let cwd = self
+                .files
+                .lock()
+                .unwrap()
+                .get_remote_working_path()
+                .to_path_buf();
+            resolve_destination(&*self.remote, &cwd, path, Path::new(dest))
+        } else {
+            let cwd = self
+                .files
+                .lock()
+                .unwrap()
+                .get_local_working_path()
+                .to_path_buf();
+            resolve_destination(&*self.local, &cwd, path, &self.local_input_path(dest))
+        };
+        let dest = match result {
+            Ok(dest) => dest,
+            Err(error) => {
+                self.user_message.error(&format!("Error: {}.", error));
+                return;
+            }
+        };
+
+        if is_move {
+            let result = if is_remote {
+                self.remote.rename(path, &dest)
+            } else {
+                self.local.rename(path, &dest)
+            };
+            match result {
+                Ok(()) => {
+                    self.user_message
+                        .report(&format!("Moved {:?} to {:?}.", path, dest));
+                    self.refresh_side(is_remote);
+                }
+                Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+            }
+        } else if is_remote {
+            let session = self.session.as_ref().map(Connection::session);
+            self.spawn_copy(Arc::clone(&self.remote), session, path, &dest, true);
+        } else {
+            let session = self.local_session.as_ref().map(Connection::session);
+            self.spawn_copy(Arc::clone(&self.local), session, path, &dest, false);
+        }
+    }
+
+    /// Spawn a task that copies `from` to `to` on `fs`, with `cp` on the server of `session`
+    /// if there is one, then fetch the files of the remote pane again if `is_remote`, or of the
+    /// local pane otherwise.
+    fn spawn_copy<F>(
+        &self,
+        fs: Arc<F>,
+        session: Option<ssh2::Session>,
+        from: &Path,
+        to: &Path,
+        is_remote: bool,
+    ) where
+        F: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        let transfers = Arc::clone(&self.transfers);
+        let local = Arc::clone(&self.local);
+        let remote = Arc::clone(&self.remote);
+        let user_message = Arc::clone(&self.user_message);
+        let show_hidden_files = Arc::clone(&self.show_hidden_files);
+        let files = Arc::clone(&self.files);
+        let title = format!("Copying {:?} to {:?}", from, to);
+        user_message.report(&format!("{}...", title));
+        // Shown until the copy is done, so that quitting and closing the tab wait for it.
+        let progress = Arc::new(ProgressDirectory::new(&format!(
+            "Copying \"{}\"",
+            from.display()
+        )));
+        self.progress_bars
+            .lock()
+            .unwrap()
+            .push_directory_progress(Arc::clone(&progress));
+
+        thread::spawn(move || {
+            let copy = || -> Result<()> {
+                if let Some(session) = &session {
+                    match remote_copy(session, &from, &to) {
+                        Ok(true) => return Ok(()),
+                        // Servers that cannot run commands still allow streaming.
+                        Ok(false) | Err(ErrorKind::Ssh2Error(_)) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+                fs.copy_all(&from, &to)
+            };
+            let (severity, message) = match copy() {
+                Ok(()) => (Severity::Info, format!("Copied {:?} to {:?}.", from, to)),
+                Err(error) => (Severity::Error, format!("Error: {}.", error)),
+            };
+            user_message.log(severity, Some(&title), &message);
+            progress.finish();
+            drop(transfers);
+
+            let show_hidden_files = show_hidden_files.load(Ordering::Relaxed);
+            let mut files = files.lock().unwrap();
+            let result = if is_remote {
+                files.fetch_remote_files(&*remote, show_hidden_files)
+            } else {
+                files.fetch_local_files(&*local, show_hidden_files)
+            };
+            if let Err(error) = result {
+                user_message.error(&format!("Error: {}.", error));
+            }
+        });
+    }
+
+    /// Fetch the remote files again if `is_remote`, or the local files otherwise.
+    fn refresh_side(&self, is_remote: bool) {
+        let show_hidden_files = self.show_hidden_files.load(Ordering::Relaxed);
+        let mut files = self.files.lock().unwrap();
+        let result = if is_remote {
+            files.fetch_remote_files(&*self.remote, show_hidden_files)
+        } else {
+            files.fetch_local_files(&*self.local, show_hidden_files)
+        };
+        if let Err(error) = result {
+            self.user_message.error(&format!("Error: {}.", error));
+        }
+    }
+
+    /// Return the path of the selected file and whether it is remote, or report why there
+    /// is none.
+    fn get_selected_file(&self) -> Option<(PathBuf, bool)> {
+        let files = self.files.lock().unwrap();
+        let (path, is_remote, is_dir) = match files.get_selected_entry() {
+            SelectedFileEntry::Local(entry) => (entry.path().to_path_buf(), false, entry.is_dir()),
+            SelectedFileEntry::Remote(entry) => (entry.path().to_path_buf(), true, entry.is_dir()),
+            SelectedFileEntry::None => {
+                drop(files);
+                self.user_message.error("No file selected.");
+                return None;
+            }
+        };
+        drop(files);
+        if is_dir {
+            self.user_message.error(&format!(
+                "Error: Cannot diff {:?} because it is a directory!",
+                path
+            ));
+            return None;
+        }
+        Some((path, is_remote))
+    }
+
+    /// Return the path of the file with the same name as `path` in the working directory of
+    /// the other side.
+    fn get_counterpart(&self, path: &Path, is_remote: bool) -> PathBuf {
+        let files = self.files.lock().unwrap();
+        let dir = if is_remote {
+            files.get_local_working_path()
+        } else {
+            files.get_remote_working_path()
+        };
+        dir.join(path.file_name().unwrap_or_default())
+    }
+
+    /// Diff the selected file with the file of the same name on the other side.
+    fn diff_selected(&mut self) {
+        let (path, is_remote) = match self.get_selected_file() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let other = self.get_counterpart(&path, is_remote);
+        let exists = if is_remote {
+            matches!(self.local.stat(&other), Ok(Some(_)))
+        } else {
+            matches!(self.remote.stat(&other), Ok(Some(_)))
+        };
+        if !exists {
+            self.user_message.error(&format!(
+                "Error: There is no {:?} on the other side. Press D to choose one.",
+                other
+            ));
+            return;
+        }
+        self.diff_with(&path, is_remote, &other.to_string_lossy());
+    }
+
+    /// Ask for the file on the other side to diff the selected file with.
+    fn open_diff_prompt(&mut self) {
+        let (path, is_remote) = match self.get_selected_file() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let other = self.get_counterpart(&path, is_remote);
+        let title = format!(
+            "Diff {:?} with the {} file",
+            path,
+            if is_remote { "local" } else { "remote" }
+        );
+        self.prompt = Some((
+            PromptAction::DiffWith { path, is_remote },
+            Prompt::new(&title, &other.to_string_lossy()),
+        ));
+    }
+
+    /// Show how the file at `path` differs from the file `other` on the other side.
+    ///
+    /// The local file is always shown as the old one.
+    fn diff_with(&mut self, path: &Path, is_remote: bool, other: &str) {
+        if other.is_empty() {
+            return;
+        }
+        let (local_path, remote_path) = if is_remote {
+            (self.local_input_path(other), path.to_path_buf())
+        } else {
+            (path.to_path_buf(), PathBuf::from(other))
+        };
+        self.diff_pane = Some(DiffPane::open(
+            Arc::clone(&self.local),
+            &local_path,
+            Arc::clone(&self.remote),
+            &remote_path,
+        ));
+    }
+
+    /// Plan copying every entry on the selected side that differs from the other side, or
+    /// that only exists on the selected side, to the other side.
+    fn transfer_differences(&mut self) {
+        let files = self.files.lock().unwrap();
+        let by = files.get_compare().unwrap_or(CompareBy::SizeAndTime);
+        let (local, remote) = (Arc::clone(&self.local), Arc::clone(&self.remote));
+        match files.get_selected_entry() {
+            SelectedFileEntry::Local(_) => {
+                let sources = files.get_differing_local_entries(by);
+                let source = LocalFileEntry::Directory(files.get_local_working_path().into());
+                let dest = files.get_remote_working_path().to_path_buf();
+                drop(files);
+                if sources.is_empty() {
+                    self.user_message
+                        .report("There are no differences to upload.");
+                } else {
+                    self.spawn_plan_all(
+                        local,
+                        source,
+                        sources,
+                        remote,
+                        dest,
+                        PlannedTransfer::Upload,
+                    );
+                }
+            }
+            SelectedFileEntry::Remote(_) => {
+                let sources = files.get_differing_remote_entries(by);
+                let source = RemoteFileEntry::Directory(files.get_remote_working_path().into());
+                let dest = files.get_local_working_path().to_path_buf();
+                drop(files);
+                if sources.is_empty() {
+                    self.user_message
+                        .report("There are no differences to download.");
+                } else {
+                    self.spawn_plan_all(
+                        remote,
+                        source,
+                        sources,
+                        local,
+                        dest,
+                        PlannedTransfer::Download,
+                    );
+                }
+            }
+            SelectedFileEntry::None => {
+                drop(files);
+                self.user_message.error("No file selected.");
+            }
+        }
+    }
+
+    /// Spawn a task that compares the local and remote directories and everything below them,
+    /// and reports how many entries differ.
+    fn spawn_compare_trees(&mut self) {
+        let files = self.files.lock().unwrap();
+        let by = files.get_compare().unwrap_or(CompareBy::SizeAndTime);
+        let local_path = files.get_local_working_path().to_path_buf();
+        let remote_path = files.get_remote_working_path().to_path_buf();
+        drop(files);
+        let (local, remote) = (Arc::clone(&self.local), Arc::clone(&self.remote));
+        let user_message = Arc::clone(&self.user_message);
+        let title = format!("Comparing {:?} with {:?}", local_path, remote_path);
+        user_message.report(&format!("{}...", title));
+
+        thread::spawn(move || {
+            let result = compare_trees(&*local, &local_path, &*remote, &remote_path, by);
+            let (severity, message) = match result {
+                Ok(summary) if summary.is_same() => (
+                    Severity::Info,
+                    format!(
+                        "The directories are the same, {} files in total.",
+                        summary.same
+                    ),
+                ),
+                Ok(summary) => (
+                    Severity::Warning,
+                    format!(
+                        "{} only local, {} only remote, {} different and {} same entries.",
+                        summary.only_left, summary.only_right, summary.different, summary.same
+                    ),
+                ),
+                Err(error) => (Severity::Error, format!("Error: {}.", error)),
+            };
+            user_message.log(severity, Some(&title), &message);
+        });
+    }
+
+    /// Show the properties of the selected entry.
+    fn open_properties(&mut self) {
+        let (path, is_remote, is_dir) = match self.files.lock().unwrap().get_selected_entry() {
+            SelectedFileEntry::Local(entry) => (entry.path().to_path_buf(), false, entry.is_dir()),
+            SelectedFileEntry::Remote(entry) => (entry.path().to_path_buf(), true, entry.is_dir()),
+            SelectedFileEntry::None => {
+                self.user_message.error("No file selected.");
+                return;
+            }
+        };
+        match self.load_properties(&path, is_remote) {
+            Ok(properties) => {
+                self.properties = Some(PropertiesPane::new(&path, is_remote, is_dir, properties));
+            }
+            Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+        }
+    }
+
+    /// Return the properties of `path` with the names of its owner and group.
+    fn load_properties(&self, path: &Path, is_remote: bool) -> Result<Properties> {
+        if is_remote {
+            let mut properties = self.remote.properties(path)?;
+            self.remote.find_owner_names(&mut properties);
+            Ok(properties)
+        } else {
+            let mut properties = self.local.properties(path)?;
+            self.local.find_owner_names(&mut properties);
+            Ok(properties)
+        }
+    }
+
+    /// Show the properties of the entry in the properties pane again after they changed.
+    fn reload_properties(&mut self) {
+        if let Some(pane) = &self.properties {
+            match self.load_properties(pane.path(), pane.is_remote()) {
+                Ok(properties) => self.properties.as_mut().unwrap().set_properties(properties),
+                Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+            }
+        }
+    }
+
+    /// Set the mode of the entry in the properties pane to the octal `input`, and of
+    /// everything below it if `recursive`.
+    fn change_mode(&mut self, input: &str, recursive: bool) {
+        let mode = match parse_mode(input) {
+            Some(mode) => mode,
+            None => {
+                self.user_message.error(&format!(
+                    "Error: \"{}\" is not an octal mode like 644.",
+                    input.trim()
+                ));
+                return;
+            }
+        };
+        let pane = match &self.properties {
+            Some(pane) => pane,
+            None => return,
+        };
+        let path = pane.path().to_path_buf();
+        let result = match (pane.is_remote(), recursive) {
+            (true, false) => self.remote.set_mode(&path, mode),
+            (true, true) => self.remote.set_mode_all(&path, mode),
+            (false, false) => self.local.set_mode(&path, mode),
+            (false, true) => self.local.set_mode_all(&path, mode),
+        };
+        match result {
+            Ok(()) => {
+                self.user_message.report(&format!(
+                    "Changed the mode of {:?}{} to {:04o}.",
+                    path,
+                    if recursive {
+                        " and everything in it"
+                    } else {
+                        ""
+                    },
+                    mode
+                ));
+                self.reload_properties();
+            }
+            Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+        }
+    }
+
+    /// Set the owner and group of the remote entry in the properties pane to `input`, which
+    /// is written like `uid:gid`.
+    fn change_owner(&mut self, input: &str) {
+        let (uid, gid) = match parse_owner(input) {
+            Some(owner) => owner,
+            None => {
+                self.user_message.error(&format!(
+                    "Error: \"{}\" is not an owner like 1000:1000.",
+                    input.trim()
+                ));
+                return;
+            }
+        };
+        let path = match &self.properties {
+            Some(pane) if pane.is_remote() => pane.path().to_path_buf(),
+            _ => return,
+        };
+        match self.remote.set_owner(&path, uid, gid) {
+            Ok(()) => {
+                self.user_message.report(&format!(
+                    "Changed the owner of {:?} to {}.",
+                    path,
+                    input.trim()
+                ));
+                self.reload_properties();
+            }
+            Err(error) => self.user_message.error(&format!("Error: {}.", error)),
+        }
+    }
+
+    /// Enter the selected directory.
+    fn enter_selected(&mut self) -> Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let show_hidden_files = self.show_hidden_files.load(Ordering::Relaxed);
+        let entry_name = match files.get_selected_entry() {
+            SelectedFileEntry::Local(entry) if entry.is_dir() => {
+                return files.set_local_working_path(entry.path(), &*self.local, show_hidden_files);
+            }
+            SelectedFileEntry::Remote(entry) if entry.is_dir() => {
+                return files.set_remote_working_path(
+                    entry.path(),
+                    &*self.remote,
+                    show_hidden_files,
+                );
+            }
+            SelectedFileEntry::Local(entry) => entry.file_name_lossy().unwrap().to_string(),
+            SelectedFileEntry::Remote(entry) => entry.file_name_lossy().unwrap().to_string(),
+            SelectedFileEntry::None => {
+                drop(files);
+                self.user_message.error("No directory selected.");
+                return Ok(());
+            }
+        };
+        drop(files);
+        self.user_message.error(&format!(
+            "Error: Cannot enter \"{}\" because it is not a directory!",
+            entry_name
+        ));
+        Ok(())
+    }
+
+    /// Spawn a task to plan copying `source` of `source_fs` into the directory `dest` of
+    /// `dest_fs` without changing anything. The plan is wrapped by `to_plan` and picked up
+    /// by `tick`.
+    fn spawn_plan<S, D>(
+        &mut self,
+        source_fs: Arc<S>,
+        source: S::Entry,
+        dest_fs: Arc<D>,
+        dest: PathBuf,
+        to_plan: fn(S::Entry, Vec<TransferAction>) -> PlannedTransfer,
+    ) where
+        S: FileSystem + Send + Sync + ?Sized + 'static,
+        S::Entry: Send + 'static,
+        D: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let sources = vec![source.clone()];
+        self.spawn_plan_all(source_fs, source, sources, dest_fs, dest, to_plan);
+    }
+
+    /// Spawn a task to plan copying each of `sources` of `source_fs` into the directory `dest`
+    /// of `dest_fs` as a single transfer of `source`, which is shown to the user.
+    fn spawn_plan_all<S, D>(
+        &mut self,
+        source_fs: Arc<S>,
+        source: S::Entry,
+        sources: Vec<S::Entry>,
+        dest_fs: Arc<D>,
+        dest: PathBuf,
+        to_plan: fn(S::Entry, Vec<TransferAction>) -> PlannedTransfer,
+    ) where
+        S: FileSystem + Send + Sync + ?Sized + 'static,
+        S::Entry: Send + 'static,
+        D: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let user_message = Arc::clone(&self.user_message);
+        let sender = self.planned_transfers.0.clone();
+        let filter = self.filter.clone();
+        let title = format!(
+            "{} \"{}\"",
+            transfer_verb(&*source_fs, &*dest_fs),
+            source.file_name_lossy().unwrap_or_default()
+        );
+
+        thread::spawn(move || {
+            let mut actions = vec![];
+            for entry in sources {
+                let dest = dest.join(entry.path().file_name().unwrap_or_default());
+                match plan_transfer(&*source_fs, entry, &*dest_fs, dest, &filter) {
+                    Ok(entry_actions) => actions.extend(entry_actions),
+                    Err(error) => {
+                        let message = format!("Error: {}.", error);
+                        user_message.log(Severity::Error, Some(&title), &message);
+                        return;
+                    }
+                }
+            }
+            sender.send(to_plan(source, actions)).unwrap();
+        });
+    }
+
+    /// Spawn a task that carries out `plan`.
+    fn spawn_transfer(&mut self, plan: PlannedTransfer) {
+        let local = Arc::clone(&self.local);
+        let remote = Arc::clone(&self.remote);
+        match plan {
+            PlannedTransfer::Upload(source, actions) => {
+                self.spawn_apply(local, source, remote, actions)
+            }
+            PlannedTransfer::Download(source, actions) => {
+                self.spawn_apply(remote, source, local, actions)
+            }
+        }
+    }
+
+    /// Spawn a task to copy `source` of `source_fs` to `dest_fs` as planned by `actions`,
+    /// then fetch the files of `dest_fs` again.
+    fn spawn_apply<S, D>(
+        &mut self,
+        source_fs: Arc<S>,
+        source: S::Entry,
+        dest_fs: Arc<D>,
+        actions: Vec<TransferAction>,
+    ) where
+        S: FileSystem + Send + Sync + ?Sized + 'static,
+        D: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let local = Arc::clone(&self.local);
+        let remote = Arc::clone(&self.remote);
+        let user_message = Arc::clone(&self.user_message);
+        let show_hidden_files = Arc::clone(&self.show_hidden_files);
+        let files = Arc::clone(&self.files);
+        let source_filename = source.file_name_lossy().unwrap_or_default().to_string();
+        let verb = transfer_verb(&*source_fs, &*dest_fs);
+        let listener = self.create_transfer_progress(&source, verb);
+
+        thread::spawn(move || {
+            let (severity, message) =
+                match apply_transfer(&*source_fs, &*dest_fs, &actions, &listener) {
+                    Ok(()) => (
+                        Severity::Info,
+                        format!("Finished {} \"{}\".", verb.to_lowercase(), source_filename),
+                    ),
+                    Err(ErrorKind::Cancelled) => (
+                        Severity::Warning,
+                        format!("Cancelled {} \"{}\".", verb.to_lowercase(), source_filename),
+                    ),
+                    Err(error) => (Severity::Error, format!("Error: {}.", error)),
+                };
+            user_message.log(severity, Some(&listener.title), &message);
+
+            if let Some(p) = listener.directory_progress {
+                p.finish()
+            }
+
+            let show_hidden_files = show_hidden_files.load(Ordering::Relaxed);
+            let mut files = files.lock().unwrap();
+            let result = if dest_fs.is_remote() {
+                files.fetch_remote_files(&*remote, show_hidden_files)
+            } else {
+                files.fetch_local_files(&*local, show_hidden_files)
+            };
+            if let Err(error) = result {
+                user_message.error(&format!("Error: {}.", error));
+            }
+        });
+    }
+
+    /// Create the listener that shows the progress of transferring `source`.
+    ///
+    /// If `source` is a directory, a directory progress bar is shown as well.
+    fn create_transfer_progress(&self, source: &impl FileEntry, verb: &str) -> TransferProgress {
+        let directory_progress = if source.is_dir() {
+            let progress = {
+                let title = format!("{} \"{}\"", verb, source.path().display());
+                Arc::new(ProgressDirectory::new(&title))
+            };
+            self.progress_bars
+                .lock()
+                .unwrap()
+                .push_directory_progress(Arc::clone(&progress));
+            Some(progress)
+        } else {
+            None
+        };
+        TransferProgress {
+            title: format!(
+                "{} \"{}\"",
+                verb,
+                source.file_name_lossy().unwrap_or_default()
+            ),
+            progress_bars: Arc::clone(&self.progress_bars),
+            user_message: Arc::clone(&self.user_message),
+            directory_progress,
+            _transfers: Arc::clone(&self.transfers),
+        }
+    }
+
+    /// Return the titles of all tabs with the shown one highlighted.
+    fn get_tab_bar(&self) -> Tabs<'_> {
+        let mut titles: Vec<Spans> = self
+            .tabs
+            .iter()
+            .map(|tab| Spans::from(tab.title.as_str()))
+            .collect();
+        titles.insert(self.tab_index, Spans::from(self.tab_title.as_str()));
+        Tabs::new(titles)
+            .select(self.tab_index)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    }
+
+    /// Return true if the user has not quit.
+    pub fn is_alive(&self) -> bool {
+        self.is_alive
+    }
+
+    /// Draw the current state.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>)
+    where
+        B: tui::backend::Backend,
+    {
+        let rect = frame.size();
+        let rect = match &self.prompt {
+            Some((_, prompt)) => prompt.draw(frame, rect),
+            None => rect,
+        };
+        let rect = match &self.log_pane {
+            Some(log_pane) => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                    .split(rect);
+                log_pane.draw(frame, chunks[1], &self.user_message);
+                self.log_rect.set(chunks[1]);
+                chunks[0]
+            }
+            // The log pane already shows every message.
+            None => self.user_message.draw(frame, rect),
+        };
+
+        self.progress_rect.set(rect);
+        let rect = self.progress_bars.lock().unwrap().draw(frame, rect);
+
+        // The tabs are only shown once there is more than one.
+        let rect = if self.tabs.is_empty() {
+            rect
+        } else {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(rect);
+            frame.render_widget(self.get_tab_bar(), chunks[0]);
+            chunks[1]
+        };
+
+        self.file_list_rect.set(rect);
+        self.files.lock().unwrap().draw(frame, rect);
+
+        if let Some(preview) = &self.preview {
+            preview.draw(frame, rect);
+        }
+        if let Some(command_pane) = &self.command_pane {
+            command_pane.draw(frame, rect);
+        }
+        if let Some(diff_pane) = &self.diff_pane {
+            diff_pane.draw(frame, rect);
+        }
+        if let Some(properties) = &self.properties {
+            properties.draw(frame, rect);
+        }
+
+        if let Some((_, dialog)) = &self.dialog {
+            dialog.draw(frame, frame.size());
+        }
+    }
+}
+
+/// Shows the progress of a background transfer in the progress bars.
+struct TransferProgress {
+    /// The title of the transfer in the log.
+    title: String,
+    progress_bars: Arc<Mutex<ProgressBars>>,
+    user_message: Arc<UserMessage>,
+    directory_progress: Option<Arc<ProgressDirectory>>,
+    /// Tells the tab that started this transfer that it is still running.
+    _transfers: Arc<()>,
+}
+
+impl TransferListener for TransferProgress {
+    fn file_started(&self, progress: Arc<ProgressFile>) {
+        self.progress_bars
+            .lock()
+            .unwrap()
+            .push_file_progress(progress);
+    }
+
+    fn file_finished(&self, progress: &ProgressFile) {
+        if let Some(p) = self.directory_progress.as_ref() {
+            p.inc(progress.get_total_bytes())
+        }
+    }
+
+    fn warn(&self, message: &str) {
+        self.user_message
+            .log(Severity::Warning, Some(&self.title), message);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.directory_progress
+            .as_ref()
+            .is_some_and(|p| p.is_cancelled())
+    }
+}
+
+impl Drop for Rftp {
+    fn drop(&mut self) {
+        let tabs = self
+            .tabs
+            .iter()
+            .flat_map(|tab| tab.session.iter().chain(&tab.local_session));
+        for connection in self.session.iter().chain(&self.local_session).chain(tabs) {
+            disconnect(connection);
+        }
+    }
+}
+
+/// Tell the server of `connection` that the session ends.
+fn disconnect(connection: &Connection) {
+    // A connection that was lost cannot be closed politely.
+    connection
+        .session()
+        .disconnect(Some(ssh2::DisconnectCode::ByApplication), "", None)
+        .ok();
+}
+
+/// Parse `destination`, written as `[user@]host[:port]`, into `options` and return the host.
+///
+/// An IPv6 address needs brackets only if a port follows it, like `[::1]:22`.
+fn parse_destination(destination: &str, options: &mut ConnectOptions) -> Result<String> {
+    let invalid = || ErrorKind::InvalidDestination(destination.to_string());
+    let (username, host, port) = split_destination(destination).ok_or_else(invalid)?;
+    let address = username.map_or(destination, |username| &destination[username.len() + 1..]);
+    let (host, port) = match port {
+        // A bare IPv6 address has more than one colon.
+        Some(port) if port.contains(':') && !address.starts_with('[') => (address, None),
+        _ => (host, port),
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    if let Some(username) = username {
+        options.username = username.to_string();
+    }
+    if let Some(port) = port {
+        options.port = Some(parse_port(port)?);
+    }
+    Ok(host.to_string())
+}
+
+/// Return where `source` ends up when it is copied or moved to `dest` on `fs`, relative to
+/// the working directory `cwd`.
+///
+/// Existing directories are copied into, existing files are never replaced.
+fn resolve_destination<F>(fs: &F, cwd: &Path, source: &Path, dest: &Path) -> Result<PathBuf>
+where
+    F: FileSystem + ?Sized,
+{
+    let mut dest = cwd.join(dest);
+    if fs.stat(&dest)?.is_some_and(|entry| entry.is_dir()) {
+        dest.push(source.file_name().unwrap_or_default());
+    }
+    if fs.stat(&dest)?.is_some() {
+        let dest = dest.to_string_lossy().to_string();
+        return Err(if fs.is_remote() {
+            ErrorKind::RemoteFileExists(dest)
+        } else {
+            ErrorKind::LocalFileExists(dest)
+        });
+    }
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Driver;
+    use rftp::memory::MemoryFileSystem;
+    use rftp::utils::CommandOutput;
+
+    type LocalFake = MemoryFileSystem<LocalFileEntry>;
+    type RemoteFake = MemoryFileSystem<RemoteFileEntry>;
+
+    /// Return a driver for an app that shows `/home/me` on the left and `/srv/www` on the
+    /// right in a terminal of `width` by `height`.
+    fn driver(width: u16, height: u16) -> (Driver, Arc<LocalFake>, Arc<RemoteFake>) {
+        let local = Arc::new(LocalFake::new(
+            false,
+            &[
+                ("/home/me/notes.txt", "hello"),
+                ("/home/me/src/main.rs", "fn main() {}"),
+                ("/home/me/.profile", "export A=1"),
+            ],
+        ));
+        let remote = Arc::new(RemoteFake::new(
+            true,
+            &[
+                ("/srv/www/index.html", "<html></html>"),
+                ("/srv/www/css/site.css", "body {}"),
+            ],
+        ));
+        let rftp = Rftp::with_file_systems(
+            Arc::clone(&local) as Arc<LocalFs>,
+            PathBuf::from("/home/me"),
+            Arc::clone(&remote) as Arc<RemoteFs>,
+            PathBuf::from("/srv/www"),
+            Filter::default(),
+        )
+        .unwrap();
+        (Driver::new(rftp, width, height), local, remote)
+    }
+
+    #[test]
+    fn test_navigation() {
+        let (mut driver, _, _) = driver(60, 7);
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"───────────┐┌Remote: \"/srv/www\"──────────┐",
+            "│>>notes.txt              5 B││index.html            13 B  │",
+            "│  src/                      ││css/                        │",
+            "│  ⬅                         ││⬅                           │",
+            "│                            ││                            │",
+            "└────────────────────────────┘└────────────────────────────┘",
+            "Press \"?\" for help.                                         ",
+        ]);
+
+        driver.press("j Enter");
+        driver.assert_screen(&[
+            "┌Local: \"/home/me/src\"───────┐┌Remote: \"/srv/www\"──────────┐",
+            "│  main.rs               12 B││index.html            13 B  │",
+            "│>>⬅                         ││css/                        │",
+            "│                            ││⬅                           │",
+            "│                            ││                            │",
+            "└────────────────────────────┘└────────────────────────────┘",
+            "Press \"?\" for help.                                         ",
+        ]);
+
+        driver.press("Enter l j Enter");
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"───────────┐┌Remote: \"/srv\"──────────────┐",
+            "│notes.txt              5 B  ││>>www/                      │",
+            "│src/                        ││  ⬅                         │",
+            "│⬅                           ││                            │",
+            "│                            ││                            │",
+            "└────────────────────────────┘└────────────────────────────┘",
+            "Press \"?\" for help.                                         ",
+        ]);
+
+        driver.press("z");
+        assert!(driver.screen().contains(".profile"));
+    }
+
+    #[test]
+    fn test_help() {
+        let (mut driver, _, _) = driver(80, 41);
+        driver.press("?");
+        let version = format!("Controls for rftp version {}.", clap::crate_version!());
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"─────────────────────┐┌Remote: \"/srv/www\"────────────────────┐",
+            "│>>notes.txt                        5 B││index.html                      13 B  │",
+            "│  src/                                ││css/                                  │",
+            "│  ⬅                                   ││⬅                                     │",
+            "│                                      ││                                      │",
+            "└──────────────────────────────────────┘└──────────────────────────────────────┘",
+            "Press \"?\" for help.                                                             ",
+            &format!("{:80}", version),
+            "------------------------------------------------------------                    ",
+            "h/j/k/l       Navigate the files.                                               ",
+            "Enter         Enter the selected directory.                                     ",
+            "Spacebar      Download/Upload the selected file/directory.                      ",
+            "Directories and overwrites are confirmed                                        ",
+            "first.                                                                          ",
+            "c/m           Copy/move the selected entry on the same side.                    ",
+            "O             Connect the selected pane to another host.                        ",
+            "t             Open a tab connected to another host.                             ",
+            "Tab           Show the next tab, Shift+Tab the previous one.                    ",
+            "w             Close the shown tab.                                              ",
+            "Mouse         Click to select, double-click to enter a                          ",
+            "directory.                                                                      ",
+            "Click a progress bar to cancel its transfer.                                    ",
+            "z             Show/hide hidden files.                                           ",
+            "x             Edit the patterns of entries to exclude.                          ",
+            "X             Respect/ignore .gitignore and .rftpignore                         ",
+            "files.                                                                          ",
+            "e             Edit the selected remote file in $EDITOR.                         ",
+            "!             Run a command in the remote directory.                            ",
+            "S             Open a shell in the remote directory.                             ",
+            "v             Preview the selected file.                                        ",
+            "x: hexdump  F: follow a growing file                                            ",
+            "d             Diff the selected file with the other side.                       ",
+            "D             Diff the selected file with a chosen file.                        ",
+            "=             Compare the files with the other side.                            ",
+            "T             Copy what differs to the other side.                              ",
+            "C             Compare the directories recursively.                              ",
+            "p             Show/change the mode and owner of an entry.                       ",
+            "L             Show the log of all messages.                                     ",
+            "q             Quit.                                                             ",
+            "Q             Force quit.                                                       ",
+            "?             Print this help message.                                          ",
+        ]);
+    }
+
+    #[test]
+    fn test_error() {
+        let (mut driver, _, _) = driver(60, 8);
+        driver.press("Enter");
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"───────────┐┌Remote: \"/srv/www\"──────────┐",
+            "│>>notes.txt              5 B││index.html            13 B  │",
+            "│  src/                      ││css/                        │",
+            "│  ⬅                         ││⬅                           │",
+            "└────────────────────────────┘└────────────────────────────┘",
+            "Press \"?\" for help.                                         ",
+            "Error: Cannot enter \"notes.txt\" because it is not a         ",
+            "directory!                                                  ",
+        ]);
+    }
+
+    #[test]
+    fn test_progress() {
+        let (mut driver, _, _) = driver(60, 7);
+        let progress = Arc::new(ProgressFile::new("Uploading \"big.iso\"", 2048));
+        driver
+            .rftp
+            .progress_bars
+            .lock()
+            .unwrap()
+            .push_file_progress(progress);
+        driver.tick();
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"───────────┐┌Remote: \"/srv/www\"──────────┐",
+            "│>>notes.txt              5 B││index.html            13 B  │",
+            "│  src/                      ││css/                        │",
+            "│  ⬅                         ││⬅                           │",
+            "└────────────────────────────┘└────────────────────────────┘",
+            "Uploading \"big.iso\"           0 B/2.0 KB  0 bit/s  ??:?? ETA",
+            "Press \"?\" for help.                                         ",
+        ]);
+
+        driver.press("q");
+        assert!(driver.rftp.is_alive());
+        assert!(driver
+            .screen()
+            .contains("There are still downloads/uploads in progress."));
+        driver.press("Q");
+        assert!(!driver.rftp.is_alive());
+    }
+
+    #[test]
+    fn test_mouse() {
+        let (mut driver, _, _) = driver(60, 7);
+        // Select "css/" on the remote side, then double-click it.
+        driver.click(32, 2);
+        assert!(driver.screen().contains("│>>css/"));
+        driver.click(32, 2);
+        assert!(driver.screen().contains("Remote: \"/srv/www/css\""));
+
+        // Scrolling moves the selection of the side under the mouse.
+        driver.scroll(5, 1, 2);
+        assert!(driver.screen().contains("│>>⬅"));
+        driver.scroll(5, 1, -1);
+        assert!(driver.screen().contains("│>>src/"));
+
+        // Clicks outside of the lists do nothing.
+        driver.click(5, 6);
+        assert!(driver.screen().contains("│>>src/"));
+    }
+
+    #[test]
+    fn test_log_pane() {
+        let (mut driver, _, _) = driver(80, 16);
+        driver.press("Enter");
+        driver.rftp.user_message.warn("Something is odd.");
+        driver.press("L");
+        let screen = driver.screen();
+        assert!(screen.contains("Log of all messages (3/3)"), "{}", screen);
+        assert!(screen.contains("info    Press \"?\" for help."));
+        assert!(screen.contains("error   Error: Cannot enter \"notes.txt\" because it"));
+        assert!(screen.contains("warning Something is odd."));
+
+        driver.press("f");
+        let screen = driver.screen();
+        assert!(screen.contains("Log of warnings and errors (2/2)"));
+        assert!(!screen.contains("info    Press"));
+
+        driver.press("k");
+        let screen = driver.screen();
+        assert!(screen.contains("(1/2)"));
+        assert!(!screen.contains("Something is odd."));
+
+        // Keys go to the log pane until it is closed.
+        assert!(screen.contains("│>>notes.txt"));
+        driver.press("Esc j");
+        assert!(!driver.screen().contains("Log of"));
+        assert!(driver.screen().contains("│>>src/"));
+    }
+
+    #[test]
+    fn test_log_transfer_source() {
+        let (mut driver, _, _) = driver(80, 16);
+        driver
+            .press("l Space")
+            .wait_for("Finished downloading \"index.html\".");
+        driver.press("L");
+        assert!(driver
+            .screen()
+            .contains("info    [Downloading \"index.html\"] Finished downloading"));
+    }
+
+    #[test]
+    fn test_preview() {
+        let (mut driver, _, remote) = driver(80, 8);
+        driver.press("l v").wait_for("<html></html>");
+        driver.assert_screen(&[
+            "┌Preview \"/srv/www/index.html\"  0 B-13 B of 13 B───────────────────────────────┐",
+            "│<html></html>                                                                 │",
+            "│                                                                              │",
+            "│                                                                              │",
+            "│                                                                              │",
+            "│j/k: scroll  x: hexdump  F: follow  q: close                                  │",
+            "└──────────────────────────────────────────────────────────────────────────────┘",
+            "Press \"?\" for help.                                                             ",
+        ]);
+
+        driver.press("x");
+        assert!(driver.screen().contains(
+            "│00000000  3c 68 74 6d 6c 3e 3c 2f  68 74 6d 6c 3e           |<html></html>|"
+        ));
+
+        // Followed files show what is appended to them.
+        driver.press("x F");
+        let comments: String = (1..=5).map(|i| format!("\n<!-- {} -->", i)).collect();
+        remote.append("/srv/www/index.html", &comments);
+        driver.wait_for("<!-- 5 -->");
+        let screen = driver.screen();
+        assert!(screen.contains("[follow]"));
+        assert!(!screen.contains("<html></html>"));
+
+        driver.press("k");
+        assert!(!driver.screen().contains("[follow]"));
+        driver.press("q");
+        assert!(driver.screen().contains("│>>index.html"));
+
+        driver.press("j v");
+        assert!(driver
+            .screen()
+            .contains("Error: Cannot preview \"css\" because it is a directory!"));
+    }
+
+    #[test]
+    fn test_edit() {
+        let (mut driver, _, remote) = driver(80, 10);
+        driver.press("e");
+        assert!(driver.screen().contains("No remote file selected."));
+        assert!(driver.rftp.take_suspended_task().is_none());
+
+        driver.press("l e");
+        let edit = match driver.rftp.take_suspended_task() {
+            Some(SuspendedTask::Edit(edit)) => edit,
+            _ => panic!("expected the file to be edited"),
+        };
+        std::fs::write(edit.local_path(), "<html>edited</html>").unwrap();
+        let local_path = edit.local_path().to_path_buf();
+        driver.rftp.finish_edit(edit, Ok(()));
+        driver.tick();
+        assert!(driver
+            .screen()
+            .contains("Uploaded \"/srv/www/index.html\"."));
+        assert_eq!(
+            remote.contents("/srv/www/index.html").unwrap(),
+            "<html>edited</html>"
+        );
+        assert!(!local_path.exists());
+    }
+
+    #[test]
+    fn test_edit_conflict() {
+        let (mut driver, _, remote) = driver(80, 12);
+        driver.press("l e");
+        let edit = match driver.rftp.take_suspended_task() {
+            Some(SuspendedTask::Edit(edit)) => edit,
+            _ => panic!("expected the file to be edited"),
+        };
+        std::fs::write(edit.local_path(), "<html>mine</html>").unwrap();
+        let local_path = edit.local_path().to_path_buf();
+        remote.write("/srv/www/index.html", "<html>theirs</html>");
+        driver.rftp.finish_edit(edit, Ok(()));
+        driver.tick();
+        assert!(driver
+            .screen()
+            .contains("Overwrite \"/srv/www/index.html\"?"));
+
+        driver.press("n");
+        assert!(driver.screen().contains("Did not upload"));
+        assert_eq!(
+            remote.contents("/srv/www/index.html").unwrap(),
+            "<html>theirs</html>"
+        );
+        // The edits are not lost.
+        assert_eq!(
+            std::fs::read_to_string(&local_path).unwrap(),
+            "<html>mine</html>"
+        );
+        std::fs::remove_dir_all(local_path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_command() {
+        let (mut driver, _, _) = driver(60, 9);
+        driver.press("! l s Enter");
+        assert!(driver
+            .screen()
+            .contains("Commands can only be run when connected to a"));
+        driver.press("S");
+        assert!(driver.rftp.take_suspended_task().is_none());
+
+        driver.rftp.command_pane = Some(CommandPane::spawn("ls", || {
+            Ok(CommandOutput {
+                output: b"index.html\n".to_vec(),
+                exit_status: 0,
+            })
+        }));
+        driver.wait_for("exit status 0");
+        assert!(driver.screen().contains("$ ls"));
+        driver.press("q");
+        assert!(driver.rftp.command_pane.is_none());
+        assert!(!driver.screen().contains("$ ls"));
+    }
+
+    #[test]
+    fn test_properties() {
+        let (mut driver, _, remote) = driver(80, 16);
+        remote.write("/etc/passwd", "www:x:1000:1000::/srv/www:/bin/sh\n");
+        driver.press("l p");
+        assert!(driver.screen().contains("Mode:      -rw-r--r-- (0644)"));
+        assert!(driver.screen().contains("Owner:     www (1000)"));
+
+        driver.press("m Backspace Backspace Backspace 6 0 0 Enter");
+        assert!(driver
+            .screen()
+            .contains("Changed the mode of \"/srv/www/index.html\" to 0600."));
+        assert!(driver.screen().contains("Mode:      -rw------- (0600)"));
+
+        driver.press("m Backspace Backspace Backspace 9 Enter");
+        assert!(driver.screen().contains("is not an octal mode"));
+
+        driver.press("o");
+        for _ in 0.."1000:1000".len() {
+            driver.press("Backspace");
+        }
+        driver.press(": 0 Enter");
+        let properties = remote.properties(Path::new("/srv/www/index.html")).unwrap();
+        assert_eq!((properties.uid, properties.gid), (Some(1000), Some(0)));
+        assert!(driver.screen().contains("Group:     0"));
+
+        driver.press("q");
+        assert!(!driver.screen().contains("Properties"));
+    }
+
+    #[test]
+    fn test_properties_recursive() {
+        let (mut driver, local, _) = driver(80, 16);
+        driver.press("j p M");
+        assert!(driver
+            .screen()
+            .contains("Mode of everything in \"/home/me/src\""));
+        driver.press("Backspace Backspace Backspace 7 0 0 Enter");
+        for path in ["/home/me/src", "/home/me/src/main.rs"] {
+            let mode = local.properties(Path::new(path)).unwrap().mode.unwrap();
+            assert_eq!(mode & 0o7777, 0o700, "{}", path);
+        }
+        // The owner of local entries is not changed.
+        driver.press("o");
+        assert!(driver.rftp.prompt.is_none());
+    }
+
+    #[test]
+    fn test_copy_within() {
+        let (mut driver, local, remote) = driver(80, 16);
+        let clear = |driver: &mut Driver, input: &str| {
+            for _ in 0..input.len() {
+                driver.press("Backspace");
+            }
+        };
+
+        // Files are copied into existing directories.
+        driver.press("c");
+        assert!(driver.screen().contains("Copy \"/home/me/notes.txt\" to"));
+        clear(&mut driver, "/home/me/notes.txt");
+        driver.press("s r c Enter").wait_for("Copied");
+        assert_eq!(
+            local.contents("/home/me/src/notes.txt").as_deref(),
+            Some("hello")
+        );
+
+        // Existing files are not replaced.
+        driver.press("c Enter");
+        assert!(driver.screen().contains("already exists"));
+
+        // Remote directories are copied and moved on the remote side.
+        driver.press("l j c");
+        clear(&mut driver, "css");
+        driver
+            .press("s t y l e Enter")
+            .wait_for("Copied \"/srv/www/css\"");
+        assert_eq!(
+            remote.contents("/srv/www/style/site.css").as_deref(),
+            Some("body {}")
+        );
+        driver.press("m");
+        clear(&mut driver, "css");
+        driver.press("o l d Enter");
+        assert!(driver
+            .screen()
+            .contains("Moved \"/srv/www/css\" to \"/srv/www/old\"."));
+        assert_eq!(remote.contents("/srv/www/css/site.css"), None);
+        assert_eq!(
+            remote.contents("/srv/www/old/site.css").as_deref(),
+            Some("body {}")
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let (mut driver, local, remote) = driver(80, 16);
+        driver.press("d");
+        assert!(driver
+            .screen()
+            .contains("There is no \"/srv/www/notes.txt\" on the other side. Press D"));
+
+        local.write("/home/me/notes.txt", "one\ntwo\nthree\n");
+        remote.write("/srv/www/notes.txt", "one\n2\nthree\n");
+        driver.press("d").wait_for("+++ remote");
+        for line in ["@@ -1,3 +1,3 @@", " one", "-two", "+2", " three"] {
+            assert!(driver.screen().contains(line), "{}", line);
+        }
+        driver.press("q");
+        assert!(driver.rftp.diff_pane.is_none());
+
+        // Another remote file is chosen from the remote side.
+        remote.write("/srv/www/old.txt", "one\ntwo\nthree\n");
+        driver.press("D");
+        for _ in 0.."notes.txt".len() {
+            driver.press("Backspace");
+        }
+        driver
+            .press("o l d . t x t Enter")
+            .wait_for("The files are the same.");
+
+        // Directories are not diffed.
+        driver.press("q j d");
+        assert!(driver.screen().contains("because it is a directory"));
+    }
+
+    #[test]
+    fn test_resize() {
+        let (mut driver, _, _) = driver(60, 7);
+        driver.resize(40, 6);
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"─┐┌Remote: \"/srv/www\"┐",
+            "│>>notes.txt    5 B││index.html  13 B  │",
+            "│  src/            ││css/              │",
+            "│  ⬅               ││⬅                 │",
+            "└──────────────────┘└──────────────────┘",
+            "Press \"?\" for help.                     ",
+        ]);
+    }
+
+    #[test]
+    fn test_cancel_progress() {
+        let (mut driver, _, _) = driver(80, 10);
+        let progress = Arc::new(ProgressFile::new("Uploading \"big.iso\"", 2048));
+        driver
+            .rftp
+            .progress_bars
+            .lock()
+            .unwrap()
+            .push_file_progress(Arc::clone(&progress));
+        driver.tick();
+
+        driver.click(3, 8).wait_for("Cancel Uploading \"big.iso\"?");
+        driver.press("n");
+        assert!(!progress.is_cancelled());
+
+        // The message about the dialog moves the progress bar up.
+        let row = driver
+            .screen()
+            .lines()
+            .position(|line| line.starts_with("Uploading"))
+            .unwrap();
+        driver.click(3, row as u16).press("y");
+        assert!(progress.is_cancelled());
+        assert!(!driver.screen().contains("big.iso"));
+    }
+
+    #[test]
+    fn test_upload_directory() {
+        let (mut driver, _, remote) = driver(80, 10);
+        driver.press("j Space").wait_for("Upload \"src\"?");
+        driver.assert_screen(&[
+            "┌Local: \"/home/me\"─────────────────────┐┌Remote: \"/srv/www\"────────────────────┐",
+            "│  notes.txt                        5 B││index.html                      13 B  │",
+            "│>>src/ ┌Upload \"src\"?─────────────────────────────────────────────────┐       │",
+            "│  ⬅    │mkdir     /srv/www/src/                                       │       │",
+            "│       │create    /srv/www/src/main.rs (12 B)                         │       │",
+            "│       │1 to create, 0 to overwrite, 0 skipped, 12 B in total.        │       │",
+            "│       │y: confirm  n: cancel  j/k: scroll                            │       │",
+            "│       └──────────────────────────────────────────────────────────────┘       │",
+            "└──────────────────────────────────────┘└──────────────────────────────────────┘",
+            "Press \"?\" for help.                                                             ",
+        ]);
+
+        driver.press("y").wait_for("Finished uploading \"src\".");
+        assert_eq!(
+            remote.contents("/srv/www/src/main.rs").unwrap(),
+            "fn main() {}"
+        );
+        // The remote files are fetched again once the upload is done.
+        driver.wait_for("││src/");
+    }
+
+    #[test]
+    fn test_download_file() {
+        let (mut driver, local, _) = driver(60, 10);
+        // A single new file is not confirmed.
+        driver
+            .press("l Space")
+            .wait_for("Finished downloading \"index.html\".");
+        assert_eq!(
+            local.contents("/home/me/index.html").unwrap(),
+            "<html></html>"
+        );
+
+        // Replacing it is.
+        driver.press("Space").wait_for("Download \"index.html\"?");
+        assert!(driver
+            .screen()
+            .contains("overwrite /home/me/index.html (13 B)"));
+        driver.press("n");
+        assert!(driver.screen().contains("Cancelled."));
+        assert!(!driver.screen().contains("Download \"index.html\"?"));
+    }
+
+    #[test]
+    fn test_resume_after_lost_connection() {
+        let (mut driver, local, remote) = driver(100, 14);
+        remote.lose_connection_after(5);
+        driver
+            .press("l Space")
+            .wait_for("Finished downloading \"index.html\".");
+        assert_eq!(
+            local.contents("/home/me/index.html").unwrap(),
+            "<html></html>"
+        );
+        driver.press("L");
+        assert!(driver
+            .screen()
+            .contains("The connection was lost while copying"));
+        assert!(driver.screen().contains("index.html, retrying in 1 s."));
+    }
+
+    #[test]
+    fn test_between_servers() {
+        let host_a = Arc::new(LocalFake::new(true, &[("/home/a/build.tar", "artifact")]));
+        let host_b = Arc::new(RemoteFake::new(true, &[("/home/b/old.txt", "old")]));
+        let rftp = Rftp::with_file_systems(
+            Arc::clone(&host_a) as Arc<LocalFs>,
+            PathBuf::from("/home/a"),
+            Arc::clone(&host_b) as Arc<RemoteFs>,
+            PathBuf::from("/home/b"),
+            Filter::default(),
+        )
+        .unwrap();
+        {
+            let mut files = rftp.files.lock().unwrap();
+            files.set_local_title("a");
+            files.set_remote_title("b");
+        }
+        let mut driver = Driver::new(rftp, 60, 10);
+        assert!(driver.screen().contains("┌a: \"/home/a\""));
+        assert!(driver.screen().contains("┌b: \"/home/b\""));
+
+        driver
+            .press("Space")
+            .wait_for("Finished copying \"build.tar\".");
+        assert_eq!(host_b.contents("/home/b/build.tar").unwrap(), "artifact");
+
+        // Conflicts are confirmed like any other transfer.
+        driver.press("Space").wait_for("Copy \"build.tar\"?");
+        driver.press("n");
+        assert!(driver.screen().contains("Cancelled."));
+    }
+
+    #[test]
+    fn test_tabs() {
+        let (mut driver, _, remote) = driver(60, 10);
+        driver.press("w");
+        assert!(driver.screen().contains("This is the only tab."));
+
+        let other = Arc::new(RemoteFake::new(true, &[("/var/log/syslog", "boot")]));
+        let tab = Tab::new(
+            "backup",
+            Arc::new(LocalFake::new(false, &[("/tmp/a.txt", "a")])),
+            PathBuf::from("/tmp"),
+            Arc::clone(&other) as Arc<RemoteFs>,
+            PathBuf::from("/var/log"),
+            false,
+        )
+        .unwrap();
+        driver.rftp.add_tab(tab);
+        driver.tick();
+        assert!(driver.screen().starts_with(" Remote │ backup "));
+        assert!(driver.screen().contains("syslog"));
+
+        // A transfer in one tab goes to the server of that tab, and shows up in every tab.
+        driver
+            .press("Space")
+            .wait_for("Finished uploading \"a.txt\".");
+        assert_eq!(other.contents("/var/log/a.txt").unwrap(), "a");
+        assert_eq!(remote.contents("/srv/www/a.txt"), None);
+
+        driver.press("Tab");
+        assert!(driver.screen().contains("index.html"));
+        assert!(!driver.screen().contains("syslog"));
+        driver.press("Tab");
+        assert!(driver.screen().contains("syslog"));
+
+        // The upload thread may still be ending after it reported that it finished.
+        let start = Instant::now();
+        while Arc::strong_count(&driver.rftp.transfers) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+        let running_transfer = Arc::clone(&driver.rftp.transfers);
+        driver.press("w");
+        assert!(driver
+            .screen()
+            .contains("Wait for the downloads/uploads of this tab"));
+        assert_eq!(driver.rftp.tabs.len(), 1);
+        drop(running_transfer);
+
+        driver.press("w");
+        assert!(driver.screen().contains("Closed the tab of backup."));
+        assert!(driver.screen().contains("index.html"));
+        assert!(!driver.screen().contains("│ backup"));
+        assert!(driver.rftp.tabs.is_empty());
+
+        driver.press("t");
+        assert!(driver
+            .screen()
+            .contains("Open a tab connected to [user@]host[:port]"));
+        driver.press("b a c k u p Enter");
+        match driver.rftp.take_suspended_task() {
+            Some(SuspendedTask::OpenTab(destination)) => assert_eq!(destination, "backup"),
+            _ => panic!("expected a tab to be opened"),
+        }
+    }
+
+    #[test]
+    fn test_connect_prompt() {
+        let (mut driver, _, _) = driver(80, 10);
+        driver.press("l O");
+        assert!(driver
+            .screen()
+            .contains("Connect the right pane to [user@]host[:port]"));
+        driver.press("b a c k u p Enter");
+        match driver.rftp.take_suspended_task() {
+            Some(SuspendedTask::Connect {
+                destination,
+                is_remote,
+            }) => assert_eq!((destination.as_str(), is_remote), ("backup", true)),
+            _ => panic!("expected a connection"),
+        }
+
+        // Nothing is connected without a host.
+        driver.press("h O Enter");
+        assert!(driver.rftp.take_suspended_task().is_none());
+    }
+
+    #[test]
+    fn test_compare() {
+        let (mut driver, local, remote) = driver(80, 14);
+        local.write("/home/me/index.html", "<html></html>");
+        remote.write("/srv/www/notes.txt", "hi");
+        // Fetch the files again.
+        driver.press("z z =");
+        assert!(driver
+            .screen()
+            .contains("Compare files with the other side by size and modification time."));
+        {
+            use rftp::compare::Comparison::*;
+            let files = driver.rftp.files.lock().unwrap();
+            // index.html, notes.txt, src/ and the parent directory.
+            assert_eq!(
+                files.get_local_comparisons(),
+                Some(vec![Same, Different, Unique, Same])
+            );
+            // index.html, notes.txt, css/ and the parent directory.
+            assert_eq!(
+                files.get_remote_comparisons(),
+                Some(vec![Same, Different, Unique, Same])
+            );
+        }
+
+        driver.press("j T").wait_for("Upload \"me\"?");
+        assert!(driver
+            .screen()
+            .contains("overwrite /srv/www/notes.txt (5 B)"));
+        assert!(driver
+            .screen()
+            .contains("create    /srv/www/src/main.rs (12 B)"));
+        assert!(!driver.screen().contains("index.html (13 B)"));
+        driver.press("y").wait_for("Finished uploading \"me\".");
+        assert_eq!(remote.contents("/srv/www/notes.txt").unwrap(), "hello");
+
+        // Hidden files are compared as well.
+        driver
+            .press("C")
+            .wait_for("1 only local, 1 only remote, 0 different and 3 same entries.");
+
+        driver.press("= =");
+        assert!(driver.rftp.files.lock().unwrap().get_compare().is_none());
+    }
+
+    #[test]
+    fn test_parse_destination() {
+        let parse = |destination: &str| {
+            let mut options = ConnectOptions::default();
+            parse_destination(destination, &mut options)
+                .map(|host| (options.username, host, options.port))
+        };
+        let default_username = ConnectOptions::default().username;
+
+        assert_eq!(
+            parse("me@example.com:2222").unwrap(),
+            ("me".to_string(), "example.com".to_string(), Some(2222))
+        );
+        assert_eq!(
+            parse("example.com").unwrap(),
+            (default_username.clone(), "example.com".to_string(), None)
+        );
+        assert_eq!(
+            parse("me@[::1]:22").unwrap(),
+            ("me".to_string(), "::1".to_string(), Some(22))
+        );
+        assert_eq!(
+            parse("[fe80::1]").unwrap(),
+            (default_username.clone(), "fe80::1".to_string(), None)
+        );
+        assert_eq!(
+            parse("me@2001:db8::1").unwrap(),
+            ("me".to_string(), "2001:db8::1".to_string(), None)
+        );
+        assert_eq!(
+            parse("::1").unwrap(),
+            (default_username, "::1".to_string(), None)
+        );
+        assert!(matches!(
+            parse("example.com:ssh"),
+            Err(ErrorKind::InvalidPortNumber)
+        ));
+        assert!(matches!(
+            parse("[::1:22"),
+            Err(ErrorKind::InvalidDestination(_))
+        ));
+        assert!(matches!(
+            parse(":22"),
+            Err(ErrorKind::InvalidDestination(_))
+        ));
+    }
+}
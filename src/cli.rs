@@ -0,0 +1,541 @@
+use crate::report::{Event, OutputMode, Reporter};
+use rftp::config::{expand_tilde, Config};
+use rftp::connect::{
+    create_session, parse_count, parse_port, parse_seconds, split_destination, ConnectOptions,
+};
+use rftp::file::*;
+use rftp::filesystem::LocalFileSystem;
+use rftp::filter::Filter;
+use rftp::known_hosts::StrictHostKeyChecking;
+use rftp::sync::{apply_sync, plan_sync, SyncDirection, SyncOptions};
+use rftp::transfer::{download_recursive, plan_transfer, upload_recursive, PlannedAction};
+use rftp::utils::{ErrorKind, Result};
+
+use crossbeam_channel::{bounded, RecvTimeoutError};
+use std::io::{stderr, stdin, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// How often the progress of a one-shot transfer is printed.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Return the command line interface of rftp.
+pub fn app() -> clap::App<'static, 'static> {
+    clap::clap_app!(
+        rftp =>
+            (version: clap::crate_version!())
+            (author: clap::crate_authors!())
+            (about: clap::crate_description!())
+            (@setting SubcommandsNegateReqs)
+            (@arg destination: +required)
+            (@arg second_destination:
+                "Another host to show on the right, with the first host on the left instead of this machine")
+            (@arg port: -p --port +takes_value +global)
+            (@arg username: -u --user +takes_value +global)
+            (@arg verbose: -v --verbose +global)
+            (@arg connect_timeout: --("connect-timeout") +takes_value +global
+                "Seconds to wait for the TCP connection, 0 for the system default")
+            (@arg io_timeout: --("io-timeout") +takes_value +global
+                "Seconds to wait for the server to answer, 0 to wait forever [default: 10]")
+            (@arg server_alive_interval: --("server-alive-interval") +takes_value +global
+                "Send a keepalive after this many idle seconds, 0 to never send one [default: 0]")
+            (@arg server_alive_count_max: --("server-alive-count-max") +takes_value +global
+                "Treat the connection as lost after this many unanswered keepalives [default: 3]")
+            (@arg idle_timeout: --("idle-timeout") +takes_value +global
+                "Treat the connection as lost after this many seconds without data [default: interval * count]")
+            (@arg no_compression: -C --("no-compression") +global
+                "Do not compress the data that is sent, which is faster on fast networks")
+            (@arg ciphers: --ciphers +takes_value +global
+                "The ciphers to prefer, comma-separated")
+            (@arg kex_algorithms: --("kex-algorithms") +takes_value +global
+                "The key exchange algorithms to prefer, comma-separated")
+            (@arg host_key_algorithms: --("host-key-algorithms") +takes_value +global
+                "The host key algorithms to prefer, comma-separated")
+            (@arg macs: --macs +takes_value +global
+                "The message authentication codes to prefer, comma-separated")
+            (@arg strict_host_key_checking: --("strict-host-key-checking") +takes_value +global
+                possible_values(StrictHostKeyChecking::VALUES)
+                "Whether to ask before adding an unknown host key [default: ask]")
+            (@arg hash_known_hosts: --("hash-known-hosts") +global
+                "Hash the host names that are added to the known hosts file")
+            (@arg exclude: --exclude +takes_value +multiple number_of_values(1) +global
+                "Skip entries that match this pattern during recursive transfers")
+            (@arg include: --include +takes_value +multiple number_of_values(1) +global
+                "Do not skip entries that match this pattern, even if they are excluded")
+            (@arg exclude_from: --("exclude-from") +takes_value +multiple number_of_values(1) +global
+                "Skip entries that match any pattern listed in this file")
+            (@arg ignore_files: --("ignore-files") +global
+                "Skip entries that are listed in .gitignore and .rftpignore files")
+            (@arg log_file: --("log-file") +takes_value
+                "Append every message shown in the interface to this file")
+            (@subcommand get =>
+                (about: "Download a file from the server without starting the interface.")
+                (@arg source: +required "The remote file to download, as [user@]host:path")
+                (@arg dest: "The local file or directory to download to")
+                (@arg recursive: -r --recursive "Download directories recursively")
+                (@arg dry_run: -n --("dry-run") "Only print the planned actions")
+                (@arg json: --json "Print newline-delimited JSON events to stdout")
+            )
+            (@subcommand put =>
+                (about: "Upload a file to the server without starting the interface.")
+                (@arg source: +required "The local file to upload")
+                (@arg dest: +required "The remote file or directory to upload to, as [user@]host:path")
+                (@arg recursive: -r --recursive "Upload directories recursively")
+                (@arg dry_run: -n --("dry-run") "Only print the planned actions")
+                (@arg json: --json "Print newline-delimited JSON events to stdout")
+            )
+            (@subcommand sync =>
+                (about: "Make a remote directory match a local directory, or the other way around.")
+                (@arg source: +required "The directory to copy from, either local or as [user@]host:path")
+                (@arg dest: +required "The directory to update, either local or as [user@]host:path")
+                (@arg checksum: -c --checksum "Compare the contents of files instead of their modification times")
+                (@arg delete: --delete "Delete entries in the destination that do not exist in the source")
+                (@arg dry_run: -n --("dry-run") "Only print the planned actions")
+                (@arg yes: -y --yes "Apply the planned actions without asking")
+                (@arg json: --json "Print newline-delimited JSON events to stdout")
+            )
+    )
+}
+
+/// Return the username given on the command line or the name of the current user.
+pub fn get_username(matches: &clap::ArgMatches) -> Result<String> {
+    if let Some(username) = matches.value_of("username") {
+        Ok(username.to_string())
+    } else if cfg!(unix) {
+        Ok(std::env::var("USER")?)
+    } else if cfg!(windows) {
+        Ok(std::env::var("USERNAME")?)
+    } else {
+        unimplemented!()
+    }
+}
+
+/// Return the options to connect as `username` from the config file, overridden by the ones
+/// given on the command line.
+pub fn get_connect_options(
+    matches: &clap::ArgMatches,
+    username: &str,
+    config: &Config,
+) -> Result<ConnectOptions> {
+    let mut options = ConnectOptions {
+        port: matches.value_of("port").map(parse_port).transpose()?,
+        verbose: matches.is_present("verbose"),
+        ..ConnectOptions::new(username)
+    };
+    // The command line has the same keywords as the config file, and takes precedence.
+    let seconds = |name: &str| matches.value_of(name).map(parse_seconds).transpose();
+    let string = |name: &str| matches.value_of(name).map(str::to_string);
+    let overrides = Config {
+        connect_timeout: seconds("connect_timeout")?,
+        io_timeout: seconds("io_timeout")?,
+        server_alive_interval: seconds("server_alive_interval")?,
+        server_alive_count_max: matches
+            .value_of("server_alive_count_max")
+            .map(parse_count)
+            .transpose()?,
+        idle_timeout: seconds("idle_timeout")?,
+        compression: Some(false).filter(|_| matches.is_present("no_compression")),
+        ciphers: string("ciphers"),
+        kex_algorithms: string("kex_algorithms"),
+        host_key_algorithms: string("host_key_algorithms"),
+        macs: string("macs"),
+        strict_host_key_checking: matches
+            .value_of("strict_host_key_checking")
+            .and_then(StrictHostKeyChecking::parse),
+        hash_known_hosts: Some(true).filter(|_| matches.is_present("hash_known_hosts")),
+        ..Config::default()
+    };
+    config.apply_to(&mut options);
+    overrides.apply_to(&mut options);
+    Ok(options)
+}
+
+/// Return the patterns of the config file extended with the ones given on the command line.
+pub fn get_filter(matches: &clap::ArgMatches, config: &Config) -> Result<Filter> {
+    let mut filter = config.filter.clone();
+    for pattern in matches.values_of("exclude").into_iter().flatten() {
+        filter.exclude(pattern);
+    }
+    for pattern in matches.values_of("include").into_iter().flatten() {
+        filter.include(pattern);
+    }
+    for path in matches.values_of("exclude_from").into_iter().flatten() {
+        filter.exclude_from(&expand_tilde(path))?;
+    }
+    if matches.is_present("ignore_files") {
+        filter.set_use_ignore_files(true);
+    }
+    Ok(filter)
+}
+
+/// A path on a remote host of the form `[user@]host:path`, where an IPv6 host is written in
+/// brackets.
+#[derive(Debug, PartialEq)]
+struct RemotePath {
+    username: Option<String>,
+    host: String,
+    path: PathBuf,
+}
+
+impl FromStr for RemotePath {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (username, host, path) = match split_destination(s) {
+            Some((username, host, Some(path))) if !host.is_empty() && !host.contains('/') => {
+                (username.map(str::to_string), host, path)
+            }
+            _ => return Err(ErrorKind::InvalidRemotePath(s.to_string())),
+        };
+        // An empty path refers to the home directory of the user.
+        let path = if path.is_empty() { "." } else { path };
+        Ok(RemotePath {
+            username,
+            host: host.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl RemotePath {
+    /// Connect to the host of this path and open an sftp channel.
+    fn connect(
+        &self,
+        matches: &clap::ArgMatches,
+        config: &Config,
+        reporter: &Reporter,
+    ) -> Result<(ssh2::Session, ssh2::Sftp)> {
+        let username = match &self.username {
+            Some(username) => username.clone(),
+            None => get_username(matches)?,
+        };
+        let session = create_session(
+            &self.host,
+            &get_connect_options(matches, &username, config)?,
+        )?;
+        let sftp = session.sftp()?;
+        reporter.emit(Event::Connected {
+            host: &self.host,
+            username: &username,
+        });
+        Ok((session, sftp))
+    }
+}
+
+/// Return the output mode that was chosen on the command line.
+fn get_output_mode(matches: &clap::ArgMatches) -> OutputMode {
+    if matches.is_present("json") {
+        OutputMode::Json
+    } else {
+        OutputMode::Plain
+    }
+}
+
+/// Run `transfer` in the background and report its progress until it finishes.
+fn run_with_progress<F>(reporter: &Reporter, transfer: F) -> Result<()>
+where
+    F: FnOnce(&Reporter) -> Result<()> + Send,
+{
+    let (tx, rx) = bounded(1);
+    thread::scope(|scope| {
+        let handle = scope.spawn(|| tx.send(transfer(reporter)).unwrap());
+        loop {
+            match rx.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(result) => return result,
+                Err(RecvTimeoutError::Timeout) => reporter.emit_progress(),
+                // The transfer panicked without sending a result.
+                Err(RecvTimeoutError::Disconnected) => match handle.join() {
+                    Err(panic) => std::panic::resume_unwind(panic),
+                    Ok(()) => unreachable!("the transfer finished without a result"),
+                },
+            }
+        }
+    })
+}
+
+/// Report each of the planned `actions` followed by how much they change.
+fn report_plan(reporter: &Reporter, actions: &[impl PlannedAction]) {
+    for action in actions {
+        reporter.emit(Event::Planned(action));
+    }
+    reporter.emit(Event::PlanFinished {
+        changes: actions.iter().filter(|action| action.is_change()).count(),
+        bytes: actions.iter().map(PlannedAction::bytes).sum(),
+    });
+}
+
+/// Return the name of the file or directory at `path`.
+fn file_name(path: &Path) -> Result<&std::ffi::OsStr> {
+    path.file_name()
+        .ok_or_else(|| ErrorKind::InvalidRemotePath(path.to_string_lossy().to_string()))
+}
+
+/// Download a file or directory from a remote host.
+pub fn run_get(matches: &clap::ArgMatches, config: &Config) -> Result<()> {
+    let reporter = Reporter::new(get_output_mode(matches));
+    let result = get(matches, config, &reporter);
+    reporter.finish(&result);
+    result
+}
+
+fn get(matches: &clap::ArgMatches, config: &Config, reporter: &Reporter) -> Result<()> {
+    let filter = get_filter(matches, config)?;
+    let remote: RemotePath = matches.value_of("source").unwrap().parse()?;
+    let (session, sftp) = remote.connect(matches, config, reporter)?;
+
+    let download = || -> Result<()> {
+        let source_path = sftp.realpath(&remote.path)?;
+        let source = RemoteFileEntry::from_path(source_path.clone(), &sftp)?;
+        if source.is_dir() && !matches.is_present("recursive") {
+            return Err(ErrorKind::IsDirectory(
+                source_path.to_string_lossy().to_string(),
+            ));
+        }
+
+        let dest = PathBuf::from(matches.value_of("dest").unwrap_or("."));
+        let dest = if dest.is_dir() {
+            dest.join(file_name(&source_path)?)
+        } else {
+            dest
+        };
+
+        if matches.is_present("dry_run") {
+            // Fail like the transfer would, since it never overwrites anything.
+            if dest.exists() {
+                Err(ErrorKind::LocalFileExists(
+                    dest.to_string_lossy().to_string(),
+                ))
+            } else {
+                plan_transfer(&sftp, source, &LocalFileSystem, dest, &filter)
+                    .map(|actions| report_plan(reporter, &actions))
+            }
+        } else {
+            run_with_progress(reporter, |reporter| {
+                download_recursive(source, dest, &sftp, &filter, reporter)
+            })
+        }
+    };
+    let result = download();
+    // Disconnect even if the download failed.
+    result.and(disconnect(&session))
+}
+
+/// Upload a file or directory to a remote host.
+pub fn run_put(matches: &clap::ArgMatches, config: &Config) -> Result<()> {
+    let reporter = Reporter::new(get_output_mode(matches));
+    let result = put(matches, config, &reporter);
+    reporter.finish(&result);
+    result
+}
+
+fn put(matches: &clap::ArgMatches, config: &Config, reporter: &Reporter) -> Result<()> {
+    let filter = get_filter(matches, config)?;
+    let source_path = PathBuf::from(matches.value_of("source").unwrap());
+    let source = LocalFileEntry::from_path(source_path.canonicalize()?)?;
+    if source.is_dir() && !matches.is_present("recursive") {
+        return Err(ErrorKind::IsDirectory(
+            source_path.to_string_lossy().to_string(),
+        ));
+    }
+
+    let remote: RemotePath = matches.value_of("dest").unwrap().parse()?;
+    let (session, sftp) = remote.connect(matches, config, reporter)?;
+
+    let upload = || -> Result<()> {
+        let dest = match sftp.realpath(&remote.path) {
+            Ok(path) if sftp.stat(&path)?.is_dir() => path.join(file_name(source.path())?),
+            _ => remote.path.clone(),
+        };
+
+        if matches.is_present("dry_run") {
+            // Fail like the transfer would, since it never overwrites anything.
+            match RemoteFileEntry::exists(&dest, &sftp) {
+                Ok(true) => Err(ErrorKind::RemoteFileExists(
+                    dest.to_string_lossy().to_string(),
+                )),
+                Ok(false) => plan_transfer(&LocalFileSystem, source, &sftp, dest, &filter)
+                    .map(|actions| report_plan(reporter, &actions)),
+                Err(error) => Err(error),
+            }
+        } else {
+            run_with_progress(reporter, |reporter| {
+                upload_recursive(source, dest, &sftp, &filter, reporter)
+            })
+        }
+    };
+    let result = upload();
+    result.and(disconnect(&session))
+}
+
+/// Make a remote directory match a local directory, or the other way around.
+pub fn run_sync(matches: &clap::ArgMatches, config: &Config) -> Result<()> {
+    let reporter = Reporter::new(get_output_mode(matches));
+    let result = sync(matches, config, &reporter);
+    reporter.finish(&result);
+    result
+}
+
+fn sync(matches: &clap::ArgMatches, config: &Config, reporter: &Reporter) -> Result<()> {
+    let filter = get_filter(matches, config)?;
+    let source = matches.value_of("source").unwrap();
+    let dest = matches.value_of("dest").unwrap();
+    let (direction, remote, local) = match (source.parse::<RemotePath>(), dest.parse()) {
+        (Err(_), Ok(remote)) => (SyncDirection::Upload, remote, PathBuf::from(source)),
+        (Ok(remote), Err(_)) => (SyncDirection::Download, remote, PathBuf::from(dest)),
+        _ => return Err(ErrorKind::InvalidSyncPaths),
+    };
+    if direction == SyncDirection::Upload && !local.is_dir() {
+        return Err(ErrorKind::NotADirectory(
+            local.to_string_lossy().to_string(),
+        ));
+    }
+
+    let (session, sftp) = remote.connect(matches, config, reporter)?;
+
+    let apply = || -> Result<()> {
+        // The remote directory does not need to exist if it is the destination.
+        let remote_path = sftp
+            .realpath(&remote.path)
+            .unwrap_or_else(|_| remote.path.clone());
+        let (source, dest) = match direction {
+            SyncDirection::Upload => (local.canonicalize()?, remote_path),
+            SyncDirection::Download => {
+                if !sftp.stat(&remote_path)?.is_dir() {
+                    return Err(ErrorKind::NotADirectory(
+                        remote_path.to_string_lossy().to_string(),
+                    ));
+                }
+                (remote_path, local)
+            }
+        };
+
+        let options = SyncOptions {
+            checksum: matches.is_present("checksum"),
+            delete: matches.is_present("delete"),
+        };
+        let actions = plan_sync(&source, &dest, direction, &session, &sftp, &filter, options)?;
+        report_plan(reporter, &actions);
+
+        if !actions.iter().any(PlannedAction::is_change) || matches.is_present("dry_run") {
+            Ok(())
+        } else if matches.is_present("yes") || confirm("Apply these changes?")? {
+            run_with_progress(reporter, |reporter| {
+                apply_sync(&actions, direction, &sftp, reporter)
+            })
+        } else {
+            Ok(())
+        }
+    };
+    let result = apply();
+    result.and(disconnect(&session))
+}
+
+/// Close `session`, telling the server that the transfer is over.
+fn disconnect(session: &ssh2::Session) -> Result<()> {
+    session.disconnect(Some(ssh2::DisconnectCode::ByApplication), "", None)?;
+    Ok(())
+}
+
+/// Ask the user a yes or no `question` on the terminal.
+fn confirm(question: &str) -> Result<bool> {
+    let mut stderr = stderr();
+    write!(stderr, "{} (yes/no) ", question)?;
+    stderr.flush()?;
+    let mut input = String::new();
+    stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim(), "Y" | "y" | "YES" | "Yes" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_options() {
+        let config = Config {
+            io_timeout: Some(Duration::from_secs(20)),
+            server_alive_interval: Some(Duration::from_secs(30)),
+            ..Config::default()
+        };
+        let matches = app().get_matches_from(vec![
+            "rftp",
+            "example.com",
+            "--io-timeout",
+            "0",
+            "--server-alive-count-max",
+            "5",
+            "-C",
+            "--macs",
+            "hmac-sha2-256",
+            "--strict-host-key-checking",
+            "accept-new",
+        ]);
+        let options = get_connect_options(&matches, "me", &config).unwrap();
+        assert_eq!(options.connect_timeout, None);
+        assert_eq!(options.io_timeout, None);
+        assert_eq!(options.server_alive_interval, Some(Duration::from_secs(30)));
+        assert_eq!(options.server_alive_count_max, 5);
+        assert_eq!(options.idle_timeout(), Some(Duration::from_secs(150)));
+        assert!(!options.compression);
+        assert_eq!(options.macs.as_deref(), Some("hmac-sha2-256"));
+        assert_eq!(options.ciphers, None);
+        assert_eq!(
+            options.strict_host_key_checking,
+            StrictHostKeyChecking::AcceptNew
+        );
+        assert!(!options.hash_known_hosts);
+
+        let matches =
+            app().get_matches_from(vec!["rftp", "example.com", "--connect-timeout", "soon"]);
+        assert!(matches!(
+            get_connect_options(&matches, "me", &config),
+            Err(ErrorKind::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn test_remote_path() {
+        assert_eq!(
+            "example.com:/var/log".parse::<RemotePath>().unwrap(),
+            RemotePath {
+                username: None,
+                host: "example.com".to_string(),
+                path: PathBuf::from("/var/log"),
+            }
+        );
+        assert_eq!(
+            "me@example.com:notes.txt".parse::<RemotePath>().unwrap(),
+            RemotePath {
+                username: Some("me".to_string()),
+                host: "example.com".to_string(),
+                path: PathBuf::from("notes.txt"),
+            }
+        );
+        assert_eq!(
+            "example.com:".parse::<RemotePath>().unwrap().path,
+            PathBuf::from(".")
+        );
+        assert!("example.com".parse::<RemotePath>().is_err());
+        assert!(":/var/log".parse::<RemotePath>().is_err());
+        assert!("./odd:name".parse::<RemotePath>().is_err());
+
+        // IPv6 addresses are written in brackets.
+        assert_eq!(
+            "me@[::1]:/var/log".parse::<RemotePath>().unwrap(),
+            RemotePath {
+                username: Some("me".to_string()),
+                host: "::1".to_string(),
+                path: PathBuf::from("/var/log"),
+            }
+        );
+        assert_eq!(
+            "[fe80::1]:a:b".parse::<RemotePath>().unwrap().path,
+            PathBuf::from("a:b")
+        );
+        assert!("[::1]".parse::<RemotePath>().is_err());
+        assert!("[::1:/var/log".parse::<RemotePath>().is_err());
+        assert!("[::1]x:/var/log".parse::<RemotePath>().is_err());
+    }
+}
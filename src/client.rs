@@ -0,0 +1,155 @@
+use crate::connect::{create_session, ConnectOptions};
+use crate::file::*;
+use crate::filter::Filter;
+use crate::progress::ProgressFile;
+use crate::transfer::{download_recursive, upload_recursive, TransferListener};
+use crate::utils::{get_remote_home_dir, ErrorKind, Result};
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An authenticated connection to a host with an open sftp channel.
+pub struct Client {
+    session: ssh2::Session,
+    sftp: ssh2::Sftp,
+}
+
+impl Client {
+    /// Connect to `host` and open an sftp channel.
+    pub fn connect(host: &str, options: &ConnectOptions) -> Result<Client> {
+        let session = create_session(host, options)?;
+        let sftp = session.sftp()?;
+        Ok(Client { session, sftp })
+    }
+
+    /// Return the underlying ssh session.
+    pub fn session(&self) -> &ssh2::Session {
+        &self.session
+    }
+
+    /// Return the underlying sftp channel.
+    pub fn sftp(&self) -> &ssh2::Sftp {
+        &self.sftp
+    }
+
+    /// Return the home directory of the user on the host.
+    pub fn home_dir(&self) -> Result<PathBuf> {
+        get_remote_home_dir(&self.session)
+    }
+
+    /// Return the entry at the remote `path` without following symlinks.
+    pub fn stat(&self, path: impl AsRef<Path>) -> Result<RemoteFileEntry> {
+        Ok(RemoteFileEntry::from_path(
+            path.as_ref().to_path_buf(),
+            &self.sftp,
+        )?)
+    }
+
+    /// Return the entries of the remote directory `path`.
+    ///
+    /// The directories `.` and `..` are not included.
+    pub fn list_dir(&self, path: impl AsRef<Path>) -> Result<Vec<RemoteFileEntry>> {
+        Ok(RemoteFileEntry::read_dir(path.as_ref(), &self.sftp)?)
+    }
+
+    /// Download the remote file `source` to the local path `dest`, which must not exist.
+    pub fn get(
+        &self,
+        source: impl AsRef<Path>,
+        dest: impl AsRef<Path>,
+        listener: &impl TransferListener,
+    ) -> Result<()> {
+        let source = self.stat(source)?;
+        if source.is_dir() {
+            return Err(ErrorKind::IsDirectory(
+                source.path().to_string_lossy().to_string(),
+            ));
+        }
+        let dest = dest.as_ref().to_path_buf();
+        download_recursive(source, dest, &self.sftp, &Filter::default(), listener)
+    }
+
+    /// Upload the local file `source` to the remote path `dest`, which must not exist.
+    pub fn put(
+        &self,
+        source: impl AsRef<Path>,
+        dest: impl AsRef<Path>,
+        listener: &impl TransferListener,
+    ) -> Result<()> {
+        let source = LocalFileEntry::from_path(source.as_ref().to_path_buf())?;
+        if source.is_dir() {
+            return Err(ErrorKind::IsDirectory(
+                source.path().to_string_lossy().to_string(),
+            ));
+        }
+        let dest = dest.as_ref().to_path_buf();
+        upload_recursive(source, dest, &self.sftp, &Filter::default(), listener)
+    }
+
+    /// Download the remote file or directory `source` to the local path `dest`, which must
+    /// not exist, skipping the entries that are excluded by `filter`.
+    pub fn get_recursive(
+        &self,
+        source: impl AsRef<Path>,
+        dest: impl AsRef<Path>,
+        filter: &Filter,
+        listener: &impl TransferListener,
+    ) -> Result<()> {
+        let source = self.stat(source)?;
+        let dest = dest.as_ref().to_path_buf();
+        download_recursive(source, dest, &self.sftp, filter, listener)
+    }
+
+    /// Upload the local file or directory `source` to the remote path `dest`, which must
+    /// not exist, skipping the entries that are excluded by `filter`.
+    pub fn put_recursive(
+        &self,
+        source: impl AsRef<Path>,
+        dest: impl AsRef<Path>,
+        filter: &Filter,
+        listener: &impl TransferListener,
+    ) -> Result<()> {
+        let source = LocalFileEntry::from_path(source.as_ref().to_path_buf())?;
+        let dest = dest.as_ref().to_path_buf();
+        upload_recursive(source, dest, &self.sftp, filter, listener)
+    }
+
+    /// Close the connection.
+    pub fn disconnect(self) -> Result<()> {
+        self.session
+            .disconnect(Some(ssh2::DisconnectCode::ByApplication), "", None)?;
+        Ok(())
+    }
+}
+
+/// Calls a closure with the progress of each file as it is transferred.
+///
+/// ```no_run
+/// # use rftp::{Client, ConnectOptions, ProgressCallback};
+/// # fn main() -> rftp::Result<()> {
+/// let client = Client::connect("example.com", &ConnectOptions::new("me"))?;
+/// let listener = ProgressCallback(|progress: &rftp::progress::ProgressFile| {
+///     println!("{} {}", progress.get_title(), progress.get_info());
+/// });
+/// client.get("notes.txt", "notes.txt", &listener)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProgressCallback<F>(pub F);
+
+impl<F> TransferListener for ProgressCallback<F>
+where
+    F: Fn(&ProgressFile),
+{
+    fn file_started(&self, _progress: Arc<ProgressFile>) {}
+
+    fn file_finished(&self, progress: &ProgressFile) {
+        (self.0)(progress);
+    }
+
+    fn progress(&self, progress: &ProgressFile) {
+        (self.0)(progress);
+    }
+
+    fn warn(&self, _message: &str) {}
+}
@@ -0,0 +1,210 @@
+use rftp::utils::{CommandOutput, Result};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const COMMAND_PANE_COLOR: Color = Color::Magenta;
+
+/// What the user did with the command pane after a key press.
+#[derive(Debug, PartialEq)]
+pub enum CommandPaneResult {
+    /// The pane stays open.
+    Pending,
+    /// The user pressed `q` or escape.
+    Close,
+}
+
+/// A pane that shows what a remote command printed and how it exited.
+pub struct CommandPane {
+    command: String,
+    /// The output of the command once it exited, or why it could not be run.
+    output: Arc<Mutex<Option<std::result::Result<CommandOutput, String>>>>,
+    /// The number of lines above the top of the pane.
+    scroll: usize,
+    /// The most lines that could be scrolled when the pane was last drawn.
+    max_scroll: Cell<usize>,
+}
+
+impl CommandPane {
+    /// Show `command` while `run` runs it in the background.
+    pub fn spawn<F>(command: &str, run: F) -> Self
+    where
+        F: FnOnce() -> Result<CommandOutput> + Send + 'static,
+    {
+        let output = Arc::new(Mutex::new(None));
+        {
+            let output = Arc::clone(&output);
+            thread::spawn(move || {
+                let result = run().map_err(|error| error.to_string());
+                *output.lock().unwrap() = Some(result);
+            });
+        }
+        CommandPane {
+            command: command.to_string(),
+            output,
+            scroll: 0,
+            max_scroll: Cell::new(0),
+        }
+    }
+
+    /// Scroll the output or close the pane with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> CommandPaneResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('q'), KeyModifiers::NONE) | (KeyCode::Esc, _) => {
+                return CommandPaneResult::Close
+            }
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => self.scroll(1),
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => self.scroll(-1),
+            (KeyCode::Char('g'), KeyModifiers::NONE) | (KeyCode::Home, _) => self.scroll = 0,
+            (KeyCode::Char('G'), _) | (KeyCode::End, _) => self.scroll = self.max_scroll.get(),
+            _ => {}
+        }
+        CommandPaneResult::Pending
+    }
+
+    /// Scroll `lines` lines down, or up if negative.
+    pub fn scroll(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.max_scroll.get());
+    }
+
+    /// Return the lines of the output with their styles.
+    fn get_lines(&self) -> Vec<(String, Style)> {
+        let to_lines = |bytes: &[u8], style: Style| -> Vec<(String, Style)> {
+            String::from_utf8_lossy(bytes)
+                .lines()
+                .map(|line| (line.replace('\t', "    "), style))
+                .collect()
+        };
+        match &*self.output.lock().unwrap() {
+            None => vec![("Running...".to_string(), Style::default())],
+            Some(Err(error)) => vec![(
+                format!("Error: {}.", error),
+                Style::default().fg(Color::Red),
+            )],
+            Some(Ok(output)) => {
+                let mut lines = to_lines(&output.output, Style::default());
+                let style = if output.exit_status == 0 {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                };
+                lines.push((format!("exit status {}", output.exit_status), style));
+                lines
+            }
+        }
+    }
+
+    /// Draw this pane on top of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let block = Block::default()
+            .title(format!("$ {}", self.command))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(COMMAND_PANE_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(inner);
+
+        let lines = self.get_lines();
+        self.max_scroll
+            .set(lines.len().saturating_sub(chunks[0].height as usize));
+        let lines: Vec<Spans> = lines
+            .into_iter()
+            .skip(self.scroll.min(self.max_scroll.get()))
+            .map(|(line, style)| Spans::from(Span::styled(line, style)))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let footer = Span::styled(
+            "j/k: scroll  q: close",
+            Style::default().add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(Paragraph::new(Spans::from(footer)), chunks[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::utils::assert_buffer_symbols_eq;
+    use std::time::{Duration, Instant};
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    /// Wait for the command of `pane` to exit.
+    fn wait(pane: &CommandPane) {
+        let start = Instant::now();
+        while pane.output.lock().unwrap().is_none() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_draw_command_pane() {
+        let mut terminal = Terminal::new(TestBackend::new(30, 6)).unwrap();
+        let mut pane = CommandPane::spawn("ls missing", || {
+            Ok(CommandOutput {
+                output: b"a\nb\nls: missing: No such file\n".to_vec(),
+                exit_status: 2,
+            })
+        });
+        wait(&pane);
+
+        let mut draw = |pane: &CommandPane| {
+            terminal
+                .draw(|frame| {
+                    let rect = frame.size();
+                    pane.draw(frame, rect);
+                })
+                .unwrap();
+            terminal.backend().buffer().clone()
+        };
+        assert_buffer_symbols_eq(
+            &draw(&pane),
+            &Buffer::with_lines(vec![
+                "┌$ ls missing────────────────┐",
+                "│a                           │",
+                "│b                           │",
+                "│ls: missing: No such file   │",
+                "│j/k: scroll  q: close       │",
+                "└────────────────────────────┘",
+            ]),
+        );
+
+        pane.on_event(KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT));
+        assert_buffer_symbols_eq(
+            &draw(&pane),
+            &Buffer::with_lines(vec![
+                "┌$ ls missing────────────────┐",
+                "│b                           │",
+                "│ls: missing: No such file   │",
+                "│exit status 2               │",
+                "│j/k: scroll  q: close       │",
+                "└────────────────────────────┘",
+            ]),
+        );
+        assert_eq!(
+            pane.on_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)),
+            CommandPaneResult::Close
+        );
+    }
+}
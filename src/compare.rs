@@ -0,0 +1,228 @@
+use crate::file::FileEntry;
+use crate::filesystem::FileSystem;
+use crate::utils::Result;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// What is compared to decide if two files with the same name are the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareBy {
+    /// The size and the modification time.
+    SizeAndTime,
+    /// Only the size, for files that were copied without keeping their modification time.
+    Size,
+}
+
+/// How an entry compares with the entry of the same name on the other side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// The other side has no entry with this name.
+    Unique,
+    /// The other side has an entry with this name of another kind, size or modification time.
+    Different,
+    /// The other side has the same file, or a directory with the same name.
+    Same,
+}
+
+/// Return how `entry` compares with `other`, which has the same name.
+///
+/// Directories are the same if they have the same name, their contents are not compared.
+pub fn compare_entry(entry: &impl FileEntry, other: &impl FileEntry, by: CompareBy) -> Comparison {
+    let is_same = if entry.is_file() && other.is_file() {
+        entry.len() == other.len()
+            && (by == CompareBy::Size || entry.modified() == other.modified())
+    } else {
+        entry.is_file() == other.is_file() && entry.is_dir() == other.is_dir()
+    };
+    if is_same {
+        Comparison::Same
+    } else {
+        Comparison::Different
+    }
+}
+
+/// Return the entries that are not parent directories by their file names.
+fn by_name<E: FileEntry>(entries: &[E]) -> BTreeMap<String, &E> {
+    entries
+        .iter()
+        .filter(|entry| !entry.is_parent())
+        .filter_map(|entry| Some((entry.file_name_lossy()?.to_string(), entry)))
+        .collect()
+}
+
+/// Return how each entry of `entries` compares with the entry of the same name in `others`.
+///
+/// Parent directories are always the same.
+pub fn compare_lists<E, O>(entries: &[E], others: &[O], by: CompareBy) -> Vec<Comparison>
+where
+    E: FileEntry,
+    O: FileEntry,
+{
+    let others = by_name(others);
+    entries
+        .iter()
+        .map(|entry| {
+            if entry.is_parent() {
+                return Comparison::Same;
+            }
+            let other = entry
+                .file_name_lossy()
+                .and_then(|name| others.get(name.as_ref()).copied());
+            match other {
+                Some(other) => compare_entry(entry, other, by),
+                None => Comparison::Unique,
+            }
+        })
+        .collect()
+}
+
+/// The number of entries that differ between two directory trees.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompareSummary {
+    /// The entries that only exist on the left, where a directory counts as one entry.
+    pub only_left: usize,
+    /// The entries that only exist on the right, where a directory counts as one entry.
+    pub only_right: usize,
+    /// The entries that exist on both sides but are of another kind, size or modification time.
+    pub different: usize,
+    /// The files that are the same on both sides.
+    pub same: usize,
+}
+
+impl CompareSummary {
+    /// Return true if both trees are the same.
+    pub fn is_same(&self) -> bool {
+        self.only_left == 0 && self.only_right == 0 && self.different == 0
+    }
+}
+
+/// Compare the directory `left` of `left_fs` with the directory `right` of `right_fs` and
+/// everything below them.
+pub fn compare_trees<L, R>(
+    left_fs: &L,
+    left: &Path,
+    right_fs: &R,
+    right: &Path,
+    by: CompareBy,
+) -> Result<CompareSummary>
+where
+    L: FileSystem + ?Sized,
+    R: FileSystem + ?Sized,
+{
+    let mut summary = CompareSummary::default();
+    let mut stack: Vec<(PathBuf, PathBuf)> = vec![(left.to_path_buf(), right.to_path_buf())];
+    while let Some((left, right)) = stack.pop() {
+        let left_entries = left_fs.read_dir(&left)?;
+        let right_entries = right_fs.read_dir(&right)?;
+        for (entry, comparison) in
+            left_entries
+                .iter()
+                .zip(compare_lists(&left_entries, &right_entries, by))
+        {
+            match comparison {
+                Comparison::Unique => summary.only_left += 1,
+                Comparison::Different => summary.different += 1,
+                Comparison::Same if entry.is_dir() => {
+                    let name = entry.path().file_name().unwrap_or_default();
+                    stack.push((entry.path().to_path_buf(), right.join(name)));
+                }
+                Comparison::Same => summary.same += 1,
+            }
+        }
+        summary.only_right += compare_lists(&right_entries, &left_entries, by)
+            .into_iter()
+            .filter(|comparison| *comparison == Comparison::Unique)
+            .count();
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{LocalFileEntry, RemoteFileEntry};
+    use crate::filesystem::LocalFileSystem;
+
+    fn local_file(name: &str, len: u64, modified: Option<u64>) -> LocalFileEntry {
+        LocalFileEntry::File(PathBuf::from("/l").join(name), len, modified)
+    }
+
+    fn remote_file(name: &str, len: u64, modified: Option<u64>) -> RemoteFileEntry {
+        RemoteFileEntry::File(PathBuf::from("/r").join(name), len, modified)
+    }
+
+    #[test]
+    fn test_compare_lists() {
+        let local = vec![
+            LocalFileEntry::Parent(PathBuf::from("/")),
+            local_file("same.txt", 3, Some(10)),
+            local_file("newer.txt", 3, Some(20)),
+            local_file("longer.txt", 4, Some(10)),
+            local_file("local.txt", 1, None),
+            LocalFileEntry::Directory(PathBuf::from("/l/dir")),
+            local_file("kind", 1, None),
+        ];
+        let remote = vec![
+            RemoteFileEntry::Parent(PathBuf::from("/")),
+            remote_file("same.txt", 3, Some(10)),
+            remote_file("newer.txt", 3, Some(10)),
+            remote_file("longer.txt", 3, Some(10)),
+            RemoteFileEntry::Directory(PathBuf::from("/r/dir")),
+            RemoteFileEntry::Directory(PathBuf::from("/r/kind")),
+            remote_file("remote.txt", 1, None),
+        ];
+        use Comparison::*;
+        assert_eq!(
+            compare_lists(&local, &remote, CompareBy::SizeAndTime),
+            vec![Same, Same, Different, Different, Unique, Same, Different]
+        );
+        assert_eq!(
+            compare_lists(&local, &remote, CompareBy::Size),
+            vec![Same, Same, Same, Different, Unique, Same, Different]
+        );
+        assert_eq!(
+            compare_lists(&remote, &local, CompareBy::Size),
+            vec![Same, Same, Same, Different, Same, Different, Unique]
+        );
+    }
+
+    #[test]
+    fn test_compare_trees() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-compare-{}", std::process::id()));
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            fs.set_modified(&path, 1_000_000).unwrap();
+        };
+        write("left/same.txt", "same");
+        write("left/sub/changed.txt", "old");
+        write("left/sub/deep/only-left.txt", "a");
+        write("left/only-left/a.txt", "a");
+        write("right/same.txt", "same");
+        write("right/sub/changed.txt", "new!");
+        write("right/sub/deep/only-right.txt", "b");
+
+        let summary = compare_trees(
+            &fs,
+            &root.join("left"),
+            &fs,
+            &root.join("right"),
+            CompareBy::SizeAndTime,
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            CompareSummary {
+                only_left: 2,
+                only_right: 1,
+                different: 1,
+                same: 1,
+            }
+        );
+        assert!(!summary.is_same());
+        fs.remove_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,267 @@
+use crate::connect::{parse_count, parse_seconds, ConnectOptions};
+use crate::filter::Filter;
+use crate::known_hosts::StrictHostKeyChecking;
+use crate::utils::{ErrorKind, Result};
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Settings that are read from `~/.config/rftp/config` on startup.
+///
+/// Each line holds a keyword followed by its value, like `ssh_config`.
+/// ```text
+/// # Never transfer build output.
+/// Exclude target/
+/// Exclude node_modules/
+/// IgnoreFiles yes
+/// LogFile ~/.cache/rftp.log
+/// ServerAliveInterval 30
+/// ```
+#[derive(Clone, Default)]
+pub struct Config {
+    /// The default patterns used by recursive transfers.
+    pub filter: Filter,
+    /// The file that every message shown in the interface is appended to.
+    pub log_file: Option<PathBuf>,
+    /// How long to wait for the TCP connection, where zero waits as long as the system does.
+    pub connect_timeout: Option<Duration>,
+    /// How long a blocking call waits for the server, where zero waits forever.
+    pub io_timeout: Option<Duration>,
+    /// How long a connection may be idle before a keepalive is sent, where zero sends none.
+    pub server_alive_interval: Option<Duration>,
+    /// How many keepalives in a row may go unanswered before the connection is treated as lost.
+    pub server_alive_count_max: Option<u32>,
+    /// How long the server may send nothing before the connection is treated as lost, where
+    /// zero uses the keepalive interval times the count.
+    pub idle_timeout: Option<Duration>,
+    /// Compress the data that is sent.
+    pub compression: Option<bool>,
+    /// The ciphers to prefer, comma-separated.
+    pub ciphers: Option<String>,
+    /// The key exchange algorithms to prefer, comma-separated.
+    pub kex_algorithms: Option<String>,
+    /// The host key algorithms to prefer, comma-separated.
+    pub host_key_algorithms: Option<String>,
+    /// The message authentication codes to prefer, comma-separated.
+    pub macs: Option<String>,
+    /// What to do when the host key is not in the known hosts file.
+    pub strict_host_key_checking: Option<StrictHostKeyChecking>,
+    /// Hash the host names that are added to the known hosts file.
+    pub hash_known_hosts: Option<bool>,
+}
+
+impl Config {
+    /// Return the path of the config file.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rftp").join("config"))
+    }
+
+    /// Read the config file, or return the default config if it does not exist.
+    pub fn load() -> Result<Config> {
+        match Config::default_path() {
+            Some(path) if path.is_file() => Config::read(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Read the config file at `path`.
+    pub fn read(path: &Path) -> Result<Config> {
+        let contents = std::fs::read_to_string(path)?;
+        Config::parse(&contents).map_err(|(line, message)| {
+            ErrorKind::InvalidConfig(path.to_string_lossy().to_string(), line, message)
+        })
+    }
+
+    /// Replace the defaults of `options` with the connection settings of this config.
+    pub fn apply_to(&self, options: &mut ConnectOptions) {
+        let nonzero = |duration: Duration| Some(duration).filter(|d| !d.is_zero());
+        if let Some(timeout) = self.connect_timeout {
+            options.connect_timeout = nonzero(timeout);
+        }
+        if let Some(timeout) = self.io_timeout {
+            options.io_timeout = nonzero(timeout);
+        }
+        if let Some(interval) = self.server_alive_interval {
+            options.server_alive_interval = nonzero(interval);
+        }
+        if let Some(count) = self.server_alive_count_max {
+            options.server_alive_count_max = count;
+        }
+        if let Some(timeout) = self.idle_timeout {
+            options.idle_timeout = nonzero(timeout);
+        }
+        if let Some(compression) = self.compression {
+            options.compression = compression;
+        }
+        if let Some(checking) = self.strict_host_key_checking {
+            options.strict_host_key_checking = checking;
+        }
+        if let Some(hash) = self.hash_known_hosts {
+            options.hash_known_hosts = hash;
+        }
+        for (prefs, option) in [
+            (&self.ciphers, &mut options.ciphers),
+            (&self.kex_algorithms, &mut options.kex_algorithms),
+            (&self.host_key_algorithms, &mut options.host_key_algorithms),
+            (&self.macs, &mut options.macs),
+        ] {
+            if prefs.is_some() {
+                option.clone_from(prefs);
+            }
+        }
+    }
+
+    /// Parse the contents of a config file, or return the line number and reason it is invalid.
+    fn parse(contents: &str) -> std::result::Result<Config, (usize, String)> {
+        let mut config = Config::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, value) = line
+                .split_once(|c: char| c.is_whitespace() || c == '=')
+                .map(|(keyword, value)| (keyword, value.trim_start_matches('=').trim()))
+                .ok_or_else(|| (line_number, format!("missing value for \"{}\"", line)))?;
+            let seconds = |value: &str| {
+                parse_seconds(value).map_err(|error| (line_number, error.to_string()))
+            };
+            let yes_no = |value: &str| {
+                parse_yes_no(value).ok_or_else(|| {
+                    (
+                        line_number,
+                        format!("expected yes or no, found \"{}\"", value),
+                    )
+                })
+            };
+            match keyword.to_lowercase().as_str() {
+                "exclude" => config.filter.exclude(value),
+                "include" => config.filter.include(value),
+                "excludefrom" => config
+                    .filter
+                    .exclude_from(&expand_tilde(value))
+                    .map_err(|error| (line_number, error.to_string()))?,
+                "logfile" => config.log_file = Some(expand_tilde(value)),
+                "connecttimeout" => config.connect_timeout = Some(seconds(value)?),
+                "iotimeout" => config.io_timeout = Some(seconds(value)?),
+                "serveraliveinterval" => config.server_alive_interval = Some(seconds(value)?),
+                "serveralivecountmax" => {
+                    config.server_alive_count_max =
+                        Some(parse_count(value).map_err(|error| (line_number, error.to_string()))?)
+                }
+                "idletimeout" => config.idle_timeout = Some(seconds(value)?),
+                "compression" => config.compression = Some(yes_no(value)?),
+                "ciphers" => config.ciphers = Some(value.to_string()),
+                "kexalgorithms" => config.kex_algorithms = Some(value.to_string()),
+                "hostkeyalgorithms" => config.host_key_algorithms = Some(value.to_string()),
+                "macs" => config.macs = Some(value.to_string()),
+                "stricthostkeychecking" => {
+                    config.strict_host_key_checking =
+                        Some(StrictHostKeyChecking::parse(value).ok_or_else(|| {
+                            (
+                                line_number,
+                                format!("expected yes, no, ask or accept-new, found \"{}\"", value),
+                            )
+                        })?)
+                }
+                "hashknownhosts" => config.hash_known_hosts = Some(yes_no(value)?),
+                "ignorefiles" => config.filter.set_use_ignore_files(yes_no(value)?),
+                _ => return Err((line_number, format!("unknown keyword \"{}\"", keyword))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parse a `yes` or `no` value.
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" => Some(true),
+        "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Replace a leading `~` in `path` with the home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "# Comment\n\
+             Exclude target/\n\
+             exclude=node_modules/\n\
+             \n\
+             IgnoreFiles yes\n\
+             LogFile /tmp/rftp.log\n\
+             ServerAliveInterval 30\n\
+             ServerAliveCountMax 5\n\
+             IOTimeout 0\n\
+             IdleTimeout 120\n\
+             Compression no\n\
+             Ciphers aes256-ctr,aes128-ctr\n\
+             StrictHostKeyChecking accept-new\n\
+             HashKnownHosts yes\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.filter.get_excludes(),
+            &["target/".to_string(), "node_modules/".to_string()]
+        );
+        assert!(config.filter.uses_ignore_files());
+        assert_eq!(config.log_file, Some(PathBuf::from("/tmp/rftp.log")));
+
+        let mut options = ConnectOptions::default();
+        config.apply_to(&mut options);
+        assert_eq!(options.server_alive_interval, Some(Duration::from_secs(30)));
+        assert_eq!(options.server_alive_count_max, 5);
+        assert_eq!(options.io_timeout, None);
+        assert_eq!(options.idle_timeout(), Some(Duration::from_secs(120)));
+        assert_eq!(options.connect_timeout, None);
+        assert!(!options.compression);
+        assert_eq!(options.ciphers.as_deref(), Some("aes256-ctr,aes128-ctr"));
+        assert_eq!(options.macs, None);
+        assert_eq!(
+            options.strict_host_key_checking,
+            StrictHostKeyChecking::AcceptNew
+        );
+        assert!(options.hash_known_hosts);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Config::parse("Exclude a\nFoo bar\n").err(),
+            Some((2, "unknown keyword \"Foo\"".to_string()))
+        );
+        assert_eq!(
+            Config::parse("IgnoreFiles maybe").err(),
+            Some((1, "expected yes or no, found \"maybe\"".to_string()))
+        );
+        assert_eq!(
+            Config::parse("ConnectTimeout soon").err(),
+            Some((1, "invalid number \"soon\"".to_string()))
+        );
+        assert_eq!(
+            Config::parse("StrictHostKeyChecking maybe").err(),
+            Some((
+                1,
+                "expected yes, no, ask or accept-new, found \"maybe\"".to_string()
+            ))
+        );
+        assert_eq!(
+            Config::parse("Exclude").err(),
+            Some((1, "missing value for \"Exclude\"".to_string()))
+        );
+    }
+}
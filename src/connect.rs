@@ -1,120 +1,457 @@
+use crate::known_hosts::{host_name, Check, KnownHosts, StrictHostKeyChecking};
 use crate::utils::{ErrorKind, Result};
 
 use dirs::home_dir;
-use rpassword::prompt_password_stdout;
+use rpassword::prompt_password_stderr;
 use std::collections::HashSet;
-use std::io::{stdin, stdout, Write};
-use std::net::TcpStream;
+use std::io::{stderr, stdin, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a blocking call waits for the server unless told otherwise.
+pub const DEFAULT_IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many keepalives in a row may go unanswered unless told otherwise.
+pub const DEFAULT_SERVER_ALIVE_COUNT_MAX: u32 = 3;
+
+/// How to connect to a host and authenticate with it.
+#[derive(Clone, Debug)]
+pub struct ConnectOptions {
+    /// The user to log in as.
+    pub username: String,
+    /// The port to connect to, or `None` to use the default port.
+    pub port: Option<u16>,
+    /// The password to log in with instead of asking the user for one.
+    pub password: Option<String>,
+    /// Print each step of connecting to stderr.
+    pub verbose: bool,
+    /// The file to check the host key against instead of `~/.ssh/known_hosts`.
+    pub known_hosts: Option<PathBuf>,
+    /// How long to wait for the TCP connection, or `None` to wait as long as the system does.
+    pub connect_timeout: Option<Duration>,
+    /// How long a blocking call waits for the server, or `None` to wait forever.
+    pub io_timeout: Option<Duration>,
+    /// How long the connection may be idle before a keepalive is sent, like
+    /// `ServerAliveInterval` of `ssh`, or `None` to never send one.
+    pub server_alive_interval: Option<Duration>,
+    /// How many keepalives in a row may go unanswered before the connection is treated as lost.
+    pub server_alive_count_max: u32,
+    /// How long the server may send nothing before the connection is treated as lost, or
+    /// `None` for `server_alive_interval` times `server_alive_count_max`.
+    pub idle_timeout: Option<Duration>,
+    /// Compress the data that is sent, which helps on slow links but slows down fast ones.
+    pub compression: bool,
+    /// The ciphers to prefer, comma-separated like `Ciphers` of `ssh_config`.
+    pub ciphers: Option<String>,
+    /// The key exchange algorithms to prefer, comma-separated.
+    pub kex_algorithms: Option<String>,
+    /// The host key algorithms to prefer, comma-separated.
+    pub host_key_algorithms: Option<String>,
+    /// The message authentication codes to prefer, comma-separated.
+    pub macs: Option<String>,
+    /// What to do when the host key is not in the known hosts file.
+    pub strict_host_key_checking: StrictHostKeyChecking,
+    /// Hash the host names that are added to the known hosts file, like `HashKnownHosts`.
+    pub hash_known_hosts: bool,
+    /// Ask the user for a password or whether to trust a new host key, and print what went
+    /// wrong to stderr. Otherwise only the password above, the ssh agent and the known hosts
+    /// file are used, which is how a lost connection is restored while the terminal is busy.
+    pub interactive: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions {
+            username: String::new(),
+            port: None,
+            password: None,
+            verbose: false,
+            known_hosts: None,
+            connect_timeout: None,
+            io_timeout: Some(DEFAULT_IO_TIMEOUT),
+            server_alive_interval: None,
+            server_alive_count_max: DEFAULT_SERVER_ALIVE_COUNT_MAX,
+            idle_timeout: None,
+            compression: true,
+            ciphers: None,
+            kex_algorithms: None,
+            host_key_algorithms: None,
+            macs: None,
+            strict_host_key_checking: StrictHostKeyChecking::Ask,
+            hash_known_hosts: false,
+            interactive: true,
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// Return the options to log in as `username` with the defaults for everything else.
+    pub fn new(username: &str) -> Self {
+        ConnectOptions {
+            username: username.to_string(),
+            ..ConnectOptions::default()
+        }
+    }
+
+    /// Return how often to make sure that an idle server is alive, or `None` to never do so.
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.server_alive_interval.or_else(|| {
+            self.idle_timeout
+                .map(|timeout| timeout / self.server_alive_count_max.max(1))
+        })
+    }
+
+    /// Return how long the server may send nothing before the connection is treated as lost,
+    /// or `None` if that is never checked.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout.or_else(|| {
+            self.server_alive_interval
+                .map(|interval| interval * self.server_alive_count_max.max(1))
+        })
+    }
+}
+
+/// Split a destination written as `[user@]host[:rest]` into its user, host and the rest
+/// after the colon.
+///
+/// An IPv6 address has to be written in brackets, like `[::1]:22`, and the host is returned
+/// without them. Return `None` if a bracket is not closed or is followed by anything but a
+/// colon.
+pub fn split_destination(s: &str) -> Option<(Option<&str>, &str, Option<&str>)> {
+    // The user name ends at the last `@` before the host, which cannot contain one.
+    let login_end = s.find([':', '[']).unwrap_or(s.len());
+    let (username, rest) = match s[..login_end].rfind('@') {
+        Some(i) => (Some(&s[..i]), &s[i + 1..]),
+        None => (None, s),
+    };
+    if let Some(bracketed) = rest.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']')?;
+        match after {
+            "" => Some((username, host, None)),
+            _ => Some((username, host, Some(after.strip_prefix(':')?))),
+        }
+    } else {
+        match rest.split_once(':') {
+            Some((host, after)) => Some((username, host, Some(after))),
+            None => Some((username, rest, None)),
+        }
+    }
+}
+
+/// Parse the port number `port` given by the user.
+pub fn parse_port(port: &str) -> Result<u16> {
+    port.parse::<u16>().or(Err(ErrorKind::InvalidPortNumber))
+}
+
+/// Parse a whole number of `seconds` given by the user, where zero turns a timeout off.
+pub fn parse_seconds(seconds: &str) -> Result<Duration> {
+    parse_count(seconds).map(|seconds| Duration::from_secs(seconds.into()))
+}
+
+/// Parse a `count` given by the user.
+pub fn parse_count(count: &str) -> Result<u32> {
+    count
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| ErrorKind::InvalidNumber(count.to_string()))
+}
 
 /// Create an authenticated `ssh2::Session`.
-pub fn create_session(
+pub fn create_session(destination: &str, options: &ConnectOptions) -> Result<ssh2::Session> {
+    connect(destination, options).map(|(session, _)| session)
+}
+
+/// Create an authenticated `ssh2::Session` and keep the password that the user entered in
+/// `options`, so that connecting again does not ask for it.
+pub fn create_session_remembering_password(
     destination: &str,
-    username: &str,
-    port: Option<&str>,
-    verbose: bool,
+    options: &mut ConnectOptions,
 ) -> Result<ssh2::Session> {
-    let tcp = if let Some(port) = port {
-        let port = port.parse::<u16>().or(Err(ErrorKind::InvalidPortNumber))?;
+    let (session, password) = connect(destination, options)?;
+    if password.is_some() {
+        options.password = password;
+    }
+    Ok(session)
+}
+
+/// Create an authenticated `ssh2::Session` and return the password that the user entered,
+/// if any.
+fn connect(destination: &str, options: &ConnectOptions) -> Result<(ssh2::Session, Option<String>)> {
+    let username = options.username.as_str();
+    let verbose = options.verbose;
+    let timeout = options.connect_timeout;
+    let tcp = if let Some(port) = options.port {
         if verbose {
-            println!("Attempting to connect to {}:{}.", destination, port);
+            eprintln!("Attempting to connect to {}:{}.", destination, port);
         }
-        TcpStream::connect((destination, port))?
+        connect_tcp((destination, port), timeout)?
     } else {
         if verbose {
-            println!("Attempting to connect to {}.", destination);
+            eprintln!("Attempting to connect to {}.", destination);
         }
-        TcpStream::connect(destination).unwrap_or(TcpStream::connect((destination, 22))?)
+        connect_tcp(destination, timeout).or_else(|_| connect_tcp((destination, 22), timeout))?
     };
     let port = tcp.peer_addr()?.port();
 
     let mut session = ssh2::Session::new()?;
-    session.set_timeout(10000);
-    session.set_compress(true);
+    // A timeout of zero waits forever.
+    session.set_timeout(
+        options
+            .io_timeout
+            .map_or(0, |timeout| timeout.as_millis() as u32),
+    );
+    if let Some(interval) = options.keepalive_interval() {
+        session.set_keepalive(true, interval.as_secs().max(1) as u32);
+    }
+    session.set_compress(options.compression);
+    set_method_prefs(&session, options)?;
     session.set_tcp_stream(tcp);
     session.handshake()?;
+    if verbose {
+        print_methods(&session);
+    }
 
-    let session = authenticate_host(session, destination, port, verbose)?;
-    let session = authenticate_session(session, username)?;
+    let (session, key_changed) = authenticate_host(session, destination, port, options)?;
+    let (session, password) = authenticate_session(
+        session,
+        username,
+        options.password.as_deref(),
+        options.interactive,
+        key_changed,
+    )?;
 
     if verbose {
-        println!("Connected to host {}@{}:{}.", username, destination, port);
+        eprintln!("Connected to host {}@{}:{}.", username, destination, port);
     }
 
-    Ok(session)
+    Ok((session, password))
+}
+
+/// Prefer the methods of `options` in the handshake of `session`.
+fn set_method_prefs(session: &ssh2::Session, options: &ConnectOptions) -> Result<()> {
+    let prefs = [
+        (
+            ssh2::MethodType::Kex,
+            "key exchange algorithms",
+            &options.kex_algorithms,
+        ),
+        (
+            ssh2::MethodType::HostKey,
+            "host key algorithms",
+            &options.host_key_algorithms,
+        ),
+        (ssh2::MethodType::CryptCs, "ciphers", &options.ciphers),
+        (ssh2::MethodType::CryptSc, "ciphers", &options.ciphers),
+        (ssh2::MethodType::MacCs, "MACs", &options.macs),
+        (ssh2::MethodType::MacSc, "MACs", &options.macs),
+    ];
+    for (method_type, name, prefs) in prefs {
+        if let Some(prefs) = prefs {
+            session
+                .method_pref(method_type, prefs)
+                .map_err(|_| ErrorKind::UnsupportedMethods(name.to_string(), prefs.clone()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the methods that were negotiated in the handshake of `session`.
+fn print_methods(session: &ssh2::Session) {
+    let method = |method_type| session.methods(method_type).unwrap_or("unknown");
+    eprintln!("Key exchange: {}", method(ssh2::MethodType::Kex));
+    eprintln!("Host key: {}", method(ssh2::MethodType::HostKey));
+    eprintln!(
+        "Ciphers: {} to the server, {} from the server",
+        method(ssh2::MethodType::CryptCs),
+        method(ssh2::MethodType::CryptSc)
+    );
+    eprintln!(
+        "MACs: {} to the server, {} from the server",
+        method(ssh2::MethodType::MacCs),
+        method(ssh2::MethodType::MacSc)
+    );
+    eprintln!(
+        "Compression: {} to the server, {} from the server",
+        method(ssh2::MethodType::CompCs),
+        method(ssh2::MethodType::CompSc)
+    );
 }
 
-/// Authenticate the identity of the host by checking the host key in `~/.ssh/known_hosts`.
+/// Open a TCP connection to the first address of `address` that answers within `timeout`.
+fn connect_tcp(address: impl ToSocketAddrs, timeout: Option<Duration>) -> Result<TcpStream> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return Ok(TcpStream::connect(address)?),
+    };
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| std::io::ErrorKind::AddrNotAvailable.into())
+        .into())
+}
+
+/// Check the host key of `session` against the known hosts file, and add it if the user or
+/// `strict_host_key_checking` allows it. Return the session and whether the known key of the
+/// host changed.
 fn authenticate_host(
     session: ssh2::Session,
     destination: &str,
     port: u16,
-    verbose: bool,
-) -> Result<ssh2::Session> {
-    let mut known_hosts = session.known_hosts()?;
-    let known_hosts_path = home_dir()
-        .ok_or(ErrorKind::UnableToFindHomeDirectory)?
-        .join(".ssh/known_hosts");
-    known_hosts.read_file(&known_hosts_path, ssh2::KnownHostFileKind::OpenSSH)?;
-    let (key, key_type) = session.host_key().ok_or(ErrorKind::HostKeyNotFound)?;
-    match known_hosts.check_port(destination, port, key) {
-        ssh2::CheckResult::Match => {
-            if verbose {
-                println!(
-                    "Host key for {}:{} matches entry in {:?}.",
-                    destination, port, known_hosts_path
+    options: &ConnectOptions,
+) -> Result<(ssh2::Session, bool)> {
+    let known_hosts_path = match &options.known_hosts {
+        Some(path) => path.clone(),
+        None => home_dir()
+            .ok_or(ErrorKind::UnableToFindHomeDirectory)?
+            .join(".ssh/known_hosts"),
+    };
+    let known_hosts = KnownHosts::read(&known_hosts_path)?;
+    let (key, _) = session.host_key().ok_or(ErrorKind::HostKeyNotFound)?;
+    let name = host_name(destination, port);
+    match known_hosts.check(destination, port, key) {
+        Check::Match => {
+            if options.verbose {
+                eprintln!(
+                    "Host key for {} matches entry in {:?}.",
+                    name, known_hosts_path
                 );
             }
-            Ok(session)
-        }
-        ssh2::CheckResult::NotFound => {
-            let fingerprint = session
-                .host_key_hash(ssh2::HashType::Sha256)
-                .map(|hash| ("SHA256", hash))
-                .or_else(|| {
-                    session
-                        .host_key_hash(ssh2::HashType::Sha1)
-                        .map(|hash| ("SHA128", hash))
-                })
-                .map(|(hash_type, fingerprint)| {
-                    format!("{}:{}", hash_type, base64::encode(fingerprint))
-                })
-                .ok_or(ErrorKind::HostFingerprintNotFound)?;
-
-            println!(
-                "No matching host key for {}:{} was not found in {:?}.",
-                destination, port, known_hosts_path
-            );
-            println!("Fingerprint: {}", fingerprint);
-            print!("Would you like to add it (yes/no)? ");
-            stdout().flush()?;
-
-            let mut input = String::new();
-            stdin().read_line(&mut input)?;
-            match input.trim() {
-                "Y" | "y" | "YES" | "Yes" | "yes" => {
-                    known_hosts.add(destination, key, "", key_type.into())?;
-                    known_hosts.write_file(&known_hosts_path, ssh2::KnownHostFileKind::OpenSSH)?;
-                    Ok(session)
+            Ok((session, false))
+        }
+        Check::NotFound => {
+            let fingerprint = fingerprint(&session)?;
+            if options.verbose && known_hosts.has_cert_authority(destination, port) {
+                eprintln!(
+                    "Ignoring the certificate authority for {} because the server sent a plain key.",
+                    name
+                );
+            }
+            match options.strict_host_key_checking {
+                StrictHostKeyChecking::Ask if !options.interactive => {
+                    return Err(ErrorKind::HostAuthenticationError(
+                        destination.to_string(),
+                        port,
+                    ));
+                }
+                StrictHostKeyChecking::Yes => {
+                    if options.interactive {
+                        eprintln!(
+                            "No host key for {} was found in {:?}, and strict host key checking is on.",
+                            name, known_hosts_path
+                        );
+                    }
+                    return Err(ErrorKind::HostAuthenticationError(
+                        destination.to_string(),
+                        port,
+                    ));
+                }
+                StrictHostKeyChecking::AcceptNew | StrictHostKeyChecking::No => {
+                    if options.interactive {
+                        eprintln!(
+                            "Warning: Permanently added {} ({}) to the list of known hosts.",
+                            name, fingerprint
+                        );
+                    }
+                }
+                StrictHostKeyChecking::Ask => {
+                    eprintln!(
+                        "No host key for {} was found in {:?}.",
+                        name, known_hosts_path
+                    );
+                    eprintln!("Fingerprint: {}", fingerprint);
+                    eprint!("Would you like to add it (yes/no)? ");
+                    stderr().flush()?;
+
+                    let mut input = String::new();
+                    stdin().read_line(&mut input)?;
+                    if !matches!(input.trim(), "Y" | "y" | "YES" | "Yes" | "yes") {
+                        return Err(ErrorKind::HostAuthenticationError(
+                            destination.to_string(),
+                            port,
+                        ));
+                    }
                 }
-                _ => Err(ErrorKind::HostAuthenticationError(
-                    destination.to_string(),
-                    port,
-                )),
             }
+            KnownHosts::append(
+                &known_hosts_path,
+                destination,
+                port,
+                key,
+                options.hash_known_hosts,
+            )?;
+            Ok((session, false))
         }
-        ssh2::CheckResult::Mismatch => {
+        // Connecting again without asking never trusts a changed key, so that a remembered
+        // password cannot be sent to a server that pretends to be the host.
+        Check::Mismatch if !options.interactive => Err(ErrorKind::MismatchedFingerprint),
+        Check::Mismatch => {
             eprintln!("####################################################");
             eprintln!("# WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED! #");
             eprintln!("####################################################");
-            Err(ErrorKind::MismatchedFingerprint)
+            if options.strict_host_key_checking == StrictHostKeyChecking::No {
+                eprintln!(
+                    "The host key for {} is now {}, continuing without password \
+                     authentication because strict host key checking is off.",
+                    name,
+                    fingerprint(&session)?
+                );
+                Ok((session, true))
+            } else {
+                Err(ErrorKind::MismatchedFingerprint)
+            }
+        }
+        Check::Revoked => {
+            if options.interactive {
+                eprintln!(
+                    "The host key for {} is marked as revoked in {:?}.",
+                    name, known_hosts_path
+                );
+            }
+            Err(ErrorKind::RevokedHostKey(name))
         }
-        ssh2::CheckResult::Failure => Err(ErrorKind::HostFileCheckError),
     }
 }
 
+/// Return the fingerprint of the host key of `session` like `ssh` shows it.
+fn fingerprint(session: &ssh2::Session) -> Result<String> {
+    session
+        .host_key_hash(ssh2::HashType::Sha256)
+        .map(|hash| ("SHA256", hash))
+        .or_else(|| {
+            session
+                .host_key_hash(ssh2::HashType::Sha1)
+                .map(|hash| ("SHA1", hash))
+        })
+        .map(|(hash_type, hash)| {
+            format!(
+                "{}:{}",
+                hash_type,
+                base64::encode_config(hash, base64::STANDARD_NO_PAD)
+            )
+        })
+        .ok_or(ErrorKind::HostFingerprintNotFound)
+}
+
 /// Authenticate the session using a password or public key.
-fn authenticate_session(session: ssh2::Session, username: &str) -> Result<ssh2::Session> {
+///
+/// If `password` is given, it is used instead of asking the user for one, and the user is
+/// only asked if `interactive`. No password is sent if `key_changed`, like `ssh` does, since
+/// the server may not be the host. Return the password that the user entered, if any.
+fn authenticate_session(
+    session: ssh2::Session,
+    username: &str,
+    password: Option<&str>,
+    interactive: bool,
+    key_changed: bool,
+) -> Result<(ssh2::Session, Option<String>)> {
     let mut has_entered_password = false;
+    let mut entered_password = None;
 
     for _ in 0..3 {
         if session.authenticated() {
@@ -127,8 +464,20 @@ fn authenticate_session(session: ssh2::Session, username: &str) -> Result<ssh2::
             session.userauth_agent(username).ok();
         }
 
-        if !has_entered_password && !session.authenticated() && auth_methods.contains("password") {
-            authenticate_with_password(&session, username)?;
+        if !has_entered_password
+            && !key_changed
+            && !session.authenticated()
+            && auth_methods.contains("password")
+        {
+            match password {
+                Some(password) => session.userauth_password(username, password).or(Err(
+                    ErrorKind::UserAuthenticationError(username.to_string()),
+                ))?,
+                None if interactive => {
+                    entered_password = Some(authenticate_with_password(&session, username)?)
+                }
+                None => {}
+            }
             // We only want to prompt the user for a password for one round.
             has_entered_password = true;
         }
@@ -146,7 +495,7 @@ fn authenticate_session(session: ssh2::Session, username: &str) -> Result<ssh2::
         //             prompts
         //                 .iter()
         //                 .map(|p| {
-        //                     println!("{}", instructions);
+        //                     eprintln!("{}", instructions);
         //                     if p.echo {
         //                         let mut input = String::new();
         //                         if stdin().read_line(&mut input).is_ok() {
@@ -155,7 +504,7 @@ fn authenticate_session(session: ssh2::Session, username: &str) -> Result<ssh2::
         //                             String::new()
         //                         }
         //                     } else {
-        //                         prompt_password_stdout(&p.text).unwrap_or_else(|_| String::new())
+        //                         prompt_password_stderr(&p.text).unwrap_or_else(|_| String::new())
         //                     }
         //                 })
         //                 .collect()
@@ -167,18 +516,20 @@ fn authenticate_session(session: ssh2::Session, username: &str) -> Result<ssh2::
     }
 
     if session.authenticated() {
-        Ok(session)
+        Ok((session, entered_password))
     } else {
         Err(ErrorKind::UserAuthenticationError(username.to_string()))
     }
 }
 
 /// Attempt to authenticate the session by prompting the user for a password three times.
-fn authenticate_with_password(session: &ssh2::Session, username: &str) -> Result<()> {
+///
+/// Return the password that was accepted.
+fn authenticate_with_password(session: &ssh2::Session, username: &str) -> Result<String> {
     for _ in 0..3 {
-        let password = prompt_password_stdout("🔐 Password: ")?;
+        let password = prompt_password_stderr("🔐 Password: ")?;
         if session.userauth_password(username, &password).is_ok() {
-            return Ok(());
+            return Ok(password);
         } else {
             eprintln!("❌ Permission denied, please try again.");
         }
@@ -0,0 +1,340 @@
+use crate::connect::{create_session, create_session_remembering_password, ConnectOptions};
+use crate::file::RemoteFileEntry;
+use crate::filesystem::{FileSystem, Properties};
+use crate::utils::{ErrorKind, Result};
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Called with the result of each attempt to connect again.
+type ReconnectCallback = Box<dyn Fn(std::result::Result<(), &ErrorKind>) + Send + Sync>;
+
+/// An sftp channel to a host that connects again with the same options once the connection
+/// is lost, for example when the laptop slept or the VPN dropped.
+///
+/// The connection is restored by a background thread without asking the user anything, and
+/// calls fail with `ErrorKind::Reconnecting` until then. Another thread sends the keepalives.
+/// Clones share the connection, so every user of a clone sees when it is restored.
+#[derive(Clone)]
+pub struct Connection {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    host: String,
+    /// The options to connect with, including the password that the user entered.
+    options: ConnectOptions,
+    state: Mutex<State>,
+    on_reconnect: Mutex<Option<ReconnectCallback>>,
+}
+
+struct State {
+    session: ssh2::Session,
+    sftp: Arc<ssh2::Sftp>,
+    /// True once a call failed because the connection was lost, until it is restored.
+    is_lost: bool,
+    /// True while a background thread connects again.
+    is_reconnecting: bool,
+    /// When the server last answered a request.
+    last_received: Instant,
+    /// When a keepalive was last sent.
+    last_keepalive: Instant,
+}
+
+impl Drop for Inner {
+    /// Tell the server that the session ends once no clone uses it anymore.
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            state
+                .session
+                .disconnect(Some(ssh2::DisconnectCode::ByApplication), "", None)
+                .ok();
+        }
+    }
+}
+
+impl State {
+    fn new(session: ssh2::Session) -> Result<State> {
+        let sftp = Arc::new(session.sftp()?);
+        Ok(State {
+            session,
+            sftp,
+            is_lost: false,
+            is_reconnecting: false,
+            last_received: Instant::now(),
+            last_keepalive: Instant::now(),
+        })
+    }
+}
+
+impl Connection {
+    /// Connect to `host` and open an sftp channel.
+    ///
+    /// This may ask the user for a password, which is remembered for connecting again.
+    pub fn connect(host: &str, mut options: ConnectOptions) -> Result<Connection> {
+        let state = State::new(create_session_remembering_password(host, &mut options)?)?;
+        let connection = Connection {
+            inner: Arc::new(Inner {
+                host: host.to_string(),
+                options,
+                state: Mutex::new(state),
+                on_reconnect: Mutex::new(None),
+            }),
+        };
+        if let Some(interval) = connection.inner.options.keepalive_interval() {
+            let inner = Arc::downgrade(&connection.inner);
+            thread::spawn(move || send_keepalives(inner, interval));
+        }
+        Ok(connection)
+    }
+
+    /// Return the host that this connects to.
+    pub fn host(&self) -> &str {
+        &self.inner.host
+    }
+
+    /// Return the current ssh session, which is replaced when the connection is restored.
+    pub fn session(&self) -> ssh2::Session {
+        self.inner.state.lock().unwrap().session.clone()
+    }
+
+    /// Call `callback` with the result of each attempt to connect again.
+    pub fn set_on_reconnect<F>(&self, callback: F)
+    where
+        F: Fn(std::result::Result<(), &ErrorKind>) + Send + Sync + 'static,
+    {
+        *self.inner.on_reconnect.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Send a keepalive if the server sent nothing for the keepalive interval, so that
+    /// routers in between keep an idle connection open.
+    ///
+    /// Return an error once the server sent nothing for the idle timeout, after which the
+    /// connection is restored in the background. This blocks until the server answers or the
+    /// IO timeout passes, so it is called by a thread of its own.
+    pub fn keepalive(&self) -> Result<()> {
+        let (interval, idle_timeout) = match (
+            self.inner.options.keepalive_interval(),
+            self.inner.options.idle_timeout(),
+        ) {
+            (Some(interval), Some(idle_timeout)) => (interval, idle_timeout),
+            _ => return Ok(()),
+        };
+        let (session, sftp, last_received) = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.is_lost
+                || state.last_received.elapsed() < interval
+                || state.last_keepalive.elapsed() < interval
+            {
+                return Ok(());
+            }
+            state.last_keepalive = Instant::now();
+            (
+                state.session.clone(),
+                Arc::clone(&state.sftp),
+                state.last_received,
+            )
+        };
+        // The reply to a keepalive is not reported, so a request that the server answers
+        // tells that it is alive.
+        let result = session
+            .keepalive_send()
+            .and_then(|_| sftp.realpath(Path::new(".")))
+            .map_err(ErrorKind::from);
+        match result {
+            Ok(_) => {
+                self.received();
+                Ok(())
+            }
+            Err(error) if last_received.elapsed() >= idle_timeout => {
+                self.lose(&sftp);
+                Err(error)
+            }
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Return the sftp channel, or start to connect again if the connection was lost.
+    fn sftp(&self) -> Result<Arc<ssh2::Sftp>> {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.is_lost {
+            self.reconnect_in_background(&mut state);
+            return Err(ErrorKind::Reconnecting(self.inner.host.clone()));
+        }
+        Ok(Arc::clone(&state.sftp))
+    }
+
+    /// Call `f` with the sftp channel, and start to connect again if it failed because the
+    /// connection was lost.
+    fn call<T>(&self, f: impl Fn(&ssh2::Sftp) -> Result<T>) -> Result<T> {
+        match f(&*self.sftp()?) {
+            Ok(value) => {
+                self.received();
+                Ok(value)
+            }
+            Err(error) => {
+                self.is_connection_lost(&error);
+                Err(error)
+            }
+        }
+    }
+
+    /// Remember that the server answered just now.
+    fn received(&self) {
+        self.inner.state.lock().unwrap().last_received = Instant::now();
+    }
+
+    /// Treat the connection of `sftp` as lost and connect again, unless it was replaced
+    /// already.
+    fn lose(&self, sftp: &Arc<ssh2::Sftp>) {
+        let mut state = self.inner.state.lock().unwrap();
+        if Arc::ptr_eq(&state.sftp, sftp) {
+            state.is_lost = true;
+            self.reconnect_in_background(&mut state);
+        }
+    }
+
+    /// Connect again in a thread of its own, unless one does already.
+    fn reconnect_in_background(&self, state: &mut State) {
+        if !state.is_reconnecting {
+            state.is_reconnecting = true;
+            let connection = self.clone();
+            thread::spawn(move || connection.reconnect());
+        }
+    }
+
+    /// Connect again with the remembered password, without asking the user anything, and
+    /// report the result to the callback.
+    fn reconnect(&self) {
+        let options = ConnectOptions {
+            verbose: false,
+            interactive: false,
+            ..self.inner.options.clone()
+        };
+        let result = create_session(&self.inner.host, &options).and_then(State::new);
+        let result = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.is_reconnecting = false;
+            result.map(|new_state| *state = new_state)
+        };
+        if let Some(callback) = &*self.inner.on_reconnect.lock().unwrap() {
+            callback(result.as_ref().copied());
+        }
+    }
+}
+
+impl FileSystem for Connection {
+    type Entry = RemoteFileEntry;
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<RemoteFileEntry>> {
+        self.call(|sftp| sftp.read_dir(path))
+    }
+
+    fn stat(&self, path: &Path) -> Result<Option<RemoteFileEntry>> {
+        self.call(|sftp| FileSystem::stat(sftp, path))
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        self.call(|sftp| sftp.canonicalize(path))
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        self.call(|sftp| Ok(Box::new(sftp.open(path)?) as Box<dyn Read>))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.call(|sftp| Ok(Box::new(sftp.create(path)?) as Box<dyn Write>))
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<()> {
+        self.call(|sftp| FileSystem::mkdir(sftp, path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.call(|sftp| FileSystem::rename(sftp, from, to))
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        self.call(|sftp| FileSystem::remove(sftp, path))
+    }
+
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()> {
+        self.call(|sftp| sftp.set_modified(path, modified))
+    }
+
+    fn open_read_at(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + '_>> {
+        self.call(|sftp| {
+            let mut file = sftp.open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            Ok(Box::new(file) as Box<dyn Read>)
+        })
+    }
+
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.call(|sftp| {
+            let mut file =
+                sftp.open_mode(path, ssh2::OpenFlags::WRITE, 0o644, ssh2::OpenType::File)?;
+            file.seek(SeekFrom::End(0))?;
+            Ok(Box::new(file) as Box<dyn Write>)
+        })
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.call(|sftp| sftp.read_range(path, offset, len))
+    }
+
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        self.call(|sftp| sftp.properties(path))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.call(|sftp| sftp.set_mode(path, mode))
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.call(|sftp| sftp.set_owner(path, uid, gid))
+    }
+
+    /// The connection is lost if `error` says so, or if it could mean that and the server
+    /// does not answer a request that always succeeds. Either way, it is restored in the
+    /// background.
+    fn is_connection_lost(&self, error: &ErrorKind) -> bool {
+        let sftp = {
+            let state = self.inner.state.lock().unwrap();
+            if state.is_lost {
+                return true;
+            }
+            Arc::clone(&state.sftp)
+        };
+        let is_lost = error.is_disconnect()
+            || (error.may_be_disconnect()
+                && sftp
+                    .realpath(Path::new("."))
+                    .map_err(ErrorKind::from)
+                    .err()
+                    .is_some_and(|error| error.is_disconnect()));
+        if is_lost {
+            self.lose(&sftp);
+        }
+        is_lost
+    }
+}
+
+/// Send the keepalives that are due to the connection of `inner` until it is dropped,
+/// checking every `interval` but at least once a second.
+fn send_keepalives(inner: Weak<Inner>, interval: Duration) {
+    loop {
+        thread::sleep(interval.min(Duration::from_secs(1)));
+        match inner.upgrade() {
+            Some(inner) => Connection { inner }.keepalive().ok(),
+            None => break,
+        };
+    }
+}
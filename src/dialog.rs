@@ -0,0 +1,164 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+const DIALOG_COLOR: Color = Color::Yellow;
+
+/// What the user did with a dialog after a key press.
+#[derive(Debug, PartialEq)]
+pub enum DialogResult {
+    /// The user has not decided yet.
+    Pending,
+    /// The user pressed `y` or enter.
+    Confirm,
+    /// The user pressed `n`, `q` or escape.
+    Cancel,
+}
+
+/// A popup that lists some lines and asks the user to confirm them.
+pub struct Dialog {
+    title: String,
+    lines: Vec<String>,
+    summary: String,
+    scroll: usize,
+}
+
+impl Dialog {
+    /// Create a dialog with a `title` that lists `lines` followed by a `summary`.
+    pub fn new(title: &str, lines: Vec<String>, summary: &str) -> Self {
+        Dialog {
+            title: title.to_string(),
+            lines,
+            summary: summary.to_string(),
+            scroll: 0,
+        }
+    }
+
+    /// Scroll the lines or answer the dialog with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> DialogResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('y'), KeyModifiers::NONE) | (KeyCode::Enter, _) => {
+                return DialogResult::Confirm
+            }
+            (KeyCode::Char('n'), KeyModifiers::NONE)
+            | (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Esc, _) => return DialogResult::Cancel,
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => {
+                self.scroll = (self.scroll + 1).min(self.lines.len().saturating_sub(1));
+            }
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => {
+                self.scroll = self.scroll.saturating_sub(1);
+            }
+            _ => {}
+        }
+        DialogResult::Pending
+    }
+
+    /// Draw this dialog on top of the center of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let width = (rect.width * 4 / 5).max(rect.width.min(40));
+        let height = (self.lines.len() as u16 + 4).min(rect.height);
+        let rect = Rect {
+            x: rect.x + (rect.width - width) / 2,
+            y: rect.y + (rect.height - height) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, rect);
+
+        let block = Block::default()
+            .title(self.title.as_str())
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(DIALOG_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(2)].as_ref())
+            .split(inner);
+
+        let lines: Vec<Spans> = self
+            .lines
+            .iter()
+            .skip(self.scroll)
+            .map(|line| Spans::from(line.as_str()))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let footer = vec![
+            Spans::from(self.summary.as_str()),
+            Spans::from(Span::styled(
+                "y: confirm  n: cancel  j/k: scroll",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ];
+        frame.render_widget(Paragraph::new(footer), chunks[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::utils::assert_buffer_symbols_eq;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_dialog_events() {
+        let lines = vec!["a".to_string(), "b".to_string()];
+        let mut dialog = Dialog::new("Download?", lines, "2 changes");
+        assert_eq!(dialog.on_event(key(KeyCode::Down)), DialogResult::Pending);
+        assert_eq!(dialog.on_event(key(KeyCode::Down)), DialogResult::Pending);
+        assert_eq!(dialog.scroll, 1);
+        assert_eq!(
+            dialog.on_event(key(KeyCode::Char('k'))),
+            DialogResult::Pending
+        );
+        assert_eq!(dialog.scroll, 0);
+        assert_eq!(
+            dialog.on_event(key(KeyCode::Char('y'))),
+            DialogResult::Confirm
+        );
+        assert_eq!(dialog.on_event(key(KeyCode::Esc)), DialogResult::Cancel);
+    }
+
+    #[test]
+    fn test_draw_dialog() {
+        let mut terminal = Terminal::new(TestBackend::new(40, 8)).unwrap();
+        let lines = vec![
+            "mkdir     /d/".to_string(),
+            "create    /d/a (3 B)".to_string(),
+        ];
+        let dialog = Dialog::new("Download?", lines, "2 changes, 3 B");
+
+        terminal
+            .draw(|frame| {
+                let rect = frame.size();
+                dialog.draw(frame, rect);
+            })
+            .unwrap();
+
+        let expected = Buffer::with_lines(vec![
+            "                                        ",
+            "┌Download?─────────────────────────────┐",
+            "│mkdir     /d/                         │",
+            "│create    /d/a (3 B)                  │",
+            "│2 changes, 3 B                        │",
+            "│y: confirm  n: cancel  j/k: scroll    │",
+            "└──────────────────────────────────────┘",
+            "                                        ",
+        ]);
+        assert_buffer_symbols_eq(terminal.backend().buffer(), &expected);
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::ops::Range;
+
+/// The most lines that may differ between two texts before they are diffed as a whole,
+/// because finding the shortest diff takes time and memory that grows with its square.
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// A line of a unified diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    /// The `@@ -1,2 +1,3 @@` header of a hunk.
+    Hunk(String),
+    /// A line that both texts have.
+    Context(String),
+    /// A line that only the old text has.
+    Removed(String),
+    /// A line that only the new text has.
+    Added(String),
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::Hunk(header) => write!(f, "{}", header),
+            DiffLine::Context(line) => write!(f, " {}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+        }
+    }
+}
+
+/// A step that turns the old lines into the new lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    /// Keep old line `.0`, which is new line `.1`.
+    Equal(usize, usize),
+    /// Remove old line `.0`.
+    Delete(usize),
+    /// Insert new line `.0`.
+    Insert(usize),
+}
+
+/// Return the shortest edits that turn `old` into `new` with the algorithm of Eugene Myers,
+/// or `None` if more than `max_distance` lines differ.
+fn shortest_edits(old: &[&str], new: &[&str], max_distance: usize) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // The furthest reaching paths before each distance, kept for finding the path back.
+    // Only the diagonals `-d - 1..=d + 1` are kept for distance `d`.
+    let mut trace: Vec<Vec<isize>> = vec![];
+    'search: for d in 0..=max.min(max_distance) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d as usize == max_distance {
+            return None;
+        }
+    }
+
+    let (mut x, mut y) = (n, m);
+    let mut edits = vec![];
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = get(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Equal(x as usize - 1, y as usize - 1));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == previous_x {
+                edits.push(Edit::Insert(y as usize - 1));
+            } else {
+                edits.push(Edit::Delete(x as usize - 1));
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    edits.reverse();
+    Some(edits)
+}
+
+/// Return edits that turn `old` into `new`.
+///
+/// The common start and end are always kept. If the rest differs too much to find the
+/// shortest edits, it is replaced as a whole.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    match shortest_edits(old_middle, new_middle, MAX_EDIT_DISTANCE) {
+        Some(middle) => edits.extend(middle.into_iter().map(|edit| match edit {
+            Edit::Equal(i, j) => Edit::Equal(prefix + i, prefix + j),
+            Edit::Delete(i) => Edit::Delete(prefix + i),
+            Edit::Insert(j) => Edit::Insert(prefix + j),
+        })),
+        None => {
+            edits.extend((0..old_middle.len()).map(|i| Edit::Delete(prefix + i)));
+            edits.extend((0..new_middle.len()).map(|j| Edit::Insert(prefix + j)));
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    edits.extend((0..suffix).map(|i| Edit::Equal(old_end + i, new_end + i)));
+    edits
+}
+
+/// Return the range of a hunk header that starts after `start` lines and is `len` lines
+/// long, like GNU diff.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Return the hunks of a unified diff that turns the text `old` into the text `new`, with
+/// `context` unchanged lines around each change.
+///
+/// The diff is empty if both texts have the same lines.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let edits = edits(&old, &new);
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Changes that are close enough to share their context are in the same hunk.
+    let mut hunks: Vec<Range<usize>> = vec![];
+    for &change in &changes {
+        let start = change.saturating_sub(context);
+        let end = (change + context + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.end => hunk.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    // The number of old and new lines before each edit.
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_position, mut new_position) = (0, 0);
+    for edit in &edits {
+        positions.push((old_position, new_position));
+        match edit {
+            Edit::Equal(..) => {
+                old_position += 1;
+                new_position += 1;
+            }
+            Edit::Delete(_) => old_position += 1,
+            Edit::Insert(_) => new_position += 1,
+        }
+    }
+
+    let mut lines = vec![];
+    for hunk in hunks {
+        let hunk_edits = &edits[hunk.clone()];
+        let old_len = hunk_edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Insert(_)))
+            .count();
+        let new_len = hunk_edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Delete(_)))
+            .count();
+        let (old_start, new_start) = positions[hunk.start];
+        lines.push(DiffLine::Hunk(format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        )));
+        lines.extend(hunk_edits.iter().map(|edit| match *edit {
+            Edit::Equal(i, _) => DiffLine::Context(old[i].to_string()),
+            Edit::Delete(i) => DiffLine::Removed(old[i].to_string()),
+            Edit::Insert(j) => DiffLine::Added(new[j].to_string()),
+        }));
+    }
+    lines
+}
+
+/// Return the ranges of offsets where the bytes of `old` and `new` differ.
+///
+/// If one is longer, the bytes past the end of the other differ as well.
+pub fn differing_ranges(old: &[u8], new: &[u8]) -> Vec<Range<u64>> {
+    let mut ranges: Vec<Range<u64>> = vec![];
+    let len = old.len().max(new.len());
+    for offset in 0..len {
+        if old.get(offset) == new.get(offset) {
+            continue;
+        }
+        let offset = offset as u64;
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end += 1,
+            _ => ranges.push(offset..offset + 1),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str, context: usize) -> Vec<String> {
+        unified_diff(old, new, context)
+            .iter()
+            .map(DiffLine::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        assert!(diff("a\nb\n", "a\nb\n", 3).is_empty());
+        assert_eq!(
+            diff("a\nb\nc\nd\ne\nf\ng\nh\n", "a\nB\nc\nd\ne\nf\ng\nh\ni\n", 1),
+            vec![
+                "@@ -1,3 +1,3 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                "@@ -8 +8,2 @@",
+                " h",
+                "+i",
+            ]
+        );
+        // Changes that share their context are in one hunk.
+        assert_eq!(
+            diff("a\nb\nc\nd\n", "b\nc\nD\n", 1),
+            vec!["@@ -1,4 +1,3 @@", "-a", " b", " c", "-d", "+D"]
+        );
+        assert_eq!(diff("", "new\n", 3), vec!["@@ -0,0 +1 @@", "+new"]);
+        assert_eq!(diff("old\n", "", 3), vec!["@@ -1 +0,0 @@", "-old"]);
+    }
+
+    #[test]
+    fn test_shortest_edits() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let distance = shortest_edits(&old, &new, 100)
+            .unwrap()
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Equal(..)))
+            .count();
+        assert_eq!(distance, 5);
+        assert!(shortest_edits(&old, &new, 4).is_none());
+
+        // Texts that differ too much are replaced as a whole, which is still a valid diff.
+        let old: Vec<String> = (0..3000).map(|i| i.to_string()).collect();
+        let new: Vec<String> = (0..3000).map(|i| (i * 7).to_string()).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        let replaced = edits(&old, &new);
+        assert_eq!(replaced[0], Edit::Equal(0, 0));
+        assert_eq!(replaced.len(), 1 + 2 * 2999);
+    }
+
+    #[test]
+    fn test_differing_ranges() {
+        assert!(differing_ranges(b"abc", b"abc").is_empty());
+        assert_eq!(differing_ranges(b"abcdef", b"aXYdeZ"), vec![1..3, 5..6]);
+        assert_eq!(differing_ranges(b"ab", b"abcd"), vec![2..4]);
+        assert_eq!(differing_ranges(b"abXd", b"ab"), vec![2..4]);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::preview::is_binary;
+use rftp::diff::{differing_ranges, unified_diff, DiffLine};
+use rftp::filesystem::FileSystem;
+use rftp::utils::{bytes_to_string, Result};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const DIFF_PANE_COLOR: Color = Color::Yellow;
+/// The max number of bytes of each file that are compared.
+const DIFF_SIZE: u64 = 1024 * 1024;
+/// The number of unchanged lines that are shown around each change.
+const DIFF_CONTEXT: usize = 3;
+
+/// What the user did with the diff pane after a key press.
+#[derive(Debug, PartialEq)]
+pub enum DiffPaneResult {
+    /// The pane stays open.
+    Pending,
+    /// The user pressed `d`, `q` or escape.
+    Close,
+}
+
+/// The lines of a diff with their styles.
+type StyledLines = Vec<(String, Style)>;
+
+/// A pane that shows how a local file differs from a remote file.
+pub struct DiffPane {
+    title: String,
+    /// The lines of the diff once both files were loaded, or why they could not be.
+    lines: Arc<Mutex<Option<std::result::Result<StyledLines, String>>>>,
+    /// The number of lines above the top of the pane.
+    scroll: usize,
+    /// The most lines that could be scrolled when the pane was last drawn.
+    max_scroll: Cell<usize>,
+}
+
+impl DiffPane {
+    /// Start loading the file `left` of `left_fs` and the file `right` of `right_fs` in the
+    /// background and show how they differ.
+    pub fn open<L, R>(left_fs: Arc<L>, left: &Path, right_fs: Arc<R>, right: &Path) -> Self
+    where
+        L: FileSystem + Send + Sync + ?Sized + 'static,
+        R: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let lines = Arc::new(Mutex::new(None));
+        {
+            let lines = Arc::clone(&lines);
+            let (left, right) = (left.to_path_buf(), right.to_path_buf());
+            thread::spawn(move || {
+                let result = Self::load(&*left_fs, &left, &*right_fs, &right)
+                    .map_err(|error| error.to_string());
+                *lines.lock().unwrap() = Some(result);
+            });
+        }
+        DiffPane {
+            title: format!("Diff {:?} {:?}", left, right),
+            lines,
+            scroll: 0,
+            max_scroll: Cell::new(0),
+        }
+    }
+
+    /// Load both files and return the lines of their diff.
+    fn load<L, R>(left_fs: &L, left: &Path, right_fs: &R, right: &Path) -> Result<StyledLines>
+    where
+        L: FileSystem + ?Sized,
+        R: FileSystem + ?Sized,
+    {
+        let mut old = left_fs.read_range(left, 0, DIFF_SIZE + 1)?;
+        let mut new = right_fs.read_range(right, 0, DIFF_SIZE + 1)?;
+        let mut lines = vec![];
+        let note = Style::default().add_modifier(Modifier::ITALIC);
+        if old.len() as u64 > DIFF_SIZE || new.len() as u64 > DIFF_SIZE {
+            old.truncate(DIFF_SIZE as usize);
+            new.truncate(DIFF_SIZE as usize);
+            lines.push((
+                format!(
+                    "Only the first {} are compared.",
+                    bytes_to_string(DIFF_SIZE)
+                ),
+                note,
+            ));
+        }
+        lines.extend(diff_lines(&old, &new));
+        Ok(lines)
+    }
+
+    /// Scroll the diff or close the pane with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> DiffPaneResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Char('d'), KeyModifiers::NONE)
+            | (KeyCode::Esc, _) => return DiffPaneResult::Close,
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => self.scroll(1),
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => self.scroll(-1),
+            (KeyCode::Char('g'), KeyModifiers::NONE) | (KeyCode::Home, _) => self.scroll = 0,
+            (KeyCode::Char('G'), _) | (KeyCode::End, _) => self.scroll = self.max_scroll.get(),
+            _ => {}
+        }
+        DiffPaneResult::Pending
+    }
+
+    /// Scroll `lines` lines down, or up if negative.
+    pub fn scroll(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.max_scroll.get());
+    }
+
+    fn get_lines(&self) -> StyledLines {
+        match &*self.lines.lock().unwrap() {
+            None => vec![("Loading...".to_string(), Style::default())],
+            Some(Err(error)) => vec![(
+                format!("Error: {}.", error),
+                Style::default().fg(Color::Red),
+            )],
+            Some(Ok(lines)) => lines.clone(),
+        }
+    }
+
+    /// Draw this pane on top of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let block = Block::default()
+            .title(self.title.as_str())
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(DIFF_PANE_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(inner);
+
+        let lines = self.get_lines();
+        self.max_scroll
+            .set(lines.len().saturating_sub(chunks[0].height as usize));
+        let lines: Vec<Spans> = lines
+            .into_iter()
+            .skip(self.scroll.min(self.max_scroll.get()))
+            .map(|(line, style)| Spans::from(Span::styled(line, style)))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let footer = Span::styled(
+            "j/k: scroll  q: close",
+            Style::default().add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(Paragraph::new(Spans::from(footer)), chunks[1]);
+    }
+}
+
+/// Return the styled lines that show how the bytes `old` differ from `new`.
+///
+/// Text is shown as a unified diff, binary files only by the ranges of bytes that differ.
+fn diff_lines(old: &[u8], new: &[u8]) -> StyledLines {
+    let note = Style::default().add_modifier(Modifier::ITALIC);
+    if old == new {
+        return vec![("The files are the same.".to_string(), note)];
+    }
+    if is_binary(old) || is_binary(new) {
+        let mut lines = vec![("Binary files differ at:".to_string(), note)];
+        lines.extend(differing_ranges(old, new).into_iter().map(|range| {
+            (
+                format!(
+                    "0x{:08x}-0x{:08x} ({} bytes)",
+                    range.start,
+                    range.end,
+                    range.end - range.start
+                ),
+                Style::default(),
+            )
+        }));
+        return lines;
+    }
+
+    let (old, new) = (String::from_utf8_lossy(old), String::from_utf8_lossy(new));
+    let diff = unified_diff(&old, &new, DIFF_CONTEXT);
+    if diff.is_empty() {
+        return vec![(
+            "The lines are the same, only the line endings or the final newline differ."
+                .to_string(),
+            note,
+        )];
+    }
+    let mut lines = vec![
+        ("--- local".to_string(), Style::default().fg(Color::Red)),
+        ("+++ remote".to_string(), Style::default().fg(Color::Green)),
+    ];
+    lines.extend(diff.into_iter().map(|line| {
+        let style = match line {
+            DiffLine::Hunk(_) => Style::default().fg(Color::Cyan),
+            DiffLine::Context(_) => Style::default(),
+            DiffLine::Removed(_) => Style::default().fg(Color::Red),
+            DiffLine::Added(_) => Style::default().fg(Color::Green),
+        };
+        (line.to_string().replace('\t', "    "), style)
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: StyledLines) -> Vec<String> {
+        lines.into_iter().map(|(line, _)| line).collect()
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(
+            text(diff_lines(b"a\n", b"a\n")),
+            vec!["The files are the same."]
+        );
+        assert_eq!(
+            text(diff_lines(b"a\nb\n", b"a\nc\n")),
+            vec![
+                "--- local",
+                "+++ remote",
+                "@@ -1,2 +1,2 @@",
+                " a",
+                "-b",
+                "+c"
+            ]
+        );
+        assert_eq!(
+            text(diff_lines(b"a\r\n", b"a\n")),
+            vec!["The lines are the same, only the line endings or the final newline differ."]
+        );
+        assert_eq!(
+            text(diff_lines(b"\x00\x01\x02\x03", b"\x00\x01\xff")),
+            vec!["Binary files differ at:", "0x00000002-0x00000004 (2 bytes)",]
+        );
+    }
+}
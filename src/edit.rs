@@ -0,0 +1,224 @@
+use rftp::file::FileEntry;
+use rftp::filesystem::FileSystem;
+use rftp::utils::{ErrorKind, Result};
+
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// What happened to the remote file after it was edited.
+#[derive(Debug, PartialEq)]
+pub enum EditResult {
+    /// The edited copy is the same as the remote file.
+    Unchanged,
+    /// The edited copy replaced the remote file.
+    Uploaded,
+    /// The remote file changed after it was downloaded, so it was not replaced.
+    Conflict,
+}
+
+/// A remote file that is downloaded to a temporary file to be edited.
+pub struct EditSession {
+    remote_path: PathBuf,
+    local_path: PathBuf,
+    /// The modification time and size of the remote file when it was downloaded.
+    remote_version: (Option<u64>, Option<u64>),
+    original: Vec<u8>,
+}
+
+impl EditSession {
+    /// Download the file at `remote_path` of `fs` to a new temporary directory that only the
+    /// user can read.
+    pub fn start<F>(fs: &F, remote_path: &Path) -> Result<Self>
+    where
+        F: FileSystem + ?Sized,
+    {
+        // Stat first, so that a change while downloading is seen as a conflict later.
+        let entry = fs
+            .stat(remote_path)?
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        let original = fs.read_range(remote_path, 0, u64::MAX)?;
+        let dir = create_private_dir()?;
+        // Keep the file name so that editors can tell the type of the file.
+        let local_path = dir.join(remote_path.file_name().unwrap_or_else(|| "file".as_ref()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(&local_path)?.write_all(&original)?;
+        Ok(EditSession {
+            remote_path: remote_path.to_path_buf(),
+            local_path,
+            remote_version: (entry.modified(), entry.len()),
+            original,
+        })
+    }
+
+    /// Return the path of the edited file on the server.
+    pub fn remote_path(&self) -> &Path {
+        &self.remote_path
+    }
+
+    /// Return the path of the temporary copy that is edited.
+    pub fn local_path(&self) -> &Path {
+        &self.local_path
+    }
+
+    /// Open the temporary copy in the editor of the user and wait for it to exit.
+    pub fn run_editor(&self) -> Result<()> {
+        run_editor(&editor(), &self.local_path)
+    }
+
+    /// Upload the temporary copy to `fs` if it was changed.
+    ///
+    /// Unless `force` is true, the remote file is only replaced if it did not change since it
+    /// was downloaded.
+    pub fn finish<F>(&self, fs: &F, force: bool) -> Result<EditResult>
+    where
+        F: FileSystem + ?Sized,
+    {
+        let contents = std::fs::read(&self.local_path)?;
+        if contents == self.original {
+            return Ok(EditResult::Unchanged);
+        }
+        if !force {
+            let remote_version = fs
+                .stat(&self.remote_path)?
+                .map(|entry| (entry.modified(), entry.len()));
+            if remote_version != Some(self.remote_version) {
+                return Ok(EditResult::Conflict);
+            }
+        }
+        let mut writer = fs.open_write(&self.remote_path)?;
+        writer.write_all(&contents)?;
+        writer.flush()?;
+        Ok(EditResult::Uploaded)
+    }
+
+    /// Remove the temporary copy.
+    pub fn remove_local(&self) {
+        if let Some(dir) = self.local_path.parent() {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+}
+
+/// Create a new directory in the temporary directory that only the user can access.
+///
+/// The directory must not exist yet, so that nobody else can have planted it or a symlink in it.
+fn create_private_dir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "rftp-edit-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// Return the editor of the user from `$VISUAL` or `$EDITOR`.
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Run `editor` on `path` and wait for it to exit.
+///
+/// Like git, the editor is run by the shell so that it may contain arguments.
+fn run_editor(editor: &str, path: &Path) -> Result<()> {
+    let status = if cfg!(unix) {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path)
+            .status()?
+    } else {
+        Command::new(editor).arg(path).status()?
+    };
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ErrorKind::EditorFailed(format!("exited with {}", status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::file::RemoteFileEntry;
+    use rftp::memory::MemoryFileSystem;
+
+    fn start(fs: &MemoryFileSystem<RemoteFileEntry>) -> EditSession {
+        EditSession::start(fs, Path::new("/etc/app.conf")).unwrap()
+    }
+
+    #[test]
+    fn test_edit() {
+        let fs = MemoryFileSystem::<RemoteFileEntry>::new(true, &[("/etc/app.conf", "port = 1\n")]);
+
+        let session = start(&fs);
+        assert_eq!(session.local_path().file_name().unwrap(), "app.conf");
+        assert_eq!(
+            std::fs::read_to_string(session.local_path()).unwrap(),
+            "port = 1\n"
+        );
+        assert_eq!(session.finish(&fs, false).unwrap(), EditResult::Unchanged);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(session.local_path()), 0o600);
+            assert_eq!(mode(session.local_path().parent().unwrap()), 0o700);
+        }
+
+        std::fs::write(session.local_path(), "port = 2\n").unwrap();
+        assert_eq!(session.finish(&fs, false).unwrap(), EditResult::Uploaded);
+        assert_eq!(fs.contents("/etc/app.conf").unwrap(), "port = 2\n");
+        session.remove_local();
+        assert!(!session.local_path().exists());
+    }
+
+    #[test]
+    fn test_edit_conflict() {
+        let fs = MemoryFileSystem::<RemoteFileEntry>::new(true, &[("/etc/app.conf", "port = 1\n")]);
+
+        let session = start(&fs);
+        std::fs::write(session.local_path(), "port = 2\n").unwrap();
+        fs.write("/etc/app.conf", "port = 10\n");
+        assert_eq!(session.finish(&fs, false).unwrap(), EditResult::Conflict);
+        assert_eq!(fs.contents("/etc/app.conf").unwrap(), "port = 10\n");
+
+        assert_eq!(session.finish(&fs, true).unwrap(), EditResult::Uploaded);
+        assert_eq!(fs.contents("/etc/app.conf").unwrap(), "port = 2\n");
+        session.remove_local();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_editor() {
+        let fs = MemoryFileSystem::<RemoteFileEntry>::new(true, &[("/etc/app.conf", "port = 1\n")]);
+        let session = start(&fs);
+
+        run_editor("printf 'port = 3\\n' >", session.local_path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(session.local_path()).unwrap(),
+            "port = 3\n"
+        );
+        match run_editor("false", session.local_path()) {
+            Err(ErrorKind::EditorFailed(_)) => {}
+            result => panic!("expected the editor to fail, got {:?}", result),
+        }
+        session.remove_local();
+    }
+}
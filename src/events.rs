@@ -1,30 +1,51 @@
 use crossbeam_channel::{tick, unbounded, Receiver, RecvError};
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 pub enum Event {
     Input(crossterm::event::KeyEvent),
+    Mouse(crossterm::event::MouseEvent),
+    Resize(u16, u16),
     Tick,
 }
 
 pub struct EventListener {
-    key_receiver: Receiver<crossterm::event::KeyEvent>,
+    event_receiver: Receiver<Event>,
     tick_receiver: Receiver<Instant>,
+    is_paused: Arc<AtomicBool>,
 }
 
+/// How long to wait for an event before checking if reading events was paused.
+const POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
 impl EventListener {
     pub fn new(ticks_per_second: f64) -> Self {
-        let key_receiver = {
+        let is_paused = Arc::new(AtomicBool::new(false));
+        let event_receiver = {
             let (tx, rx) = unbounded();
+            let is_paused = Arc::clone(&is_paused);
             thread::spawn(move || loop {
-                match crossterm::event::read() {
-                    Ok(crossterm::event::Event::Key(event)) => {
-                        tx.send(event).unwrap();
-                    }
-                    Ok(_) => {}
-                    Err(_) => {}
+                if is_paused.load(Ordering::Relaxed) {
+                    thread::sleep(POLL_TIMEOUT);
+                    continue;
                 }
+                // Only read events that are ready so that a pause takes effect quickly.
+                match crossterm::event::poll(POLL_TIMEOUT) {
+                    Ok(true) if !is_paused.load(Ordering::Relaxed) => {}
+                    _ => continue,
+                }
+                let event = match crossterm::event::read() {
+                    Ok(crossterm::event::Event::Key(event)) => Event::Input(event),
+                    Ok(crossterm::event::Event::Mouse(event)) => Event::Mouse(event),
+                    Ok(crossterm::event::Event::Resize(width, height)) => {
+                        Event::Resize(width, height)
+                    }
+                    Ok(_) | Err(_) => continue,
+                };
+                tx.send(event).unwrap();
             });
             rx
         };
@@ -32,15 +53,29 @@ impl EventListener {
         let tick_receiver = tick(Duration::from_secs_f64(1.0 / ticks_per_second));
 
         EventListener {
-            key_receiver,
+            event_receiver,
             tick_receiver,
+            is_paused,
         }
     }
 
-    /// Return the next "tick" or key press that occures.
+    /// Stop reading events from the terminal, so that another program can read them.
+    pub fn pause(&mut self) {
+        self.is_paused.store(true, Ordering::Relaxed);
+        // Wait for a poll that may have started before the pause to finish.
+        thread::sleep(POLL_TIMEOUT * 2);
+    }
+
+    /// Read events from the terminal again, ignoring the ones that were queued meanwhile.
+    pub fn resume(&mut self) {
+        while self.event_receiver.try_recv().is_ok() {}
+        self.is_paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Return the next "tick", key press, mouse event or resize that occures.
     pub fn get_next_event(&mut self) -> Result<Event, RecvError> {
         select! {
-            recv(self.key_receiver) -> key => Ok(Event::Input(key?)),
+            recv(self.event_receiver) -> event => event,
             recv(self.tick_receiver) -> instant => {
                 let _ = instant?;
                 Ok(Event::Tick)
@@ -1,14 +1,14 @@
-use crate::progress::ProgressFile;
-use crate::utils::{bytes_to_string, get_remote_home_dir, Result};
+use crate::compare::{compare_lists, CompareBy, Comparison};
+use crate::filesystem::FileSystem;
+use crate::utils::{bytes_to_string, Result};
 
 use std::borrow::Cow;
-use std::env;
-use std::fs::{canonicalize, metadata, read_dir, File};
+use std::fs::{metadata, read_dir};
 use std::io;
-use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 use tui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::Text,
     widgets::{Block, Borders, List, ListItem, ListState},
@@ -18,10 +18,15 @@ const FILELIST_FILE_COLOR: Color = Color::Green;
 const FILELIST_DIRECTORY_COLOR: Color = Color::Blue;
 const FILELIST_SYMLINK_COLOR: Color = Color::Red;
 const FILELIST_HIGHLIGHT_COLOR: Color = Color::LightMagenta;
+const FILELIST_UNIQUE_COLOR: Color = Color::Yellow;
+const FILELIST_DIFFERENT_COLOR: Color = Color::LightRed;
+const FILELIST_SAME_COLOR: Color = Color::DarkGray;
 
+/// A file entry is either a file with its size and modification time in seconds since
+/// the unix epoch, a directory, a symlink, or the parent of the current directory.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LocalFileEntry {
-    File(PathBuf, u64),
+    File(PathBuf, u64, Option<u64>),
     Directory(PathBuf),
     Symlink(PathBuf),
     Parent(PathBuf),
@@ -29,7 +34,7 @@ pub enum LocalFileEntry {
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum RemoteFileEntry {
-    File(PathBuf, u64),
+    File(PathBuf, u64, Option<u64>),
     Directory(PathBuf),
     Symlink(PathBuf),
     Parent(PathBuf),
@@ -55,65 +60,16 @@ pub struct FileList {
     local_entries: Vec<LocalFileEntry>,
     remote_entries: Vec<RemoteFileEntry>,
     selected: SelectedFileEntryIndex,
+    /// How entries are compared with the other side, or `None` if they are not compared.
+    compare_by: Option<CompareBy>,
+    /// What the left pane shows, like "Local" or the name of a host.
+    local_title: String,
+    /// What the right pane shows, like "Remote" or the name of a host.
+    remote_title: String,
 }
 
-/// `CHUNK_SIZE` bytes of data is read from the source and then it is all written to the dest.
-const CHUNK_SIZE: usize = 8 * 1024;
-
-/// Reads the remote file `source`, creates/truncates the local file `dest`,
-/// and writes the data to `dest`.
-pub fn download(
-    source: RemoteFileEntry,
-    dest: impl AsRef<Path>,
-    sftp: &ssh2::Sftp,
-    progress: &ProgressFile,
-) -> io::Result<()> {
-    assert!(source.is_file(), "Source must be a file!");
-    let mut source = sftp.open(source.path())?;
-    let mut dest = File::create(dest)?;
-    let mut buffer = [0; CHUNK_SIZE];
-
-    loop {
-        let bytes_read = source.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        } else {
-            dest.write_all(&buffer[..bytes_read])?;
-            progress.inc(bytes_read as u64);
-        }
-    }
-
-    progress.finish();
-    Ok(())
-}
-
-/// Reads the local file `source`, creates/truncates the remote file `dest`,
-/// and writes the data to `dest`.
-pub fn upload(
-    source: LocalFileEntry,
-    dest: impl AsRef<Path>,
-    sftp: &ssh2::Sftp,
-    progress: &ProgressFile,
-) -> io::Result<()> {
-    assert!(source.is_file(), "Source must be a file!");
-    let mut source = File::open(source.path())?;
-    let mut dest = sftp.create(dest.as_ref())?;
-    let mut buffer = [0; CHUNK_SIZE];
-
-    loop {
-        let bytes_read = source.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        } else {
-            dest.write_all(&buffer[..bytes_read])?;
-            progress.inc(bytes_read as u64);
-        }
-    }
-
-    progress.finish();
-    Ok(())
-}
-
+// An entry is not a collection, so `is_empty` would be meaningless.
+#[allow(clippy::len_without_is_empty)]
 pub trait FileEntry {
     /// Return the full path of this entry.
     fn path(&self) -> &Path;
@@ -125,9 +81,11 @@ pub trait FileEntry {
     fn is_parent(&self) -> bool;
     /// Return the size of this entry.
     fn len(&self) -> Option<u64>;
+    /// Return the modification time of this entry in seconds since the unix epoch.
+    fn modified(&self) -> Option<u64>;
 
     /// Return the file name of this entry.
-    fn file_name_lossy(&self) -> Option<Cow<str>> {
+    fn file_name_lossy(&self) -> Option<Cow<'_, str>> {
         self.path()
             .file_name()
             .map(|file_name| file_name.to_string_lossy())
@@ -145,7 +103,7 @@ pub trait FileEntry {
     }
 
     /// Returns the text of this entry for displaying to the user.
-    fn to_text(&self, width: usize) -> Text {
+    fn to_text(&self, width: usize) -> Text<'_> {
         if self.is_parent() {
             // TODO: We can either use the emoji "⬅" or ".." for the parent directory.
             // Text::Styled(Cow::Borrowed(".."), Style::default().fg(Color::Red))
@@ -183,7 +141,7 @@ pub trait FileEntry {
 impl FileEntry for LocalFileEntry {
     fn path(&self) -> &Path {
         match self {
-            LocalFileEntry::File(path, _) => path,
+            LocalFileEntry::File(path, _, _) => path,
             LocalFileEntry::Directory(path) => path,
             LocalFileEntry::Symlink(path) => path,
             LocalFileEntry::Parent(path) => path,
@@ -192,7 +150,7 @@ impl FileEntry for LocalFileEntry {
 
     fn is_dir(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => false,
+            LocalFileEntry::File(_, _, _) => false,
             LocalFileEntry::Directory(_) => true,
             LocalFileEntry::Symlink(_) => false,
             LocalFileEntry::Parent(_) => true,
@@ -201,7 +159,7 @@ impl FileEntry for LocalFileEntry {
 
     fn is_file(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => true,
+            LocalFileEntry::File(_, _, _) => true,
             LocalFileEntry::Directory(_) => false,
             LocalFileEntry::Symlink(_) => false,
             LocalFileEntry::Parent(_) => false,
@@ -210,7 +168,7 @@ impl FileEntry for LocalFileEntry {
 
     fn is_parent(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => false,
+            LocalFileEntry::File(_, _, _) => false,
             LocalFileEntry::Directory(_) => false,
             LocalFileEntry::Symlink(_) => false,
             LocalFileEntry::Parent(_) => true,
@@ -219,7 +177,16 @@ impl FileEntry for LocalFileEntry {
 
     fn len(&self) -> Option<u64> {
         match self {
-            LocalFileEntry::File(_, len) => Some(*len),
+            LocalFileEntry::File(_, len, _) => Some(*len),
+            LocalFileEntry::Directory(_) => None,
+            LocalFileEntry::Symlink(_) => None,
+            LocalFileEntry::Parent(_) => None,
+        }
+    }
+
+    fn modified(&self) -> Option<u64> {
+        match self {
+            LocalFileEntry::File(_, _, modified) => *modified,
             LocalFileEntry::Directory(_) => None,
             LocalFileEntry::Symlink(_) => None,
             LocalFileEntry::Parent(_) => None,
@@ -227,6 +194,28 @@ impl FileEntry for LocalFileEntry {
     }
 }
 
+impl From<RemoteFileEntry> for LocalFileEntry {
+    fn from(entry: RemoteFileEntry) -> Self {
+        match entry {
+            RemoteFileEntry::File(path, len, modified) => LocalFileEntry::File(path, len, modified),
+            RemoteFileEntry::Directory(path) => LocalFileEntry::Directory(path),
+            RemoteFileEntry::Symlink(path) => LocalFileEntry::Symlink(path),
+            RemoteFileEntry::Parent(path) => LocalFileEntry::Parent(path),
+        }
+    }
+}
+
+impl From<LocalFileEntry> for RemoteFileEntry {
+    fn from(entry: LocalFileEntry) -> Self {
+        match entry {
+            LocalFileEntry::File(path, len, modified) => RemoteFileEntry::File(path, len, modified),
+            LocalFileEntry::Directory(path) => RemoteFileEntry::Directory(path),
+            LocalFileEntry::Symlink(path) => RemoteFileEntry::Symlink(path),
+            LocalFileEntry::Parent(path) => RemoteFileEntry::Parent(path),
+        }
+    }
+}
+
 impl LocalFileEntry {
     /// Return a list of `LocalFileEntry`'s that `path` contains if `path`
     /// is a directory.
@@ -235,25 +224,34 @@ impl LocalFileEntry {
     pub fn read_dir(path: &Path) -> io::Result<Vec<LocalFileEntry>> {
         let mut entries = vec![];
         for entry in read_dir(path)? {
-            let path = entry?.path();
-            if path.is_file() {
-                let len = metadata(&path)?.len();
-                entries.push(LocalFileEntry::File(path, len))
-            } else if path.is_dir() {
-                entries.push(LocalFileEntry::Directory(path))
-            } else {
-                // Guess that this file is a symlink.
-                entries.push(LocalFileEntry::Symlink(path))
-            }
+            entries.push(LocalFileEntry::from_path(entry?.path())?);
         }
         Ok(entries)
     }
+
+    /// Return the `LocalFileEntry` found at `path`.
+    pub fn from_path(path: PathBuf) -> io::Result<LocalFileEntry> {
+        if path.is_file() {
+            let metadata = metadata(&path)?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+            Ok(LocalFileEntry::File(path, metadata.len(), modified))
+        } else if path.is_dir() {
+            Ok(LocalFileEntry::Directory(path))
+        } else {
+            // Guess that this file is a symlink.
+            Ok(LocalFileEntry::Symlink(path))
+        }
+    }
 }
 
 impl FileEntry for RemoteFileEntry {
     fn path(&self) -> &Path {
         match self {
-            RemoteFileEntry::File(path, _) => path,
+            RemoteFileEntry::File(path, _, _) => path,
             RemoteFileEntry::Directory(path) => path,
             RemoteFileEntry::Symlink(path) => path,
             RemoteFileEntry::Parent(path) => path,
@@ -262,7 +260,7 @@ impl FileEntry for RemoteFileEntry {
 
     fn is_dir(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => false,
+            RemoteFileEntry::File(_, _, _) => false,
             RemoteFileEntry::Directory(_) => true,
             RemoteFileEntry::Symlink(_) => false,
             RemoteFileEntry::Parent(_) => true,
@@ -271,7 +269,7 @@ impl FileEntry for RemoteFileEntry {
 
     fn is_file(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => true,
+            RemoteFileEntry::File(_, _, _) => true,
             RemoteFileEntry::Directory(_) => false,
             RemoteFileEntry::Symlink(_) => false,
             RemoteFileEntry::Parent(_) => false,
@@ -280,7 +278,7 @@ impl FileEntry for RemoteFileEntry {
 
     fn is_parent(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => false,
+            RemoteFileEntry::File(_, _, _) => false,
             RemoteFileEntry::Directory(_) => false,
             RemoteFileEntry::Symlink(_) => false,
             RemoteFileEntry::Parent(_) => true,
@@ -289,7 +287,16 @@ impl FileEntry for RemoteFileEntry {
 
     fn len(&self) -> Option<u64> {
         match self {
-            RemoteFileEntry::File(_, len) => Some(*len),
+            RemoteFileEntry::File(_, len, _) => Some(*len),
+            RemoteFileEntry::Directory(_) => None,
+            RemoteFileEntry::Symlink(_) => None,
+            RemoteFileEntry::Parent(_) => None,
+        }
+    }
+
+    fn modified(&self) -> Option<u64> {
+        match self {
+            RemoteFileEntry::File(_, _, modified) => *modified,
             RemoteFileEntry::Directory(_) => None,
             RemoteFileEntry::Symlink(_) => None,
             RemoteFileEntry::Parent(_) => None,
@@ -301,20 +308,28 @@ impl RemoteFileEntry {
     pub fn read_dir(path: &Path, sftp: &ssh2::Sftp) -> io::Result<Vec<RemoteFileEntry>> {
         Ok(sftp
             .readdir(path)?
-            .iter()
-            .map(|(path, stat)| {
-                if stat.is_file() {
-                    RemoteFileEntry::File(path.to_path_buf(), stat.size.unwrap())
-                } else if stat.is_dir() {
-                    RemoteFileEntry::Directory(path.to_path_buf())
-                } else {
-                    // Guess that this file is a symlink.
-                    RemoteFileEntry::Symlink(path.to_path_buf())
-                }
-            })
+            .into_iter()
+            .map(|(path, stat)| RemoteFileEntry::from_stat(path, &stat))
             .collect())
     }
 
+    /// Return the `RemoteFileEntry` found at `path`.
+    pub fn from_path(path: PathBuf, sftp: &ssh2::Sftp) -> io::Result<RemoteFileEntry> {
+        let stat = sftp.lstat(&path)?;
+        Ok(RemoteFileEntry::from_stat(path, &stat))
+    }
+
+    pub(crate) fn from_stat(path: PathBuf, stat: &ssh2::FileStat) -> RemoteFileEntry {
+        if stat.is_file() {
+            RemoteFileEntry::File(path, stat.size.unwrap(), stat.mtime)
+        } else if stat.is_dir() {
+            RemoteFileEntry::Directory(path)
+        } else {
+            // Guess that this file is a symlink.
+            RemoteFileEntry::Symlink(path)
+        }
+    }
+
     pub fn exists(path: impl AsRef<Path>, sftp: &ssh2::Sftp) -> Result<bool> {
         match sftp.stat(path.as_ref()) {
             // NOTE: `stat` will fail if this path does not exist on the remote host. We
@@ -329,34 +344,46 @@ impl RemoteFileEntry {
 }
 
 impl FileList {
-    pub fn new(
-        session: &ssh2::Session,
-        sftp: &ssh2::Sftp,
+    /// Create a file list that shows `local_path` of `local_fs` next to `remote_path` of
+    /// `remote_fs`.
+    pub fn new<L, R>(
+        local_fs: &L,
+        local_path: impl AsRef<Path>,
+        remote_fs: &R,
+        remote_path: impl AsRef<Path>,
         keep_hidden_files: bool,
-    ) -> Result<Self> {
-        let local_path = env::current_dir()?;
-        let remote_path = get_remote_home_dir(session).unwrap_or_else(|_| PathBuf::from("./"));
-
+    ) -> Result<Self>
+    where
+        L: FileSystem<Entry = LocalFileEntry> + ?Sized,
+        R: FileSystem<Entry = RemoteFileEntry> + ?Sized,
+    {
         let mut list = FileList {
             local_directory: PathBuf::new(),
             remote_directory: PathBuf::new(),
             local_entries: vec![],
             remote_entries: vec![],
             selected: SelectedFileEntryIndex::None,
+            compare_by: None,
+            local_title: "Local".to_string(),
+            remote_title: "Remote".to_string(),
         };
-        list.set_local_working_path(local_path, keep_hidden_files)?;
-        list.set_remote_working_path(remote_path, sftp, keep_hidden_files)?;
+        list.set_local_working_path(local_path, local_fs, keep_hidden_files)?;
+        list.set_remote_working_path(remote_path, remote_fs, keep_hidden_files)?;
         Ok(list)
     }
 
     /// Read the current local directory and populate this file list with the new local entries.
-    pub fn fetch_local_files(&mut self, keep_hidden_files: bool) -> io::Result<()> {
+    pub fn fetch_local_files(
+        &mut self,
+        local_fs: &(impl FileSystem<Entry = LocalFileEntry> + ?Sized),
+        keep_hidden_files: bool,
+    ) -> Result<()> {
         self.local_entries = self
             .local_directory
             .parent()
             .map(|parent| LocalFileEntry::Parent(parent.to_path_buf()))
             .into_iter()
-            .chain(LocalFileEntry::read_dir(&self.local_directory)?)
+            .chain(local_fs.read_dir(&self.local_directory)?)
             .collect();
 
         if !keep_hidden_files {
@@ -369,15 +396,15 @@ impl FileList {
     /// Read the current remote directory and populate this file list with the new remote entries.
     pub fn fetch_remote_files(
         &mut self,
-        sftp: &ssh2::Sftp,
+        remote_fs: &(impl FileSystem<Entry = RemoteFileEntry> + ?Sized),
         keep_hidden_files: bool,
-    ) -> io::Result<()> {
+    ) -> Result<()> {
         self.remote_entries = self
             .remote_directory
             .parent()
             .map(|parent| RemoteFileEntry::Parent(parent.to_path_buf()))
             .into_iter()
-            .chain(RemoteFileEntry::read_dir(&self.remote_directory, sftp)?)
+            .chain(remote_fs.read_dir(&self.remote_directory)?)
             .collect();
 
         if !keep_hidden_files {
@@ -391,10 +418,11 @@ impl FileList {
     pub fn set_local_working_path(
         &mut self,
         path: impl AsRef<Path>,
+        local_fs: &(impl FileSystem<Entry = LocalFileEntry> + ?Sized),
         keep_hidden_files: bool,
-    ) -> io::Result<()> {
-        self.local_directory = canonicalize(path)?;
-        self.fetch_local_files(keep_hidden_files)?;
+    ) -> Result<()> {
+        self.local_directory = local_fs.canonicalize(path.as_ref())?;
+        self.fetch_local_files(local_fs, keep_hidden_files)?;
         // Make sure we have a valid entry selected.
         self.apply_op_to_selected(|i| i);
         Ok(())
@@ -404,16 +432,26 @@ impl FileList {
     pub fn set_remote_working_path(
         &mut self,
         path: impl AsRef<Path>,
-        sftp: &ssh2::Sftp,
+        remote_fs: &(impl FileSystem<Entry = RemoteFileEntry> + ?Sized),
         keep_hidden_files: bool,
-    ) -> io::Result<()> {
-        self.remote_directory = sftp.realpath(path.as_ref())?;
-        self.fetch_remote_files(sftp, keep_hidden_files)?;
+    ) -> Result<()> {
+        self.remote_directory = remote_fs.canonicalize(path.as_ref())?;
+        self.fetch_remote_files(remote_fs, keep_hidden_files)?;
         // Make sure we have a valid entry selected.
         self.apply_op_to_selected(|i| i);
         Ok(())
     }
 
+    /// Set what the left pane is titled with, like the name of the host it shows.
+    pub fn set_local_title(&mut self, title: &str) {
+        self.local_title = title.to_string();
+    }
+
+    /// Set what the right pane is titled with, like the name of the host it shows.
+    pub fn set_remote_title(&mut self, title: &str) {
+        self.remote_title = title.to_string();
+    }
+
     /// Return the current local directory.
     pub fn get_local_working_path(&self) -> &Path {
         &self.local_directory
@@ -439,6 +477,54 @@ impl FileList {
         }
     }
 
+    /// Compare the entries with the entries of the same name on the other side by `compare_by`,
+    /// or stop comparing them if it is `None`.
+    pub fn set_compare(&mut self, compare_by: Option<CompareBy>) {
+        self.compare_by = compare_by;
+    }
+
+    /// Return how entries are compared with the other side.
+    pub fn get_compare(&self) -> Option<CompareBy> {
+        self.compare_by
+    }
+
+    /// Return how each local entry compares with the remote entries, or `None` if entries are
+    /// not compared.
+    pub fn get_local_comparisons(&self) -> Option<Vec<Comparison>> {
+        self.compare_by
+            .map(|by| compare_lists(&self.local_entries, &self.remote_entries, by))
+    }
+
+    /// Return how each remote entry compares with the local entries, or `None` if entries are
+    /// not compared.
+    pub fn get_remote_comparisons(&self) -> Option<Vec<Comparison>> {
+        self.compare_by
+            .map(|by| compare_lists(&self.remote_entries, &self.local_entries, by))
+    }
+
+    /// Return the local entries that differ from the remote entries or only exist locally.
+    pub fn get_differing_local_entries(&self, by: CompareBy) -> Vec<LocalFileEntry> {
+        Self::differing(&self.local_entries, &self.remote_entries, by)
+    }
+
+    /// Return the remote entries that differ from the local entries or only exist remotely.
+    pub fn get_differing_remote_entries(&self, by: CompareBy) -> Vec<RemoteFileEntry> {
+        Self::differing(&self.remote_entries, &self.local_entries, by)
+    }
+
+    fn differing<E: FileEntry + Clone>(
+        entries: &[E],
+        others: &[impl FileEntry],
+        by: CompareBy,
+    ) -> Vec<E> {
+        entries
+            .iter()
+            .zip(compare_lists(entries, others, by))
+            .filter(|(_, comparison)| *comparison != Comparison::Same)
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
     /// Return the index of the currently selected file entry if a local file entry is selected.
     pub fn get_local_selected_index(&self) -> ListState {
         let index = match self.selected {
@@ -535,6 +621,100 @@ impl FileList {
         self.apply_op_to_selected(|i| i);
     }
 
+    /// Return the rects of the local and remote lists if this file list is drawn in `rect`.
+    fn split(rect: Rect) -> (Rect, Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+            .split(rect);
+        (chunks[0], chunks[1])
+    }
+
+    /// Return the entry index that is drawn on `row` of a list in `list_rect` with `len`
+    /// entries, where `selected` is the selected index of that list.
+    fn index_at(list_rect: Rect, row: u16, len: usize, selected: Option<usize>) -> Option<usize> {
+        let height = list_rect.height.saturating_sub(2) as usize;
+        if row <= list_rect.top() || row + 1 >= list_rect.bottom() || height == 0 {
+            return None;
+        }
+        // The list scrolls just enough to keep the selected entry in view.
+        let offset = selected.map_or(0, |i| (i + 1).saturating_sub(height));
+        let index = offset + (row - list_rect.top() - 1) as usize;
+        if index < len {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Return a selection in the list under `column` if this file list is drawn in `rect`,
+    /// keeping the current index if that list is already selected.
+    fn side_at(&self, rect: Rect, column: u16) -> SelectedFileEntryIndex {
+        let (local_rect, _) = Self::split(rect);
+        let is_local = column < local_rect.right();
+        match self.selected {
+            SelectedFileEntryIndex::Local(i) if is_local => SelectedFileEntryIndex::Local(i),
+            SelectedFileEntryIndex::Remote(i) if !is_local => SelectedFileEntryIndex::Remote(i),
+            _ if is_local => SelectedFileEntryIndex::Local(0),
+            _ => SelectedFileEntryIndex::Remote(0),
+        }
+    }
+
+    /// Select the entry at `column` and `row` if this file list is drawn in `rect`.
+    ///
+    /// Return `true` if there was an entry to select.
+    pub fn select_at(&mut self, rect: Rect, column: u16, row: u16) -> bool {
+        let (local_rect, remote_rect) = Self::split(rect);
+        let local = self.get_local_selected_index().selected();
+        let remote = self.get_remote_selected_index().selected();
+        let selected = if column < local_rect.right() {
+            Self::index_at(local_rect, row, self.local_entries.len(), local)
+                .map(SelectedFileEntryIndex::Local)
+        } else {
+            Self::index_at(remote_rect, row, self.remote_entries.len(), remote)
+                .map(SelectedFileEntryIndex::Remote)
+        };
+        match selected {
+            Some(selected) => {
+                self.selected = selected;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the selection by `delta` entries in the list under `column` if this file list is
+    /// drawn in `rect`.
+    pub fn scroll_at(&mut self, rect: Rect, column: u16, delta: isize) {
+        self.selected = self.side_at(rect, column);
+        self.apply_op_to_selected(|i| i + delta);
+    }
+
+    /// Return the list items of `entries` that are `width` wide, colored by `comparisons` if
+    /// they are compared with the other side.
+    fn generate_items(
+        entries: &[impl FileEntry],
+        comparisons: Option<Vec<Comparison>>,
+        width: usize,
+    ) -> Vec<ListItem<'_>> {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut text = entry.to_text(width);
+                if let Some(comparison) = comparisons.as_ref().map(|c| c[i]) {
+                    let color = match comparison {
+                        Comparison::Unique => FILELIST_UNIQUE_COLOR,
+                        Comparison::Different => FILELIST_DIFFERENT_COLOR,
+                        Comparison::Same => FILELIST_SAME_COLOR,
+                    };
+                    text.patch_style(Style::default().fg(color));
+                }
+                ListItem::new(text)
+            })
+            .collect()
+    }
+
     fn generate_list<'a>(title: &'a str, items: Vec<ListItem<'a>>) -> List<'a> {
         List::new(items)
             .block(Block::default().title(title).borders(Borders::ALL))
@@ -547,30 +727,23 @@ impl FileList {
     where
         B: tui::backend::Backend,
     {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
-            .split(rect);
-        let (local_rect, remote_rect) = (chunks[0], chunks[1]);
+        let (local_rect, remote_rect) = Self::split(rect);
 
-        let title = format!("Local: {:?}", self.get_local_working_path());
+        let title = format!("{}: {:?}", self.local_title, self.get_local_working_path());
         let width = (local_rect.width - 4) as usize;
-        let items: Vec<_> = self
-            .local_entries
-            .iter()
-            .map(|entry| ListItem::new(entry.to_text(width)))
-            .collect();
+        let items = Self::generate_items(&self.local_entries, self.get_local_comparisons(), width);
         let mut state = self.get_local_selected_index();
         let list = Self::generate_list(&title, items);
         frame.render_stateful_widget(list, local_rect, &mut state);
 
-        let title = format!("Remote: {:?}", self.get_remote_working_path());
+        let title = format!(
+            "{}: {:?}",
+            self.remote_title,
+            self.get_remote_working_path()
+        );
         let width = (remote_rect.width - 4) as usize;
-        let items: Vec<_> = self
-            .remote_entries
-            .iter()
-            .map(|entry| ListItem::new(entry.to_text(width)))
-            .collect();
+        let items =
+            Self::generate_items(&self.remote_entries, self.get_remote_comparisons(), width);
         let mut state = self.get_remote_selected_index();
         let list = Self::generate_list(&title, items);
         frame.render_stateful_widget(list, remote_rect, &mut state);
@@ -590,18 +763,21 @@ mod tests {
             remote_directory: PathBuf::from("home/files"),
             local_entries: vec![
                 LocalFileEntry::Parent(PathBuf::from("/a/b/c/..")),
-                LocalFileEntry::File(PathBuf::from("/a/b/c/myfile.txt"), 30_000),
-                LocalFileEntry::File(PathBuf::from("/a/b/c/myotherfile.dat"), 128),
+                LocalFileEntry::File(PathBuf::from("/a/b/c/myfile.txt"), 30_000, None),
+                LocalFileEntry::File(PathBuf::from("/a/b/c/myotherfile.dat"), 128, None),
                 LocalFileEntry::Directory(PathBuf::from("/a/b/c/important")),
             ],
             remote_entries: vec![
                 RemoteFileEntry::Parent(PathBuf::from("home/files/..")),
-                RemoteFileEntry::File(PathBuf::from("home/files/pic.png"), 55_000),
-                RemoteFileEntry::File(PathBuf::from("home/files/movie.mkv"), 123_000_000),
+                RemoteFileEntry::File(PathBuf::from("home/files/pic.png"), 55_000, None),
+                RemoteFileEntry::File(PathBuf::from("home/files/movie.mkv"), 123_000_000, None),
                 RemoteFileEntry::Directory(PathBuf::from("home/files/games")),
                 RemoteFileEntry::Directory(PathBuf::from("home/files/trash")),
             ],
             selected: SelectedFileEntryIndex::Remote(2),
+            compare_by: None,
+            local_title: "Local".to_string(),
+            remote_title: "Remote".to_string(),
         };
 
         let mut terminal = Terminal::new(TestBackend::new(50, 8)).unwrap();
@@ -625,4 +801,60 @@ mod tests {
         ]);
         assert_buffer_symbols_eq(terminal.backend().buffer(), &expected);
     }
+
+    #[test]
+    fn test_compare_file_list() {
+        let mut file_list: FileList = FileList {
+            local_directory: PathBuf::from("/l"),
+            remote_directory: PathBuf::from("/r"),
+            local_entries: vec![
+                LocalFileEntry::File(PathBuf::from("/l/same.txt"), 3, Some(1)),
+                LocalFileEntry::File(PathBuf::from("/l/changed.txt"), 3, Some(2)),
+                LocalFileEntry::File(PathBuf::from("/l/local.txt"), 3, None),
+                LocalFileEntry::Parent(PathBuf::from("/")),
+            ],
+            remote_entries: vec![
+                RemoteFileEntry::File(PathBuf::from("/r/same.txt"), 3, Some(1)),
+                RemoteFileEntry::File(PathBuf::from("/r/changed.txt"), 3, Some(1)),
+                RemoteFileEntry::Parent(PathBuf::from("/")),
+            ],
+            selected: SelectedFileEntryIndex::None,
+            compare_by: None,
+            local_title: "Local".to_string(),
+            remote_title: "Remote".to_string(),
+        };
+        assert_eq!(file_list.get_local_comparisons(), None);
+
+        file_list.set_compare(Some(CompareBy::SizeAndTime));
+        let mut terminal = Terminal::new(TestBackend::new(40, 6)).unwrap();
+        terminal
+            .draw(|frame| {
+                let rect = frame.size();
+                file_list.draw(frame, rect);
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(1, 1).fg, FILELIST_SAME_COLOR);
+        assert_eq!(buffer.get(1, 2).fg, FILELIST_DIFFERENT_COLOR);
+        assert_eq!(buffer.get(1, 3).fg, FILELIST_UNIQUE_COLOR);
+        assert_eq!(buffer.get(21, 2).fg, FILELIST_DIFFERENT_COLOR);
+
+        let paths = |entries: Vec<LocalFileEntry>| -> Vec<PathBuf> {
+            entries.iter().map(|e| e.path().to_path_buf()).collect()
+        };
+        assert_eq!(
+            paths(file_list.get_differing_local_entries(CompareBy::SizeAndTime)),
+            vec![
+                PathBuf::from("/l/changed.txt"),
+                PathBuf::from("/l/local.txt")
+            ]
+        );
+        assert_eq!(
+            paths(file_list.get_differing_local_entries(CompareBy::Size)),
+            vec![PathBuf::from("/l/local.txt")]
+        );
+        assert!(file_list
+            .get_differing_remote_entries(CompareBy::Size)
+            .is_empty());
+    }
 }
@@ -0,0 +1,838 @@
+use crate::file::*;
+use crate::utils::{ErrorKind, Result};
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The metadata of an entry that is shown in its properties.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Properties {
+    pub size: Option<u64>,
+    /// The type and permission bits, like `0o100644` for a file that everyone may read.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// The name of the user with `uid`, if it could be found.
+    pub owner: Option<String>,
+    /// The name of the group with `gid`, if it could be found.
+    pub group: Option<String>,
+    /// The access time in seconds since the unix epoch.
+    pub accessed: Option<u64>,
+    /// The modification time in seconds since the unix epoch.
+    pub modified: Option<u64>,
+}
+
+/// A tree of files that entries can be read from and written to.
+///
+/// Transfers are written against this trait so that they work between any two file systems.
+pub trait FileSystem {
+    /// The kind of entry that this file system lists.
+    type Entry: FileEntry + Clone;
+
+    /// Return true if this file system lives on another host.
+    fn is_remote(&self) -> bool;
+
+    /// Return the entries of the directory `path`.
+    ///
+    /// The directories `.` and `..` are not included.
+    fn read_dir(&self, path: &Path) -> Result<Vec<Self::Entry>>;
+
+    /// Return the entry at `path` following symlinks, or `None` if it does not exist.
+    fn stat(&self, path: &Path) -> Result<Option<Self::Entry>>;
+
+    /// Return the absolute form of the existing `path` with `..` and symlinks resolved.
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+
+    /// Open the file `path` for reading.
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>>;
+
+    /// Create or truncate the file `path` and open it for writing.
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>>;
+
+    /// Create the directory `path`.
+    fn mkdir(&self, path: &Path) -> Result<()>;
+
+    /// Move the entry at `from` to `to`.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Remove the file or empty directory at `path`.
+    fn remove(&self, path: &Path) -> Result<()>;
+
+    /// Set the modification time of the file `path` in seconds since the unix epoch.
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()>;
+
+    /// Open the file `path` for reading from `offset` on.
+    fn open_read_at(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + '_>> {
+        let mut reader = self.open_read(path)?;
+        io::copy(&mut reader.by_ref().take(offset), &mut io::sink())?;
+        Ok(reader)
+    }
+
+    /// Open the existing file `path` for writing after its last byte, to resume a copy.
+    fn open_append(&self, _path: &Path) -> Result<Box<dyn Write + '_>> {
+        Err(unsupported("resuming a copy"))
+    }
+
+    /// Return true if `error` of an earlier call means that the connection to the host was
+    /// lost, and later calls connect again.
+    fn is_connection_lost(&self, _error: &ErrorKind) -> bool {
+        false
+    }
+
+    /// Return the metadata of the entry at `path` without following symlinks.
+    ///
+    /// File systems without permissions only know the size and modification time.
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        let entry = self.stat(path)?.ok_or_else(|| not_found(path))?;
+        Ok(Properties {
+            size: entry.len(),
+            modified: entry.modified(),
+            ..Properties::default()
+        })
+    }
+
+    /// Set the permission bits of the entry at `path` to `mode`, like `chmod`.
+    fn set_mode(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Err(unsupported("changing the mode"))
+    }
+
+    /// Set the owner and group of the entry at `path`, like `chown`.
+    ///
+    /// Either is left as it is if it is `None`.
+    fn set_owner(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> Result<()> {
+        Err(unsupported("changing the owner"))
+    }
+
+    /// Fill in the owner and group names of `properties` from `/etc/passwd` and `/etc/group`.
+    ///
+    /// Names that cannot be found are left empty.
+    fn find_owner_names(&self, properties: &mut Properties) {
+        let find = |database: &str, id: Option<u32>| {
+            let id = id?;
+            let contents = self.read_to_string(Path::new(database)).ok()?;
+            find_name(&contents, id)
+        };
+        properties.owner = find("/etc/passwd", properties.uid);
+        properties.group = find("/etc/group", properties.gid);
+    }
+
+    /// Return the contents of the file `path`.
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        let mut contents = String::new();
+        self.open_read(path)?.read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Return at most `len` bytes of the file `path` starting at `offset`.
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut reader = self.open_read(path)?;
+        io::copy(&mut reader.by_ref().take(offset), &mut io::sink())?;
+        read_at_most(reader, len)
+    }
+
+    /// Set the permission bits of the entry at `path` and everything below it to `mode`.
+    ///
+    /// Symlinks below `path` are not followed.
+    fn set_mode_all(&self, path: &Path, mode: u32) -> Result<()> {
+        if !self.stat(path)?.is_some_and(|entry| entry.is_dir()) {
+            return self.set_mode(path, mode);
+        }
+        let mut directories = vec![];
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            for entry in self.read_dir(&path)? {
+                if entry.is_dir() {
+                    stack.push(entry.path().to_path_buf());
+                } else if entry.is_file() {
+                    self.set_mode(entry.path(), mode)?;
+                }
+            }
+            directories.push(path);
+        }
+        // A mode without search permission would lock us out of directories we still need.
+        for directory in directories.iter().rev() {
+            self.set_mode(directory, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the entry at `from` and everything below it to `to`, keeping modification times.
+    ///
+    /// The contents are streamed through this machine, which works with any server but is
+    /// slower than copying on the server itself. Symlinks below `from` are skipped.
+    fn copy_all(&self, from: &Path, to: &Path) -> Result<()> {
+        if to.starts_with(from) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot copy {} into itself", from.display()),
+            )
+            .into());
+        }
+        let entry = self.stat(from)?.ok_or_else(|| not_found(from))?;
+        if !entry.is_dir() {
+            return copy_file(self, &entry, to);
+        }
+        let mut stack = vec![(from.to_path_buf(), to.to_path_buf())];
+        while let Some((from, to)) = stack.pop() {
+            self.mkdir(&to)?;
+            for entry in self.read_dir(&from)? {
+                let to = to.join(entry.path().file_name().unwrap_or_default());
+                if entry.is_dir() {
+                    stack.push((entry.path().to_path_buf(), to));
+                } else if entry.is_file() {
+                    copy_file(self, &entry, &to)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the entry at `path` and everything below it.
+    fn remove_all(&self, path: &Path) -> Result<()> {
+        let mut directories = vec![];
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            for entry in self.read_dir(&path)? {
+                if entry.is_dir() {
+                    stack.push(entry.path().to_path_buf());
+                } else {
+                    self.remove(entry.path())?;
+                }
+            }
+            directories.push(path);
+        }
+        // Directories must be empty before they can be removed.
+        for directory in directories.iter().rev() {
+            self.remove(directory)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return at most `len` bytes of `reader`.
+fn read_at_most(reader: impl Read, len: u64) -> Result<Vec<u8>> {
+    let mut contents = vec![];
+    reader.take(len).read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Copy the file `entry` of `fs` to `to` on the same file system.
+fn copy_file<F>(fs: &F, entry: &F::Entry, to: &Path) -> Result<()>
+where
+    F: FileSystem + ?Sized,
+{
+    {
+        let mut reader = fs.open_read(entry.path())?;
+        let mut writer = fs.open_write(to)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+    }
+    if let Some(modified) = entry.modified() {
+        fs.set_modified(to, modified)?;
+    }
+    Ok(())
+}
+
+fn not_found(path: &Path) -> ErrorKind {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+    .into()
+}
+
+fn unsupported(what: &str) -> ErrorKind {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is not supported by this file system", what),
+    )
+    .into()
+}
+
+/// Return the name of the entry with `id` in the contents of `/etc/passwd` or `/etc/group`.
+fn find_name(database: &str, id: u32) -> Option<String> {
+    database.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let entry_id = fields.nth(1)?.parse::<u32>().ok()?;
+        if entry_id == id && !name.starts_with('#') {
+            Some(name.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Return `time` in seconds since the unix epoch.
+fn to_seconds(time: io::Result<SystemTime>) -> Option<u64> {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+/// The file system of this machine.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    type Entry = LocalFileEntry;
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<LocalFileEntry>> {
+        Ok(LocalFileEntry::read_dir(path)?)
+    }
+
+    fn stat(&self, path: &Path) -> Result<Option<LocalFileEntry>> {
+        match std::fs::metadata(path) {
+            Ok(_) => Ok(Some(LocalFileEntry::from_path(path.to_path_buf())?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        Ok(std::fs::canonicalize(path)?)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn open_read_at(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + '_>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Box::new(file))
+    }
+
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        Ok(Box::new(
+            std::fs::OpenOptions::new().append(true).open(path)?,
+        ))
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        read_at_most(file, len)
+    }
+
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        #[allow(unused_mut)]
+        let mut properties = Properties {
+            size: Some(metadata.len()),
+            accessed: to_seconds(metadata.accessed()),
+            modified: to_seconds(metadata.modified()),
+            ..Properties::default()
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            properties.mode = Some(metadata.mode());
+            properties.uid = Some(metadata.uid());
+            properties.gid = Some(metadata.gid());
+        }
+        Ok(properties)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(std::fs::set_permissions(
+            path,
+            std::fs::Permissions::from_mode(mode & 0o7777),
+        )?)
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<()> {
+        Ok(std::fs::create_dir(path)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        Ok(std::fs::rename(from, to)?)
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        if std::fs::symlink_metadata(path)?.is_dir() {
+            Ok(std::fs::remove_dir(path)?)
+        } else {
+            Ok(std::fs::remove_file(path)?)
+        }
+    }
+
+    fn remove_all(&self, path: &Path) -> Result<()> {
+        // Unlike `read_dir`, this does not follow symlinks to directories.
+        if std::fs::symlink_metadata(path)?.is_dir() {
+            Ok(std::fs::remove_dir_all(path)?)
+        } else {
+            Ok(std::fs::remove_file(path)?)
+        }
+    }
+
+    fn set_mode_all(&self, path: &Path, mode: u32) -> Result<()> {
+        // Unlike `read_dir`, this does not follow symlinks, which may point out of `path`.
+        if !std::fs::symlink_metadata(path)?.is_dir() {
+            return self.set_mode(path, mode);
+        }
+        let mut directories = vec![];
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            for entry in std::fs::read_dir(&path)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    stack.push(entry.path());
+                } else if file_type.is_file() {
+                    self.set_mode(&entry.path(), mode)?;
+                }
+            }
+            directories.push(path);
+        }
+        // A mode without search permission would lock us out of directories we still need.
+        for directory in directories.iter().rev() {
+            self.set_mode(directory, mode)?;
+        }
+        Ok(())
+    }
+
+    fn copy_all(&self, from: &Path, to: &Path) -> Result<()> {
+        // `to` does not exist yet, so where it ends up is told by its parent.
+        let parent = to
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let resolved_to = parent
+            .canonicalize()?
+            .join(to.file_name().unwrap_or_default());
+        if resolved_to.starts_with(from.canonicalize()?) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot copy {} into itself", from.display()),
+            )
+            .into());
+        }
+        let entry = self.stat(from)?.ok_or_else(|| not_found(from))?;
+        if !entry.is_dir() {
+            return copy_file(self, &entry, to);
+        }
+        // Unlike `read_dir`, this skips symlinks, which may loop or point out of `from`.
+        let mut stack = vec![(from.to_path_buf(), to.to_path_buf())];
+        while let Some((from, to)) = stack.pop() {
+            self.mkdir(&to)?;
+            for entry in std::fs::read_dir(&from)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                let to = to.join(entry.file_name());
+                if file_type.is_dir() {
+                    stack.push((entry.path(), to));
+                } else if file_type.is_file() {
+                    copy_file(self, &LocalFileEntry::from_path(entry.path())?, &to)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()> {
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(modified))?;
+        Ok(())
+    }
+}
+
+/// The file system of a remote host behind an sftp channel.
+impl FileSystem for ssh2::Sftp {
+    type Entry = RemoteFileEntry;
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<RemoteFileEntry>> {
+        Ok(RemoteFileEntry::read_dir(path, self)?)
+    }
+
+    fn stat(&self, path: &Path) -> Result<Option<RemoteFileEntry>> {
+        match ssh2::Sftp::stat(self, path) {
+            Ok(stat) => Ok(Some(RemoteFileEntry::from_stat(path.to_path_buf(), &stat))),
+            // NOTE: See `RemoteFileEntry::exists`.
+            Err(error) => match error.code() {
+                ssh2::ErrorCode::SFTP(libssh2_sys::LIBSSH2_FX_NO_SUCH_FILE) => Ok(None),
+                _ => Err(error.into()),
+            },
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        Ok(self.realpath(path)?)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.open(path)?))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        Ok(Box::new(self.create(path)?))
+    }
+
+    fn open_read_at(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + '_>> {
+        let mut file = self.open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Box::new(file))
+    }
+
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        let mut file = self.open_mode(path, ssh2::OpenFlags::WRITE, 0o644, ssh2::OpenType::File)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Box::new(file))
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut file = self.open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        read_at_most(file, len)
+    }
+
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        let stat = self.lstat(path)?;
+        Ok(Properties {
+            size: stat.size,
+            mode: stat.perm,
+            uid: stat.uid,
+            gid: stat.gid,
+            accessed: stat.atime,
+            modified: stat.mtime,
+            ..Properties::default()
+        })
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode & 0o7777),
+            atime: None,
+            mtime: None,
+        };
+        Ok(self.setstat(path, stat)?)
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        // The protocol sets both ids at once, so the one that is kept must be sent as well.
+        let (uid, gid) = match (uid, gid) {
+            (Some(uid), Some(gid)) => (uid, gid),
+            _ => {
+                let stat = self.lstat(path)?;
+                (
+                    uid.or(stat.uid).unwrap_or_default(),
+                    gid.or(stat.gid).unwrap_or_default(),
+                )
+            }
+        };
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: Some(uid),
+            gid: Some(gid),
+            perm: None,
+            atime: None,
+            mtime: None,
+        };
+        Ok(self.setstat(path, stat)?)
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<()> {
+        Ok(ssh2::Sftp::mkdir(self, path, 0o0755)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        Ok(ssh2::Sftp::rename(self, from, to, None)?)
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        if self.lstat(path)?.is_dir() {
+            Ok(self.rmdir(path)?)
+        } else {
+            Ok(self.unlink(path)?)
+        }
+    }
+
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()> {
+        let stat = ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: None,
+            atime: Some(modified),
+            mtime: Some(modified),
+        };
+        Ok(self.setstat(path, stat)?)
+    }
+}
+
+/// A file system whose entries are listed as another kind of entry `E`, so that a pane
+/// that usually shows this machine can show a server and the other way around.
+pub struct Relabeled<F, E> {
+    fs: F,
+    entry: PhantomData<fn() -> E>,
+}
+
+impl<F, E> Relabeled<F, E> {
+    /// List the entries of `fs` as entries of the type `E`.
+    pub fn new(fs: F) -> Self {
+        Relabeled {
+            fs,
+            entry: PhantomData,
+        }
+    }
+}
+
+impl<F, E> FileSystem for Relabeled<F, E>
+where
+    F: FileSystem,
+    E: FileEntry + Clone + From<F::Entry>,
+{
+    type Entry = E;
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<E>> {
+        Ok(self.fs.read_dir(path)?.into_iter().map(E::from).collect())
+    }
+
+    fn stat(&self, path: &Path) -> Result<Option<E>> {
+        Ok(self.fs.stat(path)?.map(E::from))
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        self.fs.open_read(path)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.fs.open_write(path)
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.fs.read_range(path, offset, len)
+    }
+
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        self.fs.properties(path)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.fs.set_mode(path, mode)
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.fs.set_owner(path, uid, gid)
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<()> {
+        self.fs.mkdir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.fs.rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        self.fs.remove(path)
+    }
+
+    fn remove_all(&self, path: &Path) -> Result<()> {
+        self.fs.remove_all(path)
+    }
+
+    fn set_mode_all(&self, path: &Path, mode: u32) -> Result<()> {
+        self.fs.set_mode_all(path, mode)
+    }
+
+    fn copy_all(&self, from: &Path, to: &Path) -> Result<()> {
+        self.fs.copy_all(from, to)
+    }
+
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()> {
+        self.fs.set_modified(path, modified)
+    }
+
+    fn open_read_at(&self, path: &Path, offset: u64) -> Result<Box<dyn Read + '_>> {
+        self.fs.open_read_at(path, offset)
+    }
+
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.fs.open_append(path)
+    }
+
+    fn is_connection_lost(&self, error: &ErrorKind) -> bool {
+        self.fs.is_connection_lost(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_file_system() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-fs-{}", std::process::id()));
+        fs.mkdir(&root).unwrap();
+        fs.mkdir(&root.join("a")).unwrap();
+        write!(fs.open_write(&root.join("a/b.txt")).unwrap(), "hello").unwrap();
+        fs.set_modified(&root.join("a/b.txt"), 1_000_000).unwrap();
+
+        let entry = fs.stat(&root.join("a/b.txt")).unwrap().unwrap();
+        assert_eq!(entry.len(), Some(5));
+        assert_eq!(entry.modified(), Some(1_000_000));
+        assert_eq!(fs.read_to_string(&root.join("a/b.txt")).unwrap(), "hello");
+        assert_eq!(fs.read_range(&root.join("a/b.txt"), 1, 3).unwrap(), b"ell");
+        assert_eq!(fs.read_range(&root.join("a/b.txt"), 3, 10).unwrap(), b"lo");
+        assert!(fs.stat(&root.join("missing")).unwrap().is_none());
+
+        write!(fs.open_append(&root.join("a/b.txt")).unwrap(), "!").unwrap();
+        let mut rest = String::new();
+        fs.open_read_at(&root.join("a/b.txt"), 4)
+            .unwrap()
+            .read_to_string(&mut rest)
+            .unwrap();
+        assert_eq!(rest, "o!");
+        write!(fs.open_write(&root.join("a/b.txt")).unwrap(), "hello").unwrap();
+        fs.set_modified(&root.join("a/b.txt"), 1_000_000).unwrap();
+
+        fs.rename(&root.join("a/b.txt"), &root.join("a/c.txt"))
+            .unwrap();
+        assert_eq!(fs.read_dir(&root.join("a")).unwrap().len(), 1);
+
+        fs.copy_all(&root.join("a"), &root.join("d")).unwrap();
+        let entry = fs.stat(&root.join("d/c.txt")).unwrap().unwrap();
+        assert_eq!(entry.modified(), Some(1_000_000));
+        assert_eq!(fs.read_to_string(&root.join("d/c.txt")).unwrap(), "hello");
+        assert!(fs.copy_all(&root.join("a"), &root.join("a/e")).is_err());
+
+        fs.remove_all(&root).unwrap();
+        assert!(fs.stat(&root).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_local_properties() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-props-{}", std::process::id()));
+        fs.mkdir(&root).unwrap();
+        fs.mkdir(&root.join("a")).unwrap();
+        write!(fs.open_write(&root.join("a/b.txt")).unwrap(), "hello").unwrap();
+        fs.set_modified(&root.join("a/b.txt"), 1_000_000).unwrap();
+
+        let properties = fs.properties(&root.join("a/b.txt")).unwrap();
+        assert_eq!(properties.size, Some(5));
+        assert_eq!(properties.modified, Some(1_000_000));
+        assert!(properties.uid.is_some());
+
+        fs.set_mode_all(&root, 0o700).unwrap();
+        let mode = |path: &str| fs.properties(&root.join(path)).unwrap().mode.unwrap();
+        assert_eq!(mode("a/b.txt"), 0o100700);
+        assert_eq!(mode("a"), 0o040700);
+
+        fs.remove_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_local_copy_all_skips_symlinks() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-copy-{}", std::process::id()));
+        fs.mkdir(&root).unwrap();
+        fs.mkdir(&root.join("outside")).unwrap();
+        write!(fs.open_write(&root.join("outside/b.txt")).unwrap(), "hello").unwrap();
+        fs.mkdir(&root.join("tree")).unwrap();
+        write!(fs.open_write(&root.join("tree/a.txt")).unwrap(), "a").unwrap();
+        std::os::unix::fs::symlink(root.join("outside"), root.join("tree/dir")).unwrap();
+        std::os::unix::fs::symlink(root.join("outside/b.txt"), root.join("tree/file")).unwrap();
+        std::os::unix::fs::symlink(root.join("tree"), root.join("tree/loop")).unwrap();
+
+        fs.copy_all(&root.join("tree"), &root.join("copy")).unwrap();
+        let names: Vec<_> = std::fs::read_dir(root.join("copy"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a.txt"]);
+
+        // A destination below a symlink to the source is still inside the source.
+        std::os::unix::fs::symlink(root.join("tree"), root.join("link")).unwrap();
+        assert!(fs
+            .copy_all(&root.join("tree"), &root.join("link/inner"))
+            .is_err());
+        assert!(!root.join("tree/inner").exists());
+
+        fs.remove_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_local_set_mode_all_skips_symlinks() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-chmod-{}", std::process::id()));
+        let outside = root.join("outside");
+        fs.mkdir(&root).unwrap();
+        fs.mkdir(&outside).unwrap();
+        write!(fs.open_write(&outside.join("b.txt")).unwrap(), "hello").unwrap();
+        fs.set_mode(&outside, 0o755).unwrap();
+        fs.set_mode(&outside.join("b.txt"), 0o644).unwrap();
+        fs.mkdir(&root.join("tree")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("tree/dir")).unwrap();
+        std::os::unix::fs::symlink(outside.join("b.txt"), root.join("tree/file")).unwrap();
+
+        fs.set_mode_all(&root.join("tree"), 0o700).unwrap();
+        let mode = |path: &str| fs.properties(&root.join(path)).unwrap().mode.unwrap();
+        assert_eq!(mode("tree"), 0o040700);
+        assert_eq!(mode("outside"), 0o040755);
+        assert_eq!(mode("outside/b.txt"), 0o100644);
+
+        fs.remove_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_relabeled() {
+        let fs: Relabeled<_, RemoteFileEntry> = Relabeled::new(LocalFileSystem);
+        let root = std::env::temp_dir().join(format!("rftp-test-relabel-{}", std::process::id()));
+        fs.mkdir(&root).unwrap();
+        write!(fs.open_write(&root.join("a.txt")).unwrap(), "hello").unwrap();
+        assert!(!fs.is_remote());
+        assert!(matches!(
+            fs.read_dir(&root).unwrap().as_slice(),
+            [RemoteFileEntry::File(_, 5, _)]
+        ));
+        fs.remove_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_find_name() {
+        let passwd = "# comment\nroot:x:0:0:root:/root:/bin/sh\nwww:x:33:33::/var/www:/bin/sh\n";
+        assert_eq!(find_name(passwd, 0), Some("root".to_string()));
+        assert_eq!(find_name(passwd, 33), Some("www".to_string()));
+        assert_eq!(find_name(passwd, 1000), None);
+        assert_eq!(
+            find_name("staff:x:50:alice,bob\n", 50),
+            Some("staff".to_string())
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::file::FileEntry;
+use crate::utils::Result;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The names of the files that list patterns to ignore in the directory that contains them.
+pub const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".rftpignore"];
+
+/// Patterns that decide which entries are skipped during a recursive transfer.
+///
+/// Patterns use the same syntax as `.gitignore` files and include patterns take
+/// precedence over exclude patterns.
+#[derive(Clone, Default)]
+pub struct Filter {
+    excludes: Vec<String>,
+    includes: Vec<String>,
+    use_ignore_files: bool,
+}
+
+impl Filter {
+    /// Skip entries that match `pattern`.
+    pub fn exclude(&mut self, pattern: &str) {
+        self.excludes.push(pattern.to_string());
+    }
+
+    /// Do not skip entries that match `pattern`, even if they match an exclude pattern.
+    pub fn include(&mut self, pattern: &str) {
+        self.includes.push(pattern.to_string());
+    }
+
+    /// Skip entries that match any pattern listed in the file at `path`.
+    pub fn exclude_from(&mut self, path: &Path) -> io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        for line in contents.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                self.exclude(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the exclude patterns.
+    pub fn get_excludes(&self) -> &[String] {
+        &self.excludes
+    }
+
+    /// Replace all exclude patterns with `patterns`.
+    pub fn set_excludes(&mut self, patterns: Vec<String>) {
+        self.excludes = patterns;
+    }
+
+    /// Return true if patterns in `.gitignore` and `.rftpignore` files are respected.
+    pub fn uses_ignore_files(&self) -> bool {
+        self.use_ignore_files
+    }
+
+    /// Set whether patterns in `.gitignore` and `.rftpignore` files are respected.
+    pub fn set_use_ignore_files(&mut self, use_ignore_files: bool) {
+        self.use_ignore_files = use_ignore_files;
+    }
+
+    /// Return the matcher for a traversal of the tree at `root`.
+    pub fn for_tree(&self, root: &Path) -> TreeFilter {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &self.excludes {
+            // Invalid patterns are ignored, just like git does.
+            builder.add_line(None, pattern).ok();
+        }
+        for pattern in &self.includes {
+            builder.add_line(None, &format!("!{}", pattern)).ok();
+        }
+        TreeFilter {
+            patterns: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+            ignore_files: vec![],
+            use_ignore_files: self.use_ignore_files,
+        }
+    }
+}
+
+/// Decides which entries to skip while traversing a single tree.
+pub struct TreeFilter {
+    patterns: Gitignore,
+    /// The patterns read from ignore files along with the directory that contains them.
+    ignore_files: Vec<(PathBuf, Gitignore)>,
+    use_ignore_files: bool,
+}
+
+impl TreeFilter {
+    /// Add the patterns of an ignore file found in `directory` that contains `contents`.
+    pub fn add_ignore_file(&mut self, directory: &Path, contents: &str) {
+        let mut builder = GitignoreBuilder::new(directory);
+        for line in contents.lines() {
+            builder.add_line(None, line).ok();
+        }
+        if let Ok(gitignore) = builder.build() {
+            self.ignore_files.push((directory.to_path_buf(), gitignore));
+        }
+    }
+
+    /// Return the `entries` of `directory` that should not be skipped.
+    ///
+    /// If ignore files are used, the ignore files among `entries` are read with `read_to_string`
+    /// first so that their patterns apply to `directory` and everything below it.
+    pub fn filter_entries<E, F>(
+        &mut self,
+        directory: &Path,
+        entries: Vec<E>,
+        read_to_string: F,
+    ) -> Result<Vec<E>>
+    where
+        E: FileEntry,
+        F: Fn(&Path) -> Result<String>,
+    {
+        if self.use_ignore_files {
+            for entry in entries.iter().filter(|entry| entry.is_file()) {
+                let is_ignore_file = entry
+                    .file_name_lossy()
+                    .is_some_and(|name| IGNORE_FILE_NAMES.contains(&name.as_ref()));
+                if is_ignore_file {
+                    let contents = read_to_string(entry.path())?;
+                    self.add_ignore_file(directory, &contents);
+                }
+            }
+        }
+        Ok(entries
+            .into_iter()
+            .filter(|entry| !self.is_excluded(entry.path(), entry.is_dir()))
+            .collect())
+    }
+
+    /// Return true if the entry at `path` should be skipped.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        match self.patterns.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+        // Patterns in deeper ignore files take precedence.
+        let mut ignore_files: Vec<_> = self
+            .ignore_files
+            .iter()
+            .filter(|(directory, _)| path.starts_with(directory))
+            .collect();
+        ignore_files
+            .sort_by_key(|(directory, _)| std::cmp::Reverse(directory.components().count()));
+        for (_, gitignore) in ignore_files {
+            match gitignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let mut filter = Filter::default();
+        filter.exclude("target/");
+        filter.exclude("*.log");
+        filter.exclude("/build");
+        filter.include("keep.log");
+        let tree = filter.for_tree(Path::new("/project"));
+
+        assert!(tree.is_excluded(Path::new("/project/target"), true));
+        assert!(tree.is_excluded(Path::new("/project/sub/target"), true));
+        assert!(!tree.is_excluded(Path::new("/project/target"), false));
+        assert!(tree.is_excluded(Path::new("/project/sub/debug.log"), false));
+        assert!(!tree.is_excluded(Path::new("/project/sub/keep.log"), false));
+        assert!(tree.is_excluded(Path::new("/project/build"), true));
+        assert!(!tree.is_excluded(Path::new("/project/sub/build"), true));
+        assert!(!tree.is_excluded(Path::new("/project/src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_ignore_files() {
+        let mut filter = Filter::default();
+        filter.set_use_ignore_files(true);
+        let mut tree = filter.for_tree(Path::new("/project"));
+        tree.add_ignore_file(Path::new("/project"), "# comment\nnode_modules/\n*.tmp\n");
+        tree.add_ignore_file(Path::new("/project/web"), "!important.tmp\ndist\n");
+
+        assert!(tree.is_excluded(Path::new("/project/node_modules"), true));
+        assert!(tree.is_excluded(Path::new("/project/web/node_modules"), true));
+        assert!(tree.is_excluded(Path::new("/project/a.tmp"), false));
+        assert!(tree.is_excluded(Path::new("/project/web/a.tmp"), false));
+        assert!(!tree.is_excluded(Path::new("/project/web/important.tmp"), false));
+        assert!(tree.is_excluded(Path::new("/project/web/dist"), true));
+        assert!(!tree.is_excluded(Path::new("/project/dist"), true));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::utils::Result;
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// How to treat a host key that is not in the known hosts file, like `StrictHostKeyChecking`
+/// of `ssh_config`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StrictHostKeyChecking {
+    /// Ask the user whether to add the key.
+    #[default]
+    Ask,
+    /// Refuse to connect.
+    Yes,
+    /// Add the key without asking, but refuse to connect if a known key changed.
+    AcceptNew,
+    /// Add the key without asking, and only warn if a known key changed. A changed key still
+    /// turns off password authentication, leaving only the keys of the agent.
+    No,
+}
+
+impl StrictHostKeyChecking {
+    /// The values that `parse` accepts.
+    pub const VALUES: &'static [&'static str] = &["ask", "yes", "accept-new", "no"];
+
+    /// Parse one of `ask`, `yes`, `accept-new` or `no`.
+    pub fn parse(value: &str) -> Option<StrictHostKeyChecking> {
+        match value.to_lowercase().as_str() {
+            "ask" => Some(StrictHostKeyChecking::Ask),
+            "yes" | "true" => Some(StrictHostKeyChecking::Yes),
+            "accept-new" => Some(StrictHostKeyChecking::AcceptNew),
+            "no" | "false" | "off" => Some(StrictHostKeyChecking::No),
+            _ => None,
+        }
+    }
+}
+
+/// The result of looking up the key of a host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// The key is known for the host.
+    Match,
+    /// The host is known with a different key of the same type.
+    Mismatch,
+    /// The key was revoked with a `@revoked` line.
+    Revoked,
+    /// The host is not known with a key of this type.
+    NotFound,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Marker {
+    CertAuthority,
+    Revoked,
+}
+
+struct Entry {
+    marker: Option<Marker>,
+    /// The comma-separated host patterns, or a single hashed host name.
+    hosts: String,
+    key_type: String,
+    key: Vec<u8>,
+}
+
+/// The entries of a known hosts file in the format of OpenSSH.
+///
+/// Host names may be hashed, like `ssh-keygen -H` writes them, and hosts on other ports than
+/// 22 are written as `[host]:port`. Lines that cannot be parsed are skipped, like `ssh` does.
+#[derive(Default)]
+pub struct KnownHosts {
+    entries: Vec<Entry>,
+}
+
+impl KnownHosts {
+    /// Read the known hosts file at `path`, which is empty if it does not exist.
+    pub fn read(path: &Path) -> Result<KnownHosts> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(KnownHosts::parse(&contents)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(KnownHosts::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn parse(contents: &str) -> KnownHosts {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let mut fields = line.split_whitespace().peekable();
+                let marker = match fields.peek() {
+                    Some(&"@cert-authority") => Some(Marker::CertAuthority),
+                    Some(&"@revoked") => Some(Marker::Revoked),
+                    Some(field) if field.starts_with('@') => return None,
+                    _ => None,
+                };
+                if marker.is_some() {
+                    fields.next();
+                }
+                Some(Entry {
+                    marker,
+                    hosts: fields.next()?.to_string(),
+                    key_type: fields.next()?.to_string(),
+                    key: base64::decode(fields.next()?).ok()?,
+                })
+            })
+            .collect();
+        KnownHosts { entries }
+    }
+
+    /// Look up `key`, which is the host key of `host` on `port` as the server sent it.
+    ///
+    /// `@cert-authority` lines are never matched because they only vouch for certificates,
+    /// which are not negotiated.
+    pub fn check(&self, host: &str, port: u16, key: &[u8]) -> Check {
+        let name = host_name(host, port);
+        let key_type = key_type(key);
+        let mut check = Check::NotFound;
+        for entry in self.entries.iter().filter(|entry| entry.matches(&name)) {
+            match entry.marker {
+                Some(Marker::Revoked) if entry.key == key => return Check::Revoked,
+                Some(_) => {}
+                None if entry.key == key => check = Check::Match,
+                None if Some(entry.key_type.as_str()) == key_type && check != Check::Match => {
+                    check = Check::Mismatch
+                }
+                None => {}
+            }
+        }
+        check
+    }
+
+    /// Return true if a `@cert-authority` line names `host` on `port`.
+    pub fn has_cert_authority(&self, host: &str, port: u16) -> bool {
+        let name = host_name(host, port);
+        self.entries
+            .iter()
+            .any(|entry| entry.marker == Some(Marker::CertAuthority) && entry.matches(&name))
+    }
+
+    /// Append `key` as the key of `host` on `port` to the file at `path`, and hash the host
+    /// name if `hash` is true. The rest of the file is left as it is.
+    pub fn append(path: &Path, host: &str, port: u16, key: &[u8], hash: bool) -> Result<()> {
+        let name = host_name(host, port);
+        let hosts = if hash { hash_host_name(&name)? } else { name };
+        let key_type = key_type(key).unwrap_or("unknown");
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut line = format!("{} {} {}\n", hosts, key_type, base64::encode(key));
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Entry {
+    /// Return true if this entry names the host `name`, as returned by `host_name`.
+    fn matches(&self, name: &str) -> bool {
+        if let Some(hashed) = self.hosts.strip_prefix("|1|") {
+            return hashed
+                .split_once('|')
+                .and_then(|(salt, hash)| {
+                    Some((base64::decode(salt).ok()?, base64::decode(hash).ok()?))
+                })
+                .is_some_and(|(salt, hash)| {
+                    let mut mac = Hmac::<Sha1>::new_from_slice(&salt).unwrap();
+                    mac.update(name.as_bytes());
+                    mac.verify_slice(&hash).is_ok()
+                });
+        }
+        let mut is_match = false;
+        for pattern in self.hosts.split(',') {
+            match pattern.strip_prefix('!') {
+                Some(pattern) if wildcard_match(&pattern.to_lowercase(), name) => return false,
+                Some(_) => {}
+                None => is_match |= wildcard_match(&pattern.to_lowercase(), name),
+            }
+        }
+        is_match
+    }
+}
+
+/// Return the name of `host` in a known hosts file, which includes the port unless it is 22.
+///
+/// The name is lowercased like `ssh` does, so that hashed entries match however it was typed.
+pub fn host_name(host: &str, port: u16) -> String {
+    let host = host.to_lowercase();
+    if port == 22 {
+        host
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Hash `name` with a random salt like `HashKnownHosts` does.
+fn hash_host_name(name: &str) -> Result<String> {
+    let mut salt = [0; 20];
+    getrandom::getrandom(&mut salt).map_err(|error| std::io::Error::other(error.to_string()))?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&salt).unwrap();
+    mac.update(name.as_bytes());
+    Ok(format!(
+        "|1|{}|{}",
+        base64::encode(salt),
+        base64::encode(mac.finalize().into_bytes())
+    ))
+}
+
+/// Return the type of a public key blob, which starts with it, like `ssh-ed25519`.
+fn key_type(key: &[u8]) -> Option<&str> {
+    let len = key
+        .get(..4)?
+        .iter()
+        .fold(0, |len, &byte| len << 8 | byte as usize);
+    std::str::from_utf8(key.get(4..4 + len)?).ok()
+}
+
+/// Match `name` against `pattern`, where `*` matches any characters and `?` matches one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.chars().next() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len())
+            .filter(|&i| name.is_char_boundary(i))
+            .any(|i| wildcard_match(&pattern[1..], &name[i..])),
+        Some(c) => name.chars().next().is_some_and(|first| {
+            (c == '?' || c == first)
+                && wildcard_match(&pattern[c.len_utf8()..], &name[first.len_utf8()..])
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_type: &str, data: &[u8]) -> Vec<u8> {
+        let mut key = (key_type.len() as u32).to_be_bytes().to_vec();
+        key.extend(key_type.as_bytes());
+        key.extend(data);
+        key
+    }
+
+    fn line(hosts: &str, key: &[u8]) -> String {
+        format!(
+            "{} {} {}\n",
+            hosts,
+            key_type(key).unwrap(),
+            base64::encode(key)
+        )
+    }
+
+    #[test]
+    fn test_check() {
+        let ed25519 = key("ssh-ed25519", b"one");
+        let other = key("ssh-ed25519", b"two");
+        let rsa = key("ssh-rsa", b"three");
+        let known_hosts = KnownHosts::parse(&format!(
+            "# Comment\n\
+             not a valid line\n\
+             {}{}{}@cert-authority *.example.com {}",
+            line("example.com,192.0.2.1", &ed25519),
+            line("[example.com]:2222", &other),
+            line("*.example.org,!bad.example.org", &ed25519),
+            line("", &rsa).trim_start(),
+        ));
+
+        assert_eq!(known_hosts.check("example.com", 22, &ed25519), Check::Match);
+        assert_eq!(known_hosts.check("192.0.2.1", 22, &ed25519), Check::Match);
+        assert_eq!(
+            known_hosts.check("Example.COM", 22, &other),
+            Check::Mismatch
+        );
+        assert_eq!(known_hosts.check("example.com", 22, &rsa), Check::NotFound);
+        assert_eq!(known_hosts.check("example.com", 2222, &other), Check::Match);
+        assert_eq!(
+            known_hosts.check("example.com", 2222, &ed25519),
+            Check::Mismatch
+        );
+        assert_eq!(
+            known_hosts.check("www.example.org", 22, &ed25519),
+            Check::Match
+        );
+        assert_eq!(
+            known_hosts.check("bad.example.org", 22, &ed25519),
+            Check::NotFound
+        );
+        assert_eq!(
+            known_hosts.check("www.example.com", 22, &rsa),
+            Check::NotFound
+        );
+        assert!(known_hosts.has_cert_authority("www.example.com", 22));
+        assert!(!known_hosts.has_cert_authority("example.com", 22));
+    }
+
+    #[test]
+    fn test_revoked() {
+        let ed25519 = key("ssh-ed25519", b"one");
+        let known_hosts = KnownHosts::parse(&format!(
+            "{}@revoked * {}",
+            line("example.com", &ed25519),
+            line("", &ed25519).trim_start(),
+        ));
+        assert_eq!(
+            known_hosts.check("example.com", 22, &ed25519),
+            Check::Revoked
+        );
+    }
+
+    #[test]
+    fn test_hashed() {
+        // Written by `ssh-keygen -H` for `[localhost]:2222`.
+        let known_hosts = KnownHosts::parse(
+            "|1|pI9pzfsV0JJHZoHRJC6FT4qCrBc=|84UFetJpznUyF8yXBBxRi3i+jms= ssh-ed25519 \
+             AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl",
+        );
+        let ed25519 =
+            base64::decode("AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl")
+                .unwrap();
+        assert_eq!(known_hosts.check("localhost", 2222, &ed25519), Check::Match);
+        assert_eq!(known_hosts.check("LocalHost", 2222, &ed25519), Check::Match);
+        assert_eq!(
+            known_hosts.check("localhost", 22, &ed25519),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let path = std::env::temp_dir()
+            .join(format!("rftp-test-known-hosts-{}", std::process::id()))
+            .join("known_hosts");
+        let ed25519 = key("ssh-ed25519", b"one");
+        let rsa = key("ssh-rsa", b"two");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "# Kept as it is\n@unknown-marker x y z").unwrap();
+
+        KnownHosts::append(&path, "Example.com", 2222, &ed25519, false).unwrap();
+        KnownHosts::append(&path, "example.org", 22, &rsa, true).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[..2], ["# Kept as it is", "@unknown-marker x y z"]);
+        assert_eq!(lines[2], line("[example.com]:2222", &ed25519).trim_end());
+        assert!(lines[3].starts_with("|1|"));
+        assert!(!lines[3].contains("example.org"));
+
+        let known_hosts = KnownHosts::read(&path).unwrap();
+        assert_eq!(
+            known_hosts.check("example.com", 2222, &ed25519),
+            Check::Match
+        );
+        assert_eq!(known_hosts.check("Example.ORG", 22, &rsa), Check::Match);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*.example.com", "a.b.example.com"));
+        assert!(wildcard_match("192.0.2.?", "192.0.2.7"));
+        assert!(!wildcard_match("192.0.2.?", "192.0.2.17"));
+        assert!(!wildcard_match("example.com", "example.co"));
+    }
+}
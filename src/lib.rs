@@ -0,0 +1,56 @@
+//! Download and upload files from a server via sftp.
+//!
+//! This crate holds the connection, traversal and progress logic behind the `rftp` command,
+//! so that other tools can transfer files the same way.
+//!
+//! ```no_run
+//! use rftp::{Client, ConnectOptions, FileEntry, Filter, ProgressCallback};
+//!
+//! # fn main() -> rftp::Result<()> {
+//! let options = ConnectOptions {
+//!     port: Some(2222),
+//!     ..ConnectOptions::new("deploy")
+//! };
+//! let client = Client::connect("example.com", &options)?;
+//! for entry in client.list_dir("/var/www")? {
+//!     println!("{}", entry.path().display());
+//! }
+//!
+//! let mut filter = Filter::default();
+//! filter.exclude("*.log");
+//! let listener = ProgressCallback(|progress: &rftp::progress::ProgressFile| {
+//!     eprintln!("{}  {}", progress.get_title(), progress.get_info());
+//! });
+//! client.put_recursive("build", "/var/www/build", &filter, &listener)?;
+//! client.disconnect()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every fallible function returns [`ErrorKind`], which also knows the exit status that the
+//! `rftp` command uses for it.
+
+pub mod client;
+pub mod compare;
+pub mod config;
+pub mod connect;
+pub mod connection;
+pub mod diff;
+pub mod file;
+pub mod filesystem;
+pub mod filter;
+pub mod known_hosts;
+#[cfg(any(test, feature = "testing"))]
+pub mod memory;
+pub mod progress;
+pub mod sync;
+pub mod transfer;
+pub mod utils;
+
+pub use client::{Client, ProgressCallback};
+pub use connect::ConnectOptions;
+pub use file::{FileEntry, LocalFileEntry, RemoteFileEntry};
+pub use filesystem::{FileSystem, LocalFileSystem};
+pub use filter::Filter;
+pub use transfer::{PlannedAction, TransferAction, TransferListener};
+pub use utils::{ErrorKind, Result};
@@ -0,0 +1,176 @@
+use crate::user_message::{LogEntry, Severity, UserMessage};
+use rftp::utils::timestamp_to_string;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+const LOG_PANE_COLOR: Color = Color::Cyan;
+
+/// What the user did with the log pane after a key press.
+#[derive(Debug, PartialEq)]
+pub enum LogPaneResult {
+    /// The pane stays open.
+    Pending,
+    /// The user pressed `L`, `q` or escape.
+    Close,
+}
+
+/// A pane that lists every message of the session with the newest at the bottom.
+pub struct LogPane {
+    /// Only messages at least this severe are shown.
+    min_severity: Severity,
+    /// The number of newer messages below the bottom of the pane.
+    scroll: usize,
+}
+
+impl LogPane {
+    pub fn new() -> Self {
+        LogPane {
+            min_severity: Severity::Info,
+            scroll: 0,
+        }
+    }
+
+    /// Scroll or filter the messages of `user_message` with a key press.
+    pub fn on_event(&mut self, key: KeyEvent, user_message: &UserMessage) -> LogPaneResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('L'), _)
+            | (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Esc, _) => return LogPaneResult::Close,
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => {
+                self.scroll(1, user_message)
+            }
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => {
+                self.scroll(-1, user_message)
+            }
+            (KeyCode::Char('g'), KeyModifiers::NONE) | (KeyCode::Home, _) => {
+                self.scroll = self.num_entries(user_message).saturating_sub(1);
+            }
+            (KeyCode::Char('G'), _) | (KeyCode::End, _) => self.scroll = 0,
+            (KeyCode::Char('f'), KeyModifiers::NONE) => {
+                self.min_severity = match self.min_severity {
+                    Severity::Info => Severity::Warning,
+                    Severity::Warning => Severity::Error,
+                    Severity::Error => Severity::Info,
+                };
+                self.scroll = 0;
+            }
+            _ => {}
+        }
+        LogPaneResult::Pending
+    }
+
+    /// Scroll `lines` messages down, or up if negative.
+    pub fn scroll(&mut self, lines: isize, user_message: &UserMessage) {
+        let max_scroll = self.num_entries(user_message).saturating_sub(1);
+        self.scroll = (self.scroll as isize - lines).clamp(0, max_scroll as isize) as usize;
+    }
+
+    fn num_entries(&self, user_message: &UserMessage) -> usize {
+        user_message.get_log(self.min_severity).len()
+    }
+
+    /// Return a line that shows the time, severity and source of `entry`.
+    fn entry_to_string(entry: &LogEntry) -> String {
+        // Only the time of day is shown, the log file has the full date.
+        let timestamp = timestamp_to_string(entry.time);
+        let mut line = format!("{} {:7}", &timestamp[11..19], entry.severity.name());
+        if let Some(source) = &entry.source {
+            line.push_str(&format!(" [{}]", source));
+        }
+        line.push(' ');
+        line.push_str(&entry.message);
+        line
+    }
+
+    /// Draw the messages of `user_message` in `rect`.
+    pub fn draw<B>(
+        &self,
+        frame: &mut tui::terminal::Frame<B>,
+        rect: Rect,
+        user_message: &UserMessage,
+    ) where
+        B: tui::backend::Backend,
+    {
+        let entries = user_message.get_log(self.min_severity);
+        let end = entries.len() - self.scroll.min(entries.len());
+        let filter = match self.min_severity {
+            Severity::Info => "all messages",
+            Severity::Warning => "warnings and errors",
+            Severity::Error => "errors",
+        };
+        let title = format!(
+            "Log of {} ({}/{})  f: filter  j/k: scroll  L: close",
+            filter,
+            end,
+            entries.len()
+        );
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(LOG_PANE_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+
+        // Fill the pane from the bottom with the entries above the scroll position.
+        let mut lines = vec![];
+        for entry in entries[..end].iter().rev() {
+            let style = entry.severity.style();
+            let wrapped: Vec<_> =
+                textwrap::wrap_iter(&Self::entry_to_string(entry), inner.width.max(1) as usize)
+                    .map(|line| Spans::from(Span::styled(line.to_string(), style)))
+                    .collect();
+            lines.splice(0..0, wrapped);
+            if lines.len() >= inner.height as usize {
+                break;
+            }
+        }
+        let skip = lines.len().saturating_sub(inner.height as usize);
+        frame.render_widget(Paragraph::new(lines.split_off(skip)), inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_log_pane_events() {
+        let user_message = UserMessage::new();
+        user_message.report("a");
+        user_message.warn("b");
+        user_message.error("c");
+
+        let mut pane = LogPane::new();
+        pane.on_event(key(KeyCode::Char('k')), &user_message);
+        pane.on_event(key(KeyCode::Up), &user_message);
+        pane.on_event(key(KeyCode::Up), &user_message);
+        assert_eq!(pane.scroll, 2);
+        pane.on_event(key(KeyCode::Char('j')), &user_message);
+        assert_eq!(pane.scroll, 1);
+
+        assert_eq!(
+            pane.on_event(key(KeyCode::Char('f')), &user_message),
+            LogPaneResult::Pending
+        );
+        assert_eq!(pane.min_severity, Severity::Warning);
+        assert_eq!(pane.scroll, 0);
+        pane.on_event(key(KeyCode::Char('g')), &user_message);
+        assert_eq!(pane.scroll, 1);
+
+        assert_eq!(
+            pane.on_event(key(KeyCode::Esc), &user_message),
+            LogPaneResult::Close
+        );
+    }
+}
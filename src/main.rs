@@ -3,31 +3,49 @@ extern crate crossbeam_channel;
 #[macro_use]
 extern crate clap;
 
-mod connect;
+mod app;
+mod cli;
+mod command_pane;
+mod dialog;
+mod diff_pane;
+mod edit;
 mod events;
-mod file;
-mod progress;
-mod rftp;
+mod log_pane;
+mod preview;
+mod prompt;
+mod properties;
+mod report;
+mod shell;
+#[cfg(test)]
+mod testing;
 mod user_message;
-mod utils;
 
+use app::Rftp;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use events::{Event, EventListener};
-use rftp::Rftp;
+use rftp::config::Config;
+use rftp::Result;
 use std::io::{stdout, Stdout};
 use tui::{backend::CrosstermBackend, Terminal};
-use utils::Result;
 
 fn main() {
-    run_app().unwrap_or_else(|err| {
+    let matches = cli::app().get_matches();
+    let result = Config::load().and_then(|config| match matches.subcommand() {
+        ("get", Some(matches)) => cli::run_get(matches, &config),
+        ("put", Some(matches)) => cli::run_put(matches, &config),
+        ("sync", Some(matches)) => cli::run_sync(matches, &config),
+        _ => run_app(&matches, &config),
+    });
+    result.unwrap_or_else(|err| {
         eprintln!("Error: {}.", err);
-        std::process::exit(1);
+        std::process::exit(err.exit_code());
     });
 }
 
 /// Run the app.
-fn run_app() -> Result<()> {
-    let app = App::new()?;
+fn run_app(matches: &clap::ArgMatches, config: &Config) -> Result<()> {
+    let app = App::new(matches, config)?;
     app.run()?;
     Ok(())
 }
@@ -39,8 +57,8 @@ struct App {
 
 impl App {
     /// Create the `Rftp` and `Terminal` structs.
-    pub fn new() -> Result<App> {
-        let rftp = Rftp::new()?;
+    pub fn new(matches: &clap::ArgMatches, config: &Config) -> Result<App> {
+        let rftp = Rftp::new(matches, config)?;
         let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
         Ok(App { terminal, rftp })
     }
@@ -50,10 +68,7 @@ impl App {
         let rftp = &mut self.rftp;
         let terminal = &mut self.terminal;
 
-        crossterm::terminal::enable_raw_mode()?;
-        crossterm::execute!(terminal.backend_mut(), EnterAlternateScreen)?;
-
-        terminal.hide_cursor()?;
+        enter_terminal(terminal)?;
 
         let mut event_listener = EventListener::new(30.0);
 
@@ -62,22 +77,62 @@ impl App {
                 Ok(Event::Input(key)) => {
                     rftp.on_event(key)?;
                 }
+                Ok(Event::Mouse(event)) => {
+                    rftp.on_mouse(event)?;
+                }
+                Ok(Event::Resize(width, height)) => {
+                    // Clear and redraw right away instead of on the next tick.
+                    terminal.resize(tui::layout::Rect::new(0, 0, width, height))?;
+                    terminal.draw(|frame| rftp.draw(frame))?;
+                }
                 Ok(Event::Tick) => {
                     rftp.tick()?;
                     terminal.draw(|frame| rftp.draw(frame))?;
                 }
                 Err(_) => (),
             }
+
+            if let Some(task) = rftp.take_suspended_task() {
+                // The task gets the terminal and every key press until it is done.
+                event_listener.pause();
+                leave_terminal(terminal)?;
+                rftp.run_suspended_task(task);
+                enter_terminal(terminal)?;
+                terminal.clear()?;
+                event_listener.resume();
+            }
         }
 
         Ok(())
     }
 }
 
+/// Switch the terminal to the raw alternate screen that the interface is drawn on.
+fn enter_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.hide_cursor()?;
+    Ok(())
+}
+
+/// Restore the terminal to how it was before `enter_terminal`.
+fn leave_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    crossterm::execute!(
+        terminal.backend_mut(),
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    )?;
+    crossterm::terminal::disable_raw_mode()?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
 impl Drop for App {
     fn drop(&mut self) {
-        crossterm::execute!(self.terminal.backend_mut(), LeaveAlternateScreen).unwrap();
-        crossterm::terminal::disable_raw_mode().unwrap();
-        self.terminal.show_cursor().unwrap();
+        leave_terminal(&mut self.terminal).unwrap();
     }
 }
@@ -0,0 +1,530 @@
+//! An in-memory file system that can inject faults, for testing without a server.
+
+use crate::file::{FileEntry, LocalFileEntry, RemoteFileEntry};
+use crate::filesystem::{FileSystem, Properties};
+use crate::utils::{ErrorKind, Result};
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// An entry that a `MemoryFileSystem` can list.
+pub trait MemoryEntry: FileEntry + Clone {
+    fn file(path: PathBuf, len: u64, modified: Option<u64>) -> Self;
+    fn directory(path: PathBuf) -> Self;
+    fn symlink(path: PathBuf) -> Self;
+}
+
+impl MemoryEntry for LocalFileEntry {
+    fn file(path: PathBuf, len: u64, modified: Option<u64>) -> Self {
+        LocalFileEntry::File(path, len, modified)
+    }
+
+    fn directory(path: PathBuf) -> Self {
+        LocalFileEntry::Directory(path)
+    }
+
+    fn symlink(path: PathBuf) -> Self {
+        LocalFileEntry::Symlink(path)
+    }
+}
+
+impl MemoryEntry for RemoteFileEntry {
+    fn file(path: PathBuf, len: u64, modified: Option<u64>) -> Self {
+        RemoteFileEntry::File(path, len, modified)
+    }
+
+    fn directory(path: PathBuf) -> Self {
+        RemoteFileEntry::Directory(path)
+    }
+
+    fn symlink(path: PathBuf) -> Self {
+        RemoteFileEntry::Symlink(path)
+    }
+}
+
+/// Something that goes wrong when an entry is accessed.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Every access to the entry fails because the user is not allowed to.
+    PermissionDenied,
+    /// The connection drops for good after `after` bytes of the file have been read. Every
+    /// access after that fails.
+    Disconnect { after: usize },
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// The contents and modification time of a file.
+    File(Vec<u8>, Option<u64>),
+    Directory,
+    Symlink(PathBuf),
+}
+
+type Nodes = Mutex<BTreeMap<PathBuf, Node>>;
+
+/// The permission bits, uid and gid of an entry whose mode or owner was changed.
+type Attributes = Mutex<BTreeMap<PathBuf, (u32, u32, u32)>>;
+
+/// The uid and gid that own every entry until it is changed.
+const DEFAULT_ID: u32 = 1000;
+
+/// A file system that lives in memory and lists entries of type `E`.
+///
+/// Paths must be absolute. The root directory `/` always exists.
+pub struct MemoryFileSystem<E> {
+    is_remote: bool,
+    nodes: Arc<Nodes>,
+    attributes: Attributes,
+    faults: Mutex<BTreeMap<PathBuf, Fault>>,
+    max_read: Mutex<Option<usize>>,
+    /// The number of bytes after which the next file that is read loses the connection.
+    lose_connection_after: Mutex<Option<usize>>,
+    is_disconnected: Arc<AtomicBool>,
+    entry: PhantomData<fn() -> E>,
+}
+
+impl MemoryFileSystem<LocalFileEntry> {
+    /// Create an empty file system that pretends to be on this machine.
+    pub fn local() -> Self {
+        Self::new(false, &[])
+    }
+}
+
+impl MemoryFileSystem<RemoteFileEntry> {
+    /// Create an empty file system that pretends to be on another host.
+    pub fn remote() -> Self {
+        Self::new(true, &[])
+    }
+}
+
+impl<E> MemoryFileSystem<E> {
+    /// Create a file system with the files `(path, contents)` and their parent directories.
+    ///
+    /// Paths that end with `/` are directories.
+    pub fn new(is_remote: bool, files: &[(&str, &str)]) -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::Directory);
+        let fs = MemoryFileSystem {
+            is_remote,
+            nodes: Arc::new(Mutex::new(nodes)),
+            attributes: Mutex::new(BTreeMap::new()),
+            faults: Mutex::new(BTreeMap::new()),
+            max_read: Mutex::new(None),
+            lose_connection_after: Mutex::new(None),
+            is_disconnected: Arc::new(AtomicBool::new(false)),
+            entry: PhantomData,
+        };
+        for (path, contents) in files {
+            if path.ends_with('/') {
+                fs.add_dir(path);
+            } else {
+                fs.add_file(path, contents);
+            }
+        }
+        fs
+    }
+
+    /// Add the file `path` with `contents`, creating its parent directories.
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: &str) -> &Self {
+        self.add_node(
+            path.as_ref(),
+            Node::File(contents.as_bytes().to_vec(), None),
+        )
+    }
+
+    /// Add the directory `path`, creating its parent directories.
+    pub fn add_dir(&self, path: impl AsRef<Path>) -> &Self {
+        self.add_node(path.as_ref(), Node::Directory)
+    }
+
+    /// Add a symlink at `path` that points to `target`, creating its parent directories.
+    pub fn add_symlink(&self, path: impl AsRef<Path>, target: impl AsRef<Path>) -> &Self {
+        self.add_node(path.as_ref(), Node::Symlink(target.as_ref().to_path_buf()))
+    }
+
+    /// Create or replace the file `path` with `contents`.
+    pub fn write(&self, path: impl AsRef<Path>, contents: &str) -> &Self {
+        self.add_file(path, contents)
+    }
+
+    /// Append `contents` to the file `path`.
+    pub fn append(&self, path: impl AsRef<Path>, contents: &str) {
+        let path = path.as_ref();
+        match self.nodes.lock().unwrap().get_mut(path) {
+            Some(Node::File(file, _)) => file.extend_from_slice(contents.as_bytes()),
+            _ => panic!("{} is not a file", path.display()),
+        }
+    }
+
+    /// Make every access to `path` fail with `fault`.
+    pub fn inject(&self, path: impl AsRef<Path>, fault: Fault) -> &Self {
+        self.faults
+            .lock()
+            .unwrap()
+            .insert(path.as_ref().to_path_buf(), fault);
+        self
+    }
+
+    /// Return at most `max_read` bytes from each read, like a slow connection.
+    pub fn short_reads(&self, max_read: usize) -> &Self {
+        *self.max_read.lock().unwrap() = Some(max_read);
+        self
+    }
+
+    /// Make the next file that is read fail after `bytes` bytes as if the connection was lost,
+    /// after which it is restored.
+    pub fn lose_connection_after(&self, bytes: usize) -> &Self {
+        *self.lose_connection_after.lock().unwrap() = Some(bytes);
+        self
+    }
+
+    /// Return the contents of the file `path`, or `None` if it is not a file.
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<String> {
+        match self.nodes.lock().unwrap().get(path.as_ref()) {
+            Some(Node::File(contents, _)) => Some(String::from_utf8_lossy(contents).to_string()),
+            _ => None,
+        }
+    }
+
+    /// Return the paths of every entry below `/` in sorted order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let nodes = self.nodes.lock().unwrap();
+        nodes
+            .keys()
+            .filter(|p| *p != Path::new("/"))
+            .cloned()
+            .collect()
+    }
+
+    fn add_node(&self, path: &Path, node: Node) -> &Self {
+        let mut nodes = self.nodes.lock().unwrap();
+        for ancestor in path.ancestors().skip(1) {
+            nodes
+                .entry(ancestor.to_path_buf())
+                .or_insert(Node::Directory);
+        }
+        nodes.insert(path.to_path_buf(), node);
+        self
+    }
+
+    /// Fail if the connection has dropped or a fault was injected at `path`.
+    fn check(&self, path: &Path) -> io::Result<()> {
+        if self.is_disconnected.load(Ordering::SeqCst) {
+            return Err(disconnected());
+        }
+        match self.faults.lock().unwrap().get(path) {
+            Some(Fault::PermissionDenied) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("permission denied: {}", path.display()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn parent_is_dir(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> io::Result<()> {
+        match path.parent().and_then(|parent| nodes.get(parent)) {
+            Some(Node::Directory) => Ok(()),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    /// Change the permission bits, uid and gid of the existing entry at `path` with `f`.
+    fn change_attributes<F>(&self, path: &Path, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut (u32, u32, u32)),
+    {
+        self.check(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        let node = nodes.get(path).ok_or_else(|| not_found(path))?;
+        let default_mode = match node {
+            Node::Directory => 0o755,
+            _ => 0o644,
+        };
+        let mut attributes = self.attributes.lock().unwrap();
+        f(attributes
+            .entry(path.to_path_buf())
+            .or_insert((default_mode, DEFAULT_ID, DEFAULT_ID)));
+        Ok(())
+    }
+}
+
+impl<E: MemoryEntry> MemoryFileSystem<E> {
+    fn entry(path: &Path, node: &Node) -> E {
+        match node {
+            Node::File(contents, modified) => {
+                E::file(path.to_path_buf(), contents.len() as u64, *modified)
+            }
+            Node::Directory => E::directory(path.to_path_buf()),
+            Node::Symlink(_) => E::symlink(path.to_path_buf()),
+        }
+    }
+}
+
+impl<E: MemoryEntry> FileSystem for MemoryFileSystem<E> {
+    type Entry = E;
+
+    fn is_remote(&self) -> bool {
+        self.is_remote
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<E>> {
+        self.check(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(path) {
+            Some(Node::Directory) => Ok(nodes
+                .iter()
+                .filter(|(child, _)| child.parent() == Some(path))
+                .map(|(child, node)| Self::entry(child, node))
+                .collect()),
+            _ => Err(not_found(path).into()),
+        }
+    }
+
+    fn stat(&self, path: &Path) -> Result<Option<E>> {
+        self.check(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        let node = match nodes.get(path) {
+            Some(Node::Symlink(target)) => nodes.get(target),
+            node => node,
+        };
+        Ok(node.map(|node| Self::entry(path, node)))
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        self.check(path)?;
+        let mut canonical = PathBuf::from("/");
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    canonical.pop();
+                }
+                Component::Normal(name) => canonical.push(name),
+                _ => {}
+            }
+        }
+        match self.nodes.lock().unwrap().get(&canonical) {
+            Some(Node::Symlink(target)) => Ok(target.clone()),
+            Some(_) => Ok(canonical),
+            None => Err(not_found(path).into()),
+        }
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + '_>> {
+        self.check(path)?;
+        let contents = match self.nodes.lock().unwrap().get(path) {
+            Some(Node::File(contents, _)) => contents.clone(),
+            _ => return Err(not_found(path).into()),
+        };
+        let disconnect_after = match self.faults.lock().unwrap().get(path) {
+            Some(Fault::Disconnect { after }) => Some(*after),
+            _ => None,
+        };
+        Ok(Box::new(MemoryReader {
+            contents,
+            position: 0,
+            max_read: *self.max_read.lock().unwrap(),
+            disconnect_after,
+            lose_connection_after: self.lose_connection_after.lock().unwrap().take(),
+            is_disconnected: Arc::clone(&self.is_disconnected),
+        }))
+    }
+
+    fn open_write(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.check(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        Self::parent_is_dir(&nodes, path)?;
+        if let Some(Node::Directory) = nodes.get(path) {
+            return Err(io::Error::other("is a directory").into());
+        }
+        nodes.insert(path.to_path_buf(), Node::File(vec![], None));
+        Ok(Box::new(MemoryWriter {
+            fs: self,
+            path: path.to_path_buf(),
+        }))
+    }
+
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write + '_>> {
+        self.check(path)?;
+        match self.nodes.lock().unwrap().get(path) {
+            Some(Node::File(_, _)) => Ok(Box::new(MemoryWriter {
+                fs: self,
+                path: path.to_path_buf(),
+            })),
+            _ => Err(not_found(path).into()),
+        }
+    }
+
+    /// Only a remote file system has a connection, which is restored unless it was lost for
+    /// good by `Fault::Disconnect`.
+    fn is_connection_lost(&self, error: &ErrorKind) -> bool {
+        self.is_remote && error.is_disconnect() && !self.is_disconnected.load(Ordering::SeqCst)
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<()> {
+        self.check(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        Self::parent_is_dir(&nodes, path)?;
+        if nodes.contains_key(path) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists").into());
+        }
+        nodes.insert(path.to_path_buf(), Node::Directory);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check(from)?;
+        self.check(to)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        // Everything below a directory moves with it.
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return Err(not_found(from).into());
+        }
+        for path in moved {
+            let node = nodes.remove(&path).unwrap();
+            nodes.insert(to.join(path.strip_prefix(from).unwrap()), node);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        self.check(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes.keys().any(|child| child.parent() == Some(path)) {
+            return Err(io::Error::other("directory not empty").into());
+        }
+        match nodes.remove(path) {
+            Some(_) => Ok(()),
+            None => Err(not_found(path).into()),
+        }
+    }
+
+    fn set_modified(&self, path: &Path, modified: u64) -> Result<()> {
+        self.check(path)?;
+        match self.nodes.lock().unwrap().get_mut(path) {
+            Some(Node::File(_, old)) => {
+                *old = Some(modified);
+                Ok(())
+            }
+            _ => Err(not_found(path).into()),
+        }
+    }
+
+    fn properties(&self, path: &Path) -> Result<Properties> {
+        self.check(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        let node = nodes.get(path).ok_or_else(|| not_found(path))?;
+        let (kind, default_mode) = match node {
+            Node::File(_, _) => (0o100000, 0o644),
+            Node::Directory => (0o040000, 0o755),
+            Node::Symlink(_) => (0o120000, 0o777),
+        };
+        let (mode, uid, gid) = self
+            .attributes
+            .lock()
+            .unwrap()
+            .get(path)
+            .copied()
+            .unwrap_or((default_mode, DEFAULT_ID, DEFAULT_ID));
+        Ok(Properties {
+            size: match node {
+                Node::File(contents, _) => Some(contents.len() as u64),
+                _ => None,
+            },
+            mode: Some(kind | mode),
+            uid: Some(uid),
+            gid: Some(gid),
+            ..Properties::default()
+        })
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.change_attributes(path, |attributes| attributes.0 = mode & 0o7777)
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.change_attributes(path, |attributes| {
+            attributes.1 = uid.unwrap_or(attributes.1);
+            attributes.2 = gid.unwrap_or(attributes.2);
+        })
+    }
+}
+
+struct MemoryReader {
+    contents: Vec<u8>,
+    position: usize,
+    max_read: Option<usize>,
+    disconnect_after: Option<usize>,
+    lose_connection_after: Option<usize>,
+    is_disconnected: Arc<AtomicBool>,
+}
+
+impl Read for MemoryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.is_disconnected.load(Ordering::SeqCst) {
+            return Err(disconnected());
+        }
+        let mut end = self.contents.len().min(self.position + buf.len());
+        if let Some(max_read) = self.max_read {
+            end = end.min(self.position + max_read);
+        }
+        if let Some(after) = self.disconnect_after {
+            if self.position >= after {
+                self.is_disconnected.store(true, Ordering::SeqCst);
+                return Err(disconnected());
+            }
+            end = end.min(after);
+        }
+        if let Some(after) = self.lose_connection_after {
+            if self.position >= after {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            end = end.min(after);
+        }
+        let bytes_read = end - self.position;
+        buf[..bytes_read].copy_from_slice(&self.contents[self.position..end]);
+        self.position = end;
+        Ok(bytes_read)
+    }
+}
+
+struct MemoryWriter<'a, E> {
+    fs: &'a MemoryFileSystem<E>,
+    path: PathBuf,
+}
+
+impl<E> Write for MemoryWriter<'_, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fs.check(&self.path)?;
+        match self.fs.nodes.lock().unwrap().get_mut(&self.path) {
+            Some(Node::File(contents, _)) => {
+                contents.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            _ => Err(not_found(&self.path)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.fs.check(&self.path)
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no such file or directory: {}", path.display()),
+    )
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "connection lost")
+}
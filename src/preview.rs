@@ -0,0 +1,404 @@
+use rftp::file::FileEntry;
+use rftp::filesystem::FileSystem;
+use rftp::utils::{bytes_to_string, Result};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const PREVIEW_COLOR: Color = Color::Green;
+/// The max number of bytes of a file that are loaded into a preview.
+const PREVIEW_SIZE: u64 = 64 * 1024;
+/// How often the size of a followed file is checked.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+/// The number of bytes on each line of a hexdump.
+const HEXDUMP_WIDTH: usize = 16;
+
+/// What the user did with the preview after a key press.
+#[derive(Debug, PartialEq)]
+pub enum PreviewResult {
+    /// The preview stays open.
+    Pending,
+    /// The user pressed `v`, `q` or escape.
+    Close,
+}
+
+/// The part of a file that is loaded into a preview.
+#[derive(Default)]
+struct Window {
+    /// Where `bytes` start in the file.
+    offset: u64,
+    bytes: Vec<u8>,
+    /// The size of the whole file when it was last checked.
+    file_len: u64,
+    is_loaded: bool,
+    error: Option<String>,
+}
+
+impl Window {
+    /// Load the part of the file `path` of `fs` that should be shown when it is `file_len`
+    /// bytes long.
+    ///
+    /// Growing files are appended to, and only the last `PREVIEW_SIZE` bytes are kept.
+    fn update<F>(window: &Mutex<Window>, fs: &F, path: &Path, file_len: u64) -> Result<()>
+    where
+        F: FileSystem + ?Sized,
+    {
+        let end = {
+            let window = window.lock().unwrap();
+            window.offset + window.bytes.len() as u64
+        };
+        if file_len == end {
+            return Ok(());
+        }
+        if file_len < end || file_len - end > PREVIEW_SIZE {
+            // The file was truncated or grew too much to keep what is loaded.
+            let start = file_len.saturating_sub(PREVIEW_SIZE);
+            let bytes = fs.read_range(path, start, file_len - start)?;
+            let mut window = window.lock().unwrap();
+            window.offset = start;
+            window.bytes = bytes;
+        } else {
+            let bytes = fs.read_range(path, end, file_len - end)?;
+            let mut window = window.lock().unwrap();
+            window.bytes.extend_from_slice(&bytes);
+            let excess = window.bytes.len().saturating_sub(PREVIEW_SIZE as usize);
+            if excess > 0 {
+                window.bytes.drain(..excess);
+                window.offset += excess as u64;
+            }
+        }
+        window.lock().unwrap().file_len = file_len;
+        Ok(())
+    }
+}
+
+/// A pane that shows the start of a file, or its end while it is followed.
+pub struct Preview {
+    title: String,
+    window: Arc<Mutex<Window>>,
+    is_following: Arc<AtomicBool>,
+    is_closed: Arc<AtomicBool>,
+    /// Whether to show a hexdump, or `None` to decide from the contents.
+    is_hex: Option<bool>,
+    /// The number of lines above the top of the pane.
+    scroll: usize,
+    /// The most lines that could be scrolled when the preview was last drawn.
+    max_scroll: Cell<usize>,
+}
+
+impl Preview {
+    /// Start loading the file `entry` of `fs` in the background.
+    pub fn open<F>(fs: Arc<F>, entry: &F::Entry) -> Self
+    where
+        F: FileSystem + Send + Sync + ?Sized + 'static,
+    {
+        let path = entry.path().to_path_buf();
+        let preview = Preview {
+            title: format!("Preview {:?}", path),
+            window: Arc::new(Mutex::new(Window::default())),
+            is_following: Arc::new(AtomicBool::new(false)),
+            is_closed: Arc::new(AtomicBool::new(false)),
+            is_hex: None,
+            scroll: 0,
+            max_scroll: Cell::new(0),
+        };
+
+        let window = Arc::clone(&preview.window);
+        let is_following = Arc::clone(&preview.is_following);
+        let is_closed = Arc::clone(&preview.is_closed);
+        thread::spawn(move || {
+            if let Err(error) = Self::load(&*fs, &path, &window, &is_following, &is_closed) {
+                window.lock().unwrap().error = Some(error.to_string());
+            }
+        });
+        preview
+    }
+
+    /// Load the start of the file `path`, then poll its size while it is followed until the
+    /// preview is closed.
+    fn load<F>(
+        fs: &F,
+        path: &Path,
+        window: &Mutex<Window>,
+        is_following: &AtomicBool,
+        is_closed: &AtomicBool,
+    ) -> Result<()>
+    where
+        F: FileSystem + ?Sized,
+    {
+        let file_len = Self::file_len(fs, path)?;
+        let bytes = fs.read_range(path, 0, PREVIEW_SIZE)?;
+        *window.lock().unwrap() = Window {
+            offset: 0,
+            bytes,
+            file_len,
+            is_loaded: true,
+            error: None,
+        };
+        while !is_closed.load(Ordering::Relaxed) {
+            thread::sleep(FOLLOW_INTERVAL);
+            if is_following.load(Ordering::Relaxed) {
+                Window::update(window, fs, path, Self::file_len(fs, path)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn file_len<F>(fs: &F, path: &Path) -> Result<u64>
+    where
+        F: FileSystem + ?Sized,
+    {
+        Ok(fs.stat(path)?.and_then(|entry| entry.len()).unwrap_or(0))
+    }
+
+    /// Scroll, follow or change the view with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> PreviewResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('v'), KeyModifiers::NONE)
+            | (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Esc, _) => return PreviewResult::Close,
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => self.scroll(1),
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => self.scroll(-1),
+            (KeyCode::Char(' '), KeyModifiers::NONE) | (KeyCode::PageDown, _) => self.scroll(20),
+            (KeyCode::Char('b'), KeyModifiers::NONE) | (KeyCode::PageUp, _) => self.scroll(-20),
+            (KeyCode::Char('g'), KeyModifiers::NONE) | (KeyCode::Home, _) => {
+                self.is_following.store(false, Ordering::Relaxed);
+                self.scroll = 0;
+            }
+            (KeyCode::Char('G'), _) | (KeyCode::End, _) => self.scroll = usize::MAX,
+            (KeyCode::Char('x'), KeyModifiers::NONE) => {
+                self.is_hex = Some(!self.is_hex());
+                self.scroll = 0;
+            }
+            (KeyCode::Char('F'), _) => {
+                let is_following = !self.is_following.load(Ordering::Relaxed);
+                self.is_following.store(is_following, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        PreviewResult::Pending
+    }
+
+    /// Scroll `lines` lines down, or up if negative.
+    ///
+    /// Scrolling up stops following the file.
+    pub fn scroll(&mut self, lines: isize) {
+        let max_scroll = self.max_scroll.get();
+        let scroll = if self.is_following.load(Ordering::Relaxed) {
+            max_scroll
+        } else {
+            self.scroll.min(max_scroll)
+        };
+        if lines < 0 {
+            self.is_following.store(false, Ordering::Relaxed);
+        }
+        self.scroll = scroll.saturating_add_signed(lines).min(max_scroll);
+    }
+
+    /// Return true if the contents are shown as a hexdump.
+    fn is_hex(&self) -> bool {
+        self.is_hex
+            .unwrap_or_else(|| is_binary(&self.window.lock().unwrap().bytes))
+    }
+
+    /// Return the lines that show the loaded part of the file.
+    fn get_lines(&self) -> Vec<String> {
+        let is_hex = self.is_hex();
+        let window = self.window.lock().unwrap();
+        if is_hex {
+            hexdump(window.offset, &window.bytes)
+        } else {
+            String::from_utf8_lossy(&window.bytes)
+                .lines()
+                .map(|line| line.replace('\t', "    "))
+                .collect()
+        }
+    }
+
+    /// Draw this preview on top of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let is_following = self.is_following.load(Ordering::Relaxed);
+        let is_hex = self.is_hex();
+        let (status, lines) = {
+            let window = self.window.lock().unwrap();
+            match (&window.error, window.is_loaded) {
+                (Some(error), _) => (format!("Error: {}.", error), vec![]),
+                (None, false) => ("Loading...".to_string(), vec![]),
+                (None, true) => {
+                    let end = window.offset + window.bytes.len() as u64;
+                    let status = format!(
+                        "{}-{} of {}",
+                        bytes_to_string(window.offset),
+                        bytes_to_string(end),
+                        bytes_to_string(window.file_len),
+                    );
+                    drop(window);
+                    (status, self.get_lines())
+                }
+            }
+        };
+
+        let mut title = format!("{}  {}", self.title, status);
+        if is_hex {
+            title.push_str("  [hex]");
+        }
+        if is_following {
+            title.push_str("  [follow]");
+        }
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(PREVIEW_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(Clear, rect);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(inner);
+
+        // A followed file sticks to its end like `tail -f`.
+        let max_scroll = lines.len().saturating_sub(chunks[0].height as usize);
+        self.max_scroll.set(max_scroll);
+        let scroll = if is_following {
+            max_scroll
+        } else {
+            self.scroll.min(max_scroll)
+        };
+        let lines: Vec<Spans> = lines
+            .into_iter()
+            .skip(scroll)
+            .take(chunks[0].height as usize)
+            .map(Spans::from)
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let footer = Span::styled(
+            "j/k: scroll  x: hexdump  F: follow  q: close",
+            Style::default().add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(Paragraph::new(Spans::from(footer)), chunks[1]);
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        // Stop the thread that loads the file.
+        self.is_closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Return true if `bytes` do not look like text.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+        || match std::str::from_utf8(bytes) {
+            Ok(_) => false,
+            // A character may be cut off at the end of the loaded bytes.
+            Err(error) => error.error_len().is_some(),
+        }
+}
+
+/// Return the lines of a hexdump of `bytes` that start at `offset` in a file, like
+/// `hexdump -C`.
+fn hexdump(offset: u64, bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(HEXDUMP_WIDTH)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<_> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let (left, right) = hex.split_at(hex.len().min(HEXDUMP_WIDTH / 2));
+            let text: String = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:23}  {:23}  |{}|",
+                offset + (i * HEXDUMP_WIDTH) as u64,
+                left.join(" "),
+                right.join(" "),
+                text
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::file::LocalFileEntry;
+    use rftp::memory::MemoryFileSystem;
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"hello\nworld\n"));
+        assert!(!is_binary("caf\u{e9}".as_bytes()));
+        // The last character is cut off.
+        assert!(!is_binary(&"caf\u{e9}".as_bytes()[..4]));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+        assert!(is_binary(b"\xff\xfe"));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(
+            hexdump(0x20, b"Hello, world!\n\x00\x01\xffend"),
+            [
+                "00000020  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|",
+                "00000030  ff 65 6e 64                                       |.end|",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_follow() {
+        let fs = MemoryFileSystem::<LocalFileEntry>::new(false, &[("/log", "one\n")]);
+        let path = Path::new("/log");
+        let window = Mutex::new(Window::default());
+        Window::update(&window, &fs, path, 4).unwrap();
+        assert_eq!(window.lock().unwrap().bytes, b"one\n");
+
+        // Only the new bytes are read when the file grows.
+        fs.append("/log", "two\n");
+        Window::update(&window, &fs, path, 8).unwrap();
+        assert_eq!(window.lock().unwrap().bytes, b"one\ntwo\n");
+
+        // At most `PREVIEW_SIZE` bytes of the end are kept.
+        let line = "x".repeat(999) + "\n";
+        fs.append("/log", &line.repeat(100));
+        Window::update(&window, &fs, path, 100_008).unwrap();
+        {
+            let window = window.lock().unwrap();
+            assert_eq!(window.offset, 100_008 - PREVIEW_SIZE);
+            assert_eq!(window.bytes.len() as u64, PREVIEW_SIZE);
+            assert!(window.bytes.ends_with(b"x\n"));
+        }
+
+        // A truncated file is loaded again.
+        fs.write("/log", "new\n");
+        Window::update(&window, &fs, path, 4).unwrap();
+        let window = window.lock().unwrap();
+        assert_eq!((window.offset, window.bytes.as_slice()), (0, &b"new\n"[..]));
+    }
+}
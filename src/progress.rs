@@ -5,7 +5,7 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::Text,
     widgets::{Gauge, Paragraph},
@@ -15,6 +15,7 @@ use tui::{
 const HISTORY_MAX_AGE: Duration = Duration::from_secs(5);
 const PROGRESSBAR_COLOR: Color = Color::LightBlue;
 
+#[derive(Default)]
 pub struct ProgressBars {
     file_progress_bars: Vec<Arc<ProgressFile>>,
     directory_progress_bars: Vec<Arc<ProgressDirectory>>,
@@ -41,8 +42,27 @@ impl ProgressBars {
     }
 
     pub fn retain_incomplete(&mut self) {
-        self.file_progress_bars.retain(|p| !p.is_finished());
-        self.directory_progress_bars.retain(|p| !p.is_finished());
+        self.file_progress_bars
+            .retain(|p| !p.is_finished() && !p.is_cancelled());
+        self.directory_progress_bars
+            .retain(|p| !p.is_finished() && !p.is_cancelled());
+    }
+
+    /// Return the progress bar on `row` if these progress bars were drawn in `rect`.
+    pub fn get_at(&self, rect: Rect, row: u16) -> Option<ProgressBar> {
+        let len = self.directory_progress_bars.len() + self.file_progress_bars.len();
+        let top = rect.bottom().checked_sub(len as u16)?.max(rect.top());
+        if row < top || row >= rect.bottom() {
+            return None;
+        }
+        let index = (row - top) as usize;
+        match self.directory_progress_bars.get(index) {
+            Some(p) => Some(ProgressBar::Directory(Arc::clone(p))),
+            None => self
+                .file_progress_bars
+                .get(index - self.directory_progress_bars.len())
+                .map(|p| ProgressBar::File(Arc::clone(p))),
+        }
     }
 
     pub fn draw<B>(
@@ -71,16 +91,14 @@ impl ProgressBars {
 
             let directory_rects = Layout::default()
                 .constraints(
-                    std::iter::repeat(Constraint::Length(1))
-                        .take(self.directory_progress_bars.len())
+                    std::iter::repeat_n(Constraint::Length(1), self.directory_progress_bars.len())
                         .collect::<Vec<Constraint>>(),
                 )
                 .split(directory_rect);
 
             let file_rects = Layout::default()
                 .constraints(
-                    std::iter::repeat(Constraint::Length(1))
-                        .take(self.file_progress_bars.len())
+                    std::iter::repeat_n(Constraint::Length(1), self.file_progress_bars.len())
                         .collect::<Vec<Constraint>>(),
                 )
                 .split(file_rect);
@@ -98,11 +116,37 @@ impl ProgressBars {
     }
 }
 
+/// A single progress bar of either kind.
+#[derive(Clone)]
+pub enum ProgressBar {
+    Directory(Arc<ProgressDirectory>),
+    File(Arc<ProgressFile>),
+}
+
+impl ProgressBar {
+    /// Return the title of this progress bar.
+    pub fn get_title(&self) -> &str {
+        match self {
+            ProgressBar::Directory(p) => p.get_title(),
+            ProgressBar::File(p) => p.get_title(),
+        }
+    }
+
+    /// Ask the transfer behind this progress bar to stop.
+    pub fn cancel(&self) {
+        match self {
+            ProgressBar::Directory(p) => p.cancel(),
+            ProgressBar::File(p) => p.cancel(),
+        }
+    }
+}
+
 pub struct ProgressDirectory {
     title: String,
     bytes_sent: AtomicU64,
     files_sent: AtomicU64,
     is_finished: AtomicBool,
+    is_cancelled: AtomicBool,
 }
 
 impl ProgressDirectory {
@@ -112,6 +156,7 @@ impl ProgressDirectory {
             bytes_sent: AtomicU64::new(0),
             files_sent: AtomicU64::new(0),
             is_finished: AtomicBool::new(false),
+            is_cancelled: AtomicBool::new(false),
         }
     }
 
@@ -119,7 +164,17 @@ impl ProgressDirectory {
         self.is_finished.store(true, Ordering::Relaxed);
     }
 
-    fn get_title(&self) -> &str {
+    /// Ask the transfer of this directory to stop before its next chunk.
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Return `true` if this directory should stop transferring.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn get_title(&self) -> &str {
         self.title.as_str()
     }
 
@@ -172,19 +227,24 @@ pub struct ProgressFile {
     bytes_sent: AtomicU64,
     total_bytes: u64,
     is_finished: AtomicBool,
+    is_cancelled: AtomicBool,
     history: Mutex<VecDeque<(Instant, u64)>>,
+    start_time: Instant,
 }
 
 impl ProgressFile {
     /// Create a thread-safe progress bar for a file with size `total_bytes` and a `title`.
     pub fn new(title: &str, total_bytes: u64) -> Self {
-        let history = Mutex::new(vec![(Instant::now(), 0)].into_iter().collect());
+        let start_time = Instant::now();
+        let history = Mutex::new(vec![(start_time, 0)].into_iter().collect());
         ProgressFile {
             title: title.to_string(),
             bytes_sent: AtomicU64::new(0),
             total_bytes,
             is_finished: AtomicBool::new(false),
+            is_cancelled: AtomicBool::new(false),
             history,
+            start_time,
         }
     }
 
@@ -193,6 +253,21 @@ impl ProgressFile {
         self.title.as_str()
     }
 
+    /// Return the number of bytes that have been sent so far.
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Return the size of the file in bytes.
+    pub fn get_total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Return the time since this progress bar was created.
+    pub fn get_elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
     /// Return the fraction that this progress bar has completed.
     pub fn get_ratio(&self) -> f64 {
         if self.total_bytes == 0 {
@@ -233,6 +308,22 @@ impl ProgressFile {
         self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
     }
 
+    /// Count the bytes from `bytes` on again, when an interrupted transfer is resumed.
+    pub fn reset(&self, bytes: u64) {
+        self.history.lock().unwrap().clear();
+        self.bytes_sent.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Ask the transfer of this file to stop before its next chunk.
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Return `true` if this file should stop transferring.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+
     /// Finish this progress bar.
     pub fn finish(&self) {
         self.bytes_sent.store(self.total_bytes, Ordering::Relaxed);
@@ -285,23 +376,28 @@ impl ProgressFile {
         }
     }
 
-    /// Draw this progress bar.
-    fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: tui::layout::Rect)
-    where
-        B: tui::backend::Backend,
-    {
+    /// Return a summary of the bytes sent, the bitrate, and the ETA of this progress bar.
+    pub fn get_info(&self) -> String {
         let bitrate = self.get_current_bitrate();
         let eta = self
             .get_eta()
             .map(duration_to_string)
             .unwrap_or_else(|| "??:??".to_string());
-        let info = format!(
+        format!(
             "{}/{}  {}  {} ETA",
             bytes_to_string(self.bytes_sent.load(Ordering::Relaxed)),
             bytes_to_string(self.total_bytes),
             bitrate_to_string(bitrate),
             eta
-        );
+        )
+    }
+
+    /// Draw this progress bar.
+    fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: tui::layout::Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let info = self.get_info();
         let width = frame.size().width as usize;
         let label = if info.len() + 5 >= width {
             format!(
@@ -365,6 +461,7 @@ mod tests {
                         bytes_sent: AtomicU64::new(0),
                         total_bytes: 1e6 as u64,
                         is_finished: AtomicBool::new(false),
+                        is_cancelled: AtomicBool::new(false),
                         history: Mutex::new(
                             vec![
                                 (now, 0),
@@ -376,6 +473,7 @@ mod tests {
                             .into_iter()
                             .collect(),
                         ),
+                        start_time: now,
                     }
                 };
                 bars.push_file_progress(Arc::new(with_history));
@@ -395,4 +493,40 @@ mod tests {
         ]);
         assert_buffer_symbols_eq(terminal.backend().buffer(), &expected);
     }
+
+    #[test]
+    fn test_progress_at_row() {
+        let mut bars = ProgressBars::new();
+        bars.push_directory_progress(Arc::new(ProgressDirectory::new("/some/directory")));
+        bars.push_file_progress(Arc::new(ProgressFile::new("a.txt", 100)));
+        bars.push_file_progress(Arc::new(ProgressFile::new("b.txt", 100)));
+        let rect = Rect::new(0, 2, 60, 8);
+
+        let titles: Vec<_> = (0..12)
+            .map(|row| {
+                bars.get_at(rect, row)
+                    .map(|bar| bar.get_title().to_string())
+            })
+            .collect();
+        let expected: Vec<Option<String>> = vec![
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some("/some/directory".into()),
+            Some("a.txt".into()),
+            Some("b.txt".into()),
+            None,
+            None,
+        ];
+        assert_eq!(titles, expected);
+
+        bars.get_at(rect, 8).unwrap().cancel();
+        bars.retain_incomplete();
+        assert!(bars.get_at(rect, 7).is_none());
+        assert_eq!(bars.get_at(rect, 8).unwrap().get_title(), "/some/directory");
+    }
 }
@@ -0,0 +1,159 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph},
+};
+
+const PROMPT_COLOR: Color = Color::Yellow;
+
+/// What the user did with a prompt after a key press.
+#[derive(Debug, PartialEq)]
+pub enum PromptResult {
+    /// The user is still typing.
+    Pending,
+    /// The user pressed enter.
+    Submit(String),
+    /// The user pressed escape.
+    Cancel,
+}
+
+/// A single line of text that the user can edit.
+pub struct Prompt {
+    title: String,
+    input: Vec<char>,
+    cursor: usize,
+}
+
+impl Prompt {
+    /// Create a prompt with a `title` that starts with the text `initial`.
+    pub fn new(title: &str, initial: &str) -> Self {
+        let input: Vec<char> = initial.chars().collect();
+        Prompt {
+            title: title.to_string(),
+            cursor: input.len(),
+            input,
+        }
+    }
+
+    /// Return the current text of this prompt.
+    pub fn get_input(&self) -> String {
+        self.input.iter().collect()
+    }
+
+    /// Edit the text of this prompt with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> PromptResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Enter, _) => return PromptResult::Submit(self.get_input()),
+            (KeyCode::Esc, _) => return PromptResult::Cancel,
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                self.input.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            (KeyCode::Backspace, _) if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            (KeyCode::Delete, _) if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            (KeyCode::Left, _) => self.cursor = self.cursor.saturating_sub(1),
+            (KeyCode::Right, _) => self.cursor = (self.cursor + 1).min(self.input.len()),
+            (KeyCode::Home, _) => self.cursor = 0,
+            (KeyCode::End, _) => self.cursor = self.input.len(),
+            _ => {}
+        }
+        PromptResult::Pending
+    }
+
+    /// Draw this prompt at the bottom of `rect` and return the remaining space.
+    pub fn draw<B>(
+        &self,
+        frame: &mut tui::terminal::Frame<B>,
+        rect: tui::layout::Rect,
+    ) -> tui::layout::Rect
+    where
+        B: tui::backend::Backend,
+    {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+            .split(rect);
+        let (rect, prompt_rect) = (chunks[0], chunks[1]);
+
+        // Scroll the text so that the cursor is always visible.
+        let width = prompt_rect.width.saturating_sub(2) as usize;
+        let offset = (self.cursor + 1).saturating_sub(width);
+        let text: String = self.input.iter().skip(offset).take(width).collect();
+        let paragraph = Paragraph::new(text).block(
+            Block::default()
+                .title(self.title.as_str())
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(PROMPT_COLOR)),
+        );
+        frame.render_widget(paragraph, prompt_rect);
+        frame.set_cursor(
+            prompt_rect.x + 1 + (self.cursor - offset) as u16,
+            prompt_rect.y + 1,
+        );
+
+        rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::utils::assert_buffer_symbols_eq;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_prompt_editing() {
+        let mut prompt = Prompt::new("Exclude", "target/");
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Char(' '))),
+            PromptResult::Pending
+        );
+        for c in "*.log".chars() {
+            prompt.on_event(key(KeyCode::Char(c)));
+        }
+        prompt.on_event(key(KeyCode::Home));
+        prompt.on_event(key(KeyCode::Delete));
+        prompt.on_event(key(KeyCode::End));
+        prompt.on_event(key(KeyCode::Backspace));
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Enter)),
+            PromptResult::Submit("arget/ *.lo".to_string())
+        );
+        assert_eq!(prompt.on_event(key(KeyCode::Esc)), PromptResult::Cancel);
+    }
+
+    #[test]
+    fn test_draw_prompt() {
+        let mut terminal = Terminal::new(TestBackend::new(20, 4)).unwrap();
+        let prompt = Prompt::new("Exclude", "a_very_long_pattern/");
+
+        terminal
+            .draw(|frame| {
+                let rect = frame.size();
+                prompt.draw(frame, rect);
+            })
+            .unwrap();
+
+        let expected = Buffer::with_lines(vec![
+            "                    ",
+            "┌Exclude───────────┐",
+            "│ery_long_pattern/ │",
+            "└──────────────────┘",
+        ]);
+        assert_buffer_symbols_eq(terminal.backend().buffer(), &expected);
+    }
+}
@@ -0,0 +1,289 @@
+use rftp::filesystem::Properties;
+use rftp::utils::{bytes_to_string, mode_to_string, timestamp_to_string};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const PROPERTIES_COLOR: Color = Color::Cyan;
+
+/// What the user did with the properties pane after a key press.
+#[derive(Debug, PartialEq)]
+pub enum PropertiesResult {
+    /// The pane stays open.
+    Pending,
+    /// The user wants to change the mode, of everything below a directory if `recursive`.
+    ChangeMode { recursive: bool },
+    /// The user wants to change the owner and group.
+    ChangeOwner,
+    /// The user pressed `q`, `p` or escape.
+    Close,
+}
+
+/// A popup that shows the metadata of an entry.
+pub struct PropertiesPane {
+    path: PathBuf,
+    is_remote: bool,
+    is_dir: bool,
+    properties: Properties,
+}
+
+impl PropertiesPane {
+    /// Show the `properties` of the entry at `path`.
+    pub fn new(path: &Path, is_remote: bool, is_dir: bool, properties: Properties) -> Self {
+        PropertiesPane {
+            path: path.to_path_buf(),
+            is_remote,
+            is_dir,
+            properties,
+        }
+    }
+
+    /// Return the path of the entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return true if the entry is on the server.
+    pub fn is_remote(&self) -> bool {
+        self.is_remote
+    }
+
+    /// Return the properties that are shown.
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Show new `properties` after they were changed.
+    pub fn set_properties(&mut self, properties: Properties) {
+        self.properties = properties;
+    }
+
+    /// Return true if the owner can be changed, which is only done on the server.
+    fn can_change_owner(&self) -> bool {
+        self.is_remote && self.properties.uid.is_some()
+    }
+
+    /// Close the pane or ask to change a property with a key press.
+    pub fn on_event(&mut self, key: KeyEvent) -> PropertiesResult {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Char('p'), KeyModifiers::NONE)
+            | (KeyCode::Esc, _) => PropertiesResult::Close,
+            (KeyCode::Char('m'), KeyModifiers::NONE) => {
+                PropertiesResult::ChangeMode { recursive: false }
+            }
+            (KeyCode::Char('M'), _) if self.is_dir => {
+                PropertiesResult::ChangeMode { recursive: true }
+            }
+            (KeyCode::Char('o'), KeyModifiers::NONE) if self.can_change_owner() => {
+                PropertiesResult::ChangeOwner
+            }
+            _ => PropertiesResult::Pending,
+        }
+    }
+
+    /// Return the lines that describe each property.
+    fn get_lines(&self) -> Vec<String> {
+        let properties = &self.properties;
+        let unknown = || "unknown".to_string();
+        let id = |name: &Option<String>, id: Option<u32>| match (name, id) {
+            (Some(name), Some(id)) => format!("{} ({})", name, id),
+            (None, Some(id)) => id.to_string(),
+            _ => unknown(),
+        };
+        let time = |seconds: Option<u64>| {
+            seconds
+                .map(|seconds| timestamp_to_string(UNIX_EPOCH + Duration::from_secs(seconds)))
+                .unwrap_or_else(unknown)
+        };
+        vec![
+            format!("Path:      {}", self.path.display()),
+            format!(
+                "Size:      {}",
+                properties
+                    .size
+                    .map(|size| format!("{} ({} bytes)", bytes_to_string(size), size))
+                    .unwrap_or_else(unknown)
+            ),
+            format!(
+                "Mode:      {}",
+                properties
+                    .mode
+                    .map(|mode| format!("{} ({:04o})", mode_to_string(mode), mode & 0o7777))
+                    .unwrap_or_else(unknown)
+            ),
+            format!("Owner:     {}", id(&properties.owner, properties.uid)),
+            format!("Group:     {}", id(&properties.group, properties.gid)),
+            format!("Accessed:  {}", time(properties.accessed)),
+            format!("Modified:  {}", time(properties.modified)),
+        ]
+    }
+
+    /// Return the keys that can be pressed.
+    fn get_footer(&self) -> String {
+        let mut keys = vec!["m: mode"];
+        if self.is_dir {
+            keys.push("M: mode recursively");
+        }
+        if self.can_change_owner() {
+            keys.push("o: owner");
+        }
+        keys.push("q: close");
+        keys.join("  ")
+    }
+
+    /// Draw this pane on top of the center of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let lines = self.get_lines();
+        let width = (rect.width * 4 / 5).max(rect.width.min(40));
+        let height = (lines.len() as u16 + 3).min(rect.height);
+        let rect = Rect {
+            x: rect.x + (rect.width - width) / 2,
+            y: rect.y + (rect.height - height) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, rect);
+
+        let block = Block::default()
+            .title("Properties")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(PROPERTIES_COLOR));
+        let inner = block.inner(rect);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(inner);
+
+        let lines: Vec<Spans> = lines.into_iter().map(Spans::from).collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let footer = Span::styled(
+            self.get_footer(),
+            Style::default().add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(Paragraph::new(Spans::from(footer)), chunks[1]);
+    }
+}
+
+/// Parse an owner written like `chown`, as `uid`, `uid:gid` or `:gid`.
+pub fn parse_owner(s: &str) -> Option<(Option<u32>, Option<u32>)> {
+    let parse = |id: &str| -> Option<Option<u32>> {
+        if id.is_empty() {
+            Some(None)
+        } else {
+            id.parse().ok().map(Some)
+        }
+    };
+    let (uid, gid) = match s.trim().split_once(':') {
+        Some((uid, gid)) => (parse(uid)?, parse(gid)?),
+        None => (parse(s.trim())?, None),
+    };
+    if uid.is_none() && gid.is_none() {
+        None
+    } else {
+        Some((uid, gid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rftp::utils::assert_buffer_symbols_eq;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn properties() -> Properties {
+        Properties {
+            size: Some(4096),
+            mode: Some(0o040755),
+            uid: Some(33),
+            gid: Some(50),
+            owner: Some("www".to_string()),
+            group: None,
+            accessed: None,
+            modified: Some(1_614_834_367),
+        }
+    }
+
+    #[test]
+    fn test_properties_events() {
+        let mut pane = PropertiesPane::new(Path::new("/srv/www"), true, true, properties());
+        assert_eq!(
+            pane.on_event(key(KeyCode::Char('m'))),
+            PropertiesResult::ChangeMode { recursive: false }
+        );
+        assert_eq!(
+            pane.on_event(KeyEvent::new(KeyCode::Char('M'), KeyModifiers::SHIFT)),
+            PropertiesResult::ChangeMode { recursive: true }
+        );
+        assert_eq!(
+            pane.on_event(key(KeyCode::Char('o'))),
+            PropertiesResult::ChangeOwner
+        );
+        assert_eq!(pane.on_event(key(KeyCode::Esc)), PropertiesResult::Close);
+
+        // Only directories are changed recursively, and only remote owners are changed.
+        let mut pane = PropertiesPane::new(Path::new("/a.txt"), false, false, properties());
+        assert_eq!(
+            pane.on_event(KeyEvent::new(KeyCode::Char('M'), KeyModifiers::SHIFT)),
+            PropertiesResult::Pending
+        );
+        assert_eq!(
+            pane.on_event(key(KeyCode::Char('o'))),
+            PropertiesResult::Pending
+        );
+    }
+
+    #[test]
+    fn test_draw_properties() {
+        let mut terminal = Terminal::new(TestBackend::new(50, 10)).unwrap();
+        let pane = PropertiesPane::new(Path::new("/srv/www"), true, true, properties());
+        terminal
+            .draw(|frame| {
+                let rect = frame.size();
+                pane.draw(frame, rect);
+            })
+            .unwrap();
+        assert_buffer_symbols_eq(
+            terminal.backend().buffer(),
+            &Buffer::with_lines(vec![
+                "     ┌Properties────────────────────────────┐     ",
+                "     │Path:      /srv/www                   │     ",
+                "     │Size:      4.1 KB (4096 bytes)        │     ",
+                "     │Mode:      drwxr-xr-x (0755)          │     ",
+                "     │Owner:     www (33)                   │     ",
+                "     │Group:     50                         │     ",
+                "     │Accessed:  unknown                    │     ",
+                "     │Modified:  2021-03-04T05:06:07Z       │     ",
+                "     │m: mode  M: mode recursively  o: owner│     ",
+                "     └──────────────────────────────────────┘     ",
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_parse_owner() {
+        assert_eq!(parse_owner("33"), Some((Some(33), None)));
+        assert_eq!(parse_owner("33:50"), Some((Some(33), Some(50))));
+        assert_eq!(parse_owner(":50"), Some((None, Some(50))));
+        assert_eq!(parse_owner(":"), None);
+        assert_eq!(parse_owner("www"), None);
+    }
+}
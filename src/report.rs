@@ -0,0 +1,296 @@
+use rftp::progress::ProgressFile;
+use rftp::transfer::{PlannedAction, TransferListener};
+use rftp::utils::{bytes_to_string, duration_to_string, ErrorKind};
+
+use serde_json::{json, Value};
+use std::io::{stderr, stdout, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// Print human readable progress to stderr.
+    Plain,
+    /// Print one JSON object per event to stdout.
+    Json,
+}
+
+/// Something that happened during a one-shot transfer.
+pub enum Event<'a> {
+    Connected {
+        host: &'a str,
+        username: &'a str,
+    },
+    FileStarted(&'a ProgressFile),
+    Progress(&'a ProgressFile),
+    FileFinished(&'a ProgressFile),
+    Planned(&'a dyn PlannedAction),
+    PlanFinished {
+        changes: usize,
+        bytes: u64,
+    },
+    Deleted(&'a Path),
+    Warning(&'a str),
+    Error(&'a ErrorKind),
+    Summary {
+        files: u64,
+        bytes: u64,
+        duration: Duration,
+        success: bool,
+    },
+}
+
+impl Event<'_> {
+    /// Return this event as a JSON object.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Event::Connected { host, username } => json!({
+                "event": "connected",
+                "host": host,
+                "username": username,
+            }),
+            Event::FileStarted(progress) => json!({
+                "event": "file-started",
+                "title": progress.get_title(),
+                "total_bytes": progress.get_total_bytes(),
+            }),
+            Event::Progress(progress) => json!({
+                "event": "progress",
+                "title": progress.get_title(),
+                "bytes_sent": progress.get_bytes_sent(),
+                "total_bytes": progress.get_total_bytes(),
+                "bitrate": progress.get_current_bitrate(),
+                "eta": progress.get_eta().map(|eta| eta.as_secs_f64()),
+            }),
+            Event::FileFinished(progress) => json!({
+                "event": "file-finished",
+                "title": progress.get_title(),
+                "bytes": progress.get_total_bytes(),
+                "duration": progress.get_elapsed().as_secs_f64(),
+            }),
+            Event::Planned(action) => json!({
+                "event": "planned",
+                "action": action.name(),
+                "path": action.path(),
+                "bytes": action.bytes(),
+            }),
+            Event::PlanFinished { changes, bytes } => json!({
+                "event": "plan-finished",
+                "changes": changes,
+                "bytes": bytes,
+            }),
+            Event::Deleted(path) => json!({
+                "event": "deleted",
+                "path": path,
+            }),
+            Event::Warning(message) => json!({
+                "event": "warning",
+                "message": message,
+            }),
+            Event::Error(error) => json!({
+                "event": "error",
+                "kind": error.name(),
+                "message": error.to_string(),
+                "exit_code": error.exit_code(),
+            }),
+            Event::Summary {
+                files,
+                bytes,
+                duration,
+                success,
+            } => json!({
+                "event": "summary",
+                "files": files,
+                "bytes": bytes,
+                "duration": duration.as_secs_f64(),
+                "success": success,
+            }),
+        }
+    }
+}
+
+/// Reports the events of a one-shot transfer to the terminal.
+pub struct Reporter {
+    mode: OutputMode,
+    start_time: Instant,
+    current: Mutex<Option<Arc<ProgressFile>>>,
+    files_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl Reporter {
+    pub fn new(mode: OutputMode) -> Self {
+        Reporter {
+            mode,
+            start_time: Instant::now(),
+            current: Mutex::new(None),
+            files_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+        }
+    }
+
+    /// Report `event` in the current output mode.
+    pub fn emit(&self, event: Event) {
+        match self.mode {
+            OutputMode::Json => {
+                let mut stdout = stdout();
+                writeln!(stdout, "{}", event.to_json()).ok();
+                stdout.flush().ok();
+            }
+            OutputMode::Plain => {
+                let mut stderr = stderr();
+                match event {
+                    Event::Progress(progress) => {
+                        write!(
+                            stderr,
+                            "\r{}  {}",
+                            progress.get_title(),
+                            progress.get_info()
+                        )
+                        .ok();
+                    }
+                    Event::FileFinished(progress) => {
+                        writeln!(
+                            stderr,
+                            "\r{}  {}",
+                            progress.get_title(),
+                            progress.get_info()
+                        )
+                        .ok();
+                    }
+                    Event::Planned(action) => {
+                        writeln!(stderr, "{}", action.describe()).ok();
+                    }
+                    Event::PlanFinished { changes: 0, .. } => {
+                        writeln!(stderr, "Already up to date.").ok();
+                    }
+                    Event::PlanFinished { changes, bytes } => {
+                        writeln!(
+                            stderr,
+                            "{} {}, {} to transfer.",
+                            changes,
+                            if changes == 1 { "change" } else { "changes" },
+                            bytes_to_string(bytes)
+                        )
+                        .ok();
+                    }
+                    Event::Deleted(path) => {
+                        writeln!(stderr, "Deleted {}", path.display()).ok();
+                    }
+                    Event::Warning(message) => {
+                        writeln!(stderr, "{}", message).ok();
+                    }
+                    Event::Summary {
+                        files,
+                        bytes,
+                        duration,
+                        success: true,
+                    } => {
+                        writeln!(
+                            stderr,
+                            "Transferred {} {} ({}) in {}.",
+                            files,
+                            if files == 1 { "file" } else { "files" },
+                            bytes_to_string(bytes),
+                            duration_to_string(duration)
+                        )
+                        .ok();
+                    }
+                    // Errors are printed by `main` before exiting.
+                    _ => {}
+                }
+                stderr.flush().ok();
+            }
+        }
+    }
+
+    /// Report the progress of the file that is currently being transferred.
+    pub fn emit_progress(&self) {
+        if let Some(progress) = self.current.lock().unwrap().as_ref() {
+            self.emit(Event::Progress(progress));
+        }
+    }
+
+    /// Report the outcome of the transfer followed by a summary of everything that was sent.
+    pub fn finish(&self, result: &Result<(), ErrorKind>) {
+        if let Err(error) = result {
+            self.emit(Event::Error(error));
+        }
+        self.emit(Event::Summary {
+            files: self.files_sent.load(Ordering::Relaxed),
+            bytes: self.bytes_sent.load(Ordering::Relaxed),
+            duration: self.start_time.elapsed(),
+            success: result.is_ok(),
+        });
+    }
+}
+
+impl TransferListener for Reporter {
+    fn file_started(&self, progress: Arc<ProgressFile>) {
+        self.emit(Event::FileStarted(&progress));
+        *self.current.lock().unwrap() = Some(progress);
+    }
+
+    fn file_finished(&self, progress: &ProgressFile) {
+        let mut current = self.current.lock().unwrap();
+        self.files_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent
+            .fetch_add(progress.get_total_bytes(), Ordering::Relaxed);
+        self.emit(Event::FileFinished(progress));
+        *current = None;
+    }
+
+    fn warn(&self, message: &str) {
+        self.emit(Event::Warning(message));
+    }
+
+    fn deleted(&self, path: &Path) {
+        self.emit(Event::Deleted(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_to_json() {
+        let progress = ProgressFile::new("Downloading \"a.txt\"", 100);
+        progress.inc(40);
+        assert_eq!(
+            Event::FileStarted(&progress).to_json(),
+            json!({
+                "event": "file-started",
+                "title": "Downloading \"a.txt\"",
+                "total_bytes": 100,
+            })
+        );
+        assert_eq!(
+            Event::Error(&ErrorKind::RemoteFileExists("/tmp/a.txt".to_string())).to_json(),
+            json!({
+                "event": "error",
+                "kind": "remote-file-exists",
+                "message": "remote file /tmp/a.txt already exists",
+                "exit_code": 5,
+            })
+        );
+        assert_eq!(
+            Event::Summary {
+                files: 2,
+                bytes: 1234,
+                duration: Duration::from_millis(1500),
+                success: true,
+            }
+            .to_json(),
+            json!({
+                "event": "summary",
+                "files": 2,
+                "bytes": 1234,
+                "duration": 1.5,
+                "success": true,
+            })
+        );
+    }
+}
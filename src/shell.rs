@@ -0,0 +1,152 @@
+use rftp::utils::{shell_quote, Result};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long to wait for a key press before checking for output of the shell again.
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Run an interactive login shell in `cwd` on the server of `session` with the terminal of
+/// this process, and return its exit status once it exits.
+///
+/// The session is not blocking while the shell runs, so nothing else may use it meanwhile.
+pub fn run_shell(session: &ssh2::Session, cwd: &Path) -> Result<i32> {
+    let (width, height) = crossterm::terminal::size()?;
+    let term = std::env::var("TERM").unwrap_or_else(|_| "xterm".to_string());
+    let mut channel = session.channel_session()?;
+    channel.request_pty(&term, None, Some((width.into(), height.into(), 0, 0)))?;
+    channel.exec(&format!(
+        "cd {} && exec \"${{SHELL:-sh}}\" -l",
+        shell_quote(&cwd.to_string_lossy())
+    ))?;
+
+    crossterm::terminal::enable_raw_mode()?;
+    session.set_blocking(false);
+    let result = forward(&mut channel);
+    session.set_blocking(true);
+    crossterm::terminal::disable_raw_mode()?;
+    result?;
+
+    channel.wait_close()?;
+    Ok(channel.exit_status()?)
+}
+
+/// Copy the output of `channel` to stdout and key presses to `channel` until it is closed.
+fn forward(channel: &mut ssh2::Channel) -> Result<()> {
+    let mut stdout = io::stdout();
+    let mut buffer = [0; 4096];
+    loop {
+        match channel.read(&mut buffer) {
+            Ok(0) => {}
+            Ok(len) => {
+                stdout.write_all(&buffer[..len])?;
+                stdout.flush()?;
+                continue;
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error.into()),
+        }
+        if channel.eof() {
+            return Ok(());
+        }
+
+        if crossterm::event::poll(POLL_TIMEOUT)? {
+            match crossterm::event::read()? {
+                Event::Key(key) => write_all(channel, &key_to_bytes(key))?,
+                Event::Resize(width, height) => loop {
+                    match channel.request_pty_size(width.into(), height.into(), None, None) {
+                        Err(error) if is_would_block(&error) => {}
+                        result => break result?,
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Write all of `bytes` to the non-blocking `channel`.
+fn write_all(channel: &mut ssh2::Channel, mut bytes: &[u8]) -> Result<()> {
+    while !bytes.is_empty() {
+        match channel.write(bytes) {
+            Ok(len) => bytes = &bytes[len..],
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(())
+}
+
+fn is_would_block(error: &ssh2::Error) -> bool {
+    error.code() == ssh2::ErrorCode::Session(libssh2_sys::LIBSSH2_ERROR_EAGAIN)
+}
+
+/// Return the bytes that a terminal sends for `key`.
+fn key_to_bytes(key: KeyEvent) -> Vec<u8> {
+    let escape = |sequence: &str| [b"\x1b", sequence.as_bytes()].concat();
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match c.to_ascii_lowercase() {
+                c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+                '@' | ' ' => vec![0],
+                '[' => vec![0x1b],
+                '\\' => vec![0x1c],
+                ']' => vec![0x1d],
+                c => c.to_string().into_bytes(),
+            }
+        }
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => escape(&c.to_string()),
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => escape("[Z"),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => escape("[A"),
+        KeyCode::Down => escape("[B"),
+        KeyCode::Right => escape("[C"),
+        KeyCode::Left => escape("[D"),
+        KeyCode::Home => escape("[H"),
+        KeyCode::End => escape("[F"),
+        KeyCode::Insert => escape("[2~"),
+        KeyCode::Delete => escape("[3~"),
+        KeyCode::PageUp => escape("[5~"),
+        KeyCode::PageDown => escape("[6~"),
+        KeyCode::F(n @ 1..=4) => escape(&format!("O{}", (b'P' + n - 1) as char)),
+        KeyCode::F(n @ 5..=12) => {
+            let codes = [15, 17, 18, 19, 20, 21, 23, 24];
+            escape(&format!("[{}~", codes[n as usize - 5]))
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Vec<u8> {
+        key_to_bytes(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn test_key_to_bytes() {
+        assert_eq!(key(KeyCode::Char('a'), KeyModifiers::NONE), b"a");
+        assert_eq!(key(KeyCode::Char('A'), KeyModifiers::SHIFT), b"A");
+        assert_eq!(
+            key(KeyCode::Char('\u{e9}'), KeyModifiers::NONE),
+            "\u{e9}".as_bytes()
+        );
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), b"\x03");
+        assert_eq!(key(KeyCode::Char('D'), KeyModifiers::CONTROL), b"\x04");
+        assert_eq!(key(KeyCode::Char('b'), KeyModifiers::ALT), b"\x1bb");
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), b"\r");
+        assert_eq!(key(KeyCode::Backspace, KeyModifiers::NONE), b"\x7f");
+        assert_eq!(key(KeyCode::Up, KeyModifiers::NONE), b"\x1b[A");
+        assert_eq!(key(KeyCode::F(1), KeyModifiers::NONE), b"\x1bOP");
+        assert_eq!(key(KeyCode::F(12), KeyModifiers::NONE), b"\x1b[24~");
+    }
+}
@@ -0,0 +1,645 @@
+use crate::file::*;
+use crate::filesystem::{FileSystem, LocalFileSystem};
+use crate::filter::Filter;
+use crate::progress::ProgressFile;
+use crate::transfer::{copy_file_with_retries, transfer_verb, PlannedAction, TransferListener};
+use crate::utils::{bytes_to_string, run_remote_command, shell_quote, ErrorKind, Result};
+
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The direction that files are copied in during a sync.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncDirection {
+    /// Make the remote directory match the local directory.
+    Upload,
+    /// Make the local directory match the remote directory.
+    Download,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SyncOptions {
+    /// Compare the contents of files with the same size instead of their modification times.
+    pub checksum: bool,
+    /// Delete entries in the destination that do not exist in the source.
+    pub delete: bool,
+}
+
+/// A single step that brings the destination closer to the source.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncAction {
+    /// Create the directory `dest`.
+    CreateDirectory { dest: PathBuf },
+    /// Copy the file `source` to `dest`, which does not exist yet.
+    Create {
+        source: PathBuf,
+        dest: PathBuf,
+        len: u64,
+        modified: Option<u64>,
+    },
+    /// Overwrite `dest` with the file `source` because they differ.
+    Update {
+        source: PathBuf,
+        dest: PathBuf,
+        len: u64,
+        modified: Option<u64>,
+    },
+    /// Remove `dest` because it does not exist in the source.
+    Delete { dest: PathBuf, is_dir: bool },
+    /// Leave `path` alone.
+    Skip { path: PathBuf, reason: &'static str },
+}
+
+impl PlannedAction for SyncAction {
+    fn bytes(&self) -> u64 {
+        match self {
+            SyncAction::Create { len, .. } | SyncAction::Update { len, .. } => *len,
+            _ => 0,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SyncAction::CreateDirectory { .. } => "mkdir",
+            SyncAction::Create { .. } => "create",
+            SyncAction::Update { .. } => "update",
+            SyncAction::Delete { .. } => "delete",
+            SyncAction::Skip { .. } => "skip",
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            SyncAction::CreateDirectory { dest }
+            | SyncAction::Create { dest, .. }
+            | SyncAction::Update { dest, .. }
+            | SyncAction::Delete { dest, .. } => dest,
+            SyncAction::Skip { path, .. } => path,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SyncAction::CreateDirectory { dest } => format!("mkdir   {}/", dest.display()),
+            SyncAction::Create { dest, len, .. } => {
+                format!("create  {} ({})", dest.display(), bytes_to_string(*len))
+            }
+            SyncAction::Update { dest, len, .. } => {
+                format!("update  {} ({})", dest.display(), bytes_to_string(*len))
+            }
+            SyncAction::Delete { dest, is_dir } => format!(
+                "delete  {}{}",
+                dest.display(),
+                if *is_dir { "/" } else { "" }
+            ),
+            SyncAction::Skip { path, reason } => {
+                format!("skip    {} ({})", path.display(), reason)
+            }
+        }
+    }
+
+    fn is_change(&self) -> bool {
+        !matches!(self, SyncAction::Skip { .. })
+    }
+}
+
+/// The parts of a file entry that are compared during a sync.
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    File {
+        path: PathBuf,
+        len: u64,
+        modified: Option<u64>,
+    },
+    Directory(PathBuf),
+    Other(PathBuf),
+}
+
+impl Node {
+    fn new(entry: &impl FileEntry) -> Self {
+        let path = entry.path().to_path_buf();
+        if entry.is_file() {
+            Node::File {
+                path,
+                len: entry.len().unwrap_or(0),
+                modified: entry.modified(),
+            }
+        } else if entry.is_dir() {
+            Node::Directory(path)
+        } else {
+            Node::Other(path)
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Node::File { path, .. } | Node::Directory(path) | Node::Other(path) => path,
+        }
+    }
+}
+
+/// Return the entries of a directory keyed by their file names.
+fn by_name(nodes: Vec<Node>) -> BTreeMap<OsString, Node> {
+    nodes
+        .into_iter()
+        .filter_map(|node| {
+            let name = node.path().file_name()?.to_os_string();
+            Some((name, node))
+        })
+        .collect()
+}
+
+/// Compare the source tree at `source_root` with the destination tree at `dest_root` and
+/// return the actions that make the destination match the source.
+///
+/// `list_source` and `list_dest` return the entries of a directory on either side, and
+/// `dest_exists` returns true if a path exists in the destination. When `options.checksum`
+/// is set, `same_contents` decides if two files of the same size are identical.
+///
+/// Entries in the source for which `is_excluded` returns true are skipped, and entries in the
+/// destination that would be excluded in the source are never deleted.
+#[allow(clippy::too_many_arguments)]
+fn plan<S, D, E, C, X>(
+    source_root: &Path,
+    dest_root: &Path,
+    list_source: S,
+    list_dest: D,
+    dest_exists: E,
+    same_contents: C,
+    is_excluded: X,
+    options: SyncOptions,
+) -> Result<Vec<SyncAction>>
+where
+    S: Fn(&Path) -> Result<Vec<Node>>,
+    D: Fn(&Path) -> Result<Vec<Node>>,
+    E: Fn(&Path) -> Result<bool>,
+    C: Fn(&Path, &Path) -> Result<bool>,
+    X: Fn(&Path, bool) -> bool,
+{
+    let mut actions = vec![];
+    let root_exists = dest_exists(dest_root)?;
+    if !root_exists {
+        actions.push(SyncAction::CreateDirectory {
+            dest: dest_root.to_path_buf(),
+        });
+    }
+
+    // Walk both trees in depth-first order.
+    let mut stack = vec![(
+        source_root.to_path_buf(),
+        dest_root.to_path_buf(),
+        root_exists,
+    )];
+    while let Some((source_dir, dest_dir, dest_dir_exists)) = stack.pop() {
+        let source_children = by_name(
+            list_source(&source_dir)?
+                .into_iter()
+                .filter(|node| !is_excluded(node.path(), matches!(node, Node::Directory(_))))
+                .collect(),
+        );
+        let mut dest_children = if dest_dir_exists {
+            by_name(list_dest(&dest_dir)?)
+        } else {
+            BTreeMap::new()
+        };
+        let mut subdirectories = vec![];
+
+        for (name, source) in source_children {
+            let dest_path = dest_dir.join(&name);
+            let dest = dest_children.remove(&name);
+            match (source, dest) {
+                (Node::Other(path), _) => actions.push(SyncAction::Skip {
+                    path,
+                    reason: "it might be a symlink",
+                }),
+                (
+                    Node::File {
+                        path,
+                        len,
+                        modified,
+                    },
+                    None,
+                ) => actions.push(SyncAction::Create {
+                    source: path,
+                    dest: dest_path,
+                    len,
+                    modified,
+                }),
+                (
+                    Node::File {
+                        path,
+                        len,
+                        modified,
+                    },
+                    Some(Node::File {
+                        len: dest_len,
+                        modified: dest_modified,
+                        ..
+                    }),
+                ) => {
+                    let is_different = if len != dest_len {
+                        true
+                    } else if options.checksum {
+                        !same_contents(&path, &dest_path)?
+                    } else {
+                        modified != dest_modified
+                    };
+                    if is_different {
+                        actions.push(SyncAction::Update {
+                            source: path,
+                            dest: dest_path,
+                            len,
+                            modified,
+                        });
+                    }
+                }
+                (Node::Directory(path), None) => {
+                    actions.push(SyncAction::CreateDirectory {
+                        dest: dest_path.clone(),
+                    });
+                    subdirectories.push((path, dest_path, false));
+                }
+                (Node::Directory(path), Some(Node::Directory(_))) => {
+                    subdirectories.push((path, dest_path, true));
+                }
+                (source, Some(dest)) => {
+                    // The source and destination are different kinds of entries.
+                    if options.delete {
+                        actions.push(SyncAction::Delete {
+                            dest: dest_path.clone(),
+                            is_dir: matches!(dest, Node::Directory(_)),
+                        });
+                        match source {
+                            Node::File {
+                                path,
+                                len,
+                                modified,
+                            } => actions.push(SyncAction::Create {
+                                source: path,
+                                dest: dest_path,
+                                len,
+                                modified,
+                            }),
+                            Node::Directory(path) => {
+                                actions.push(SyncAction::CreateDirectory {
+                                    dest: dest_path.clone(),
+                                });
+                                subdirectories.push((path, dest_path, false));
+                            }
+                            Node::Other(_) => unreachable!(),
+                        }
+                    } else {
+                        actions.push(SyncAction::Skip {
+                            path: dest_path,
+                            reason: "a different kind of entry exists in the destination",
+                        });
+                    }
+                }
+            }
+        }
+
+        if options.delete {
+            for (name, dest) in dest_children {
+                let is_dir = matches!(dest, Node::Directory(_));
+                if is_excluded(&source_dir.join(name), is_dir) {
+                    continue;
+                }
+                actions.push(SyncAction::Delete {
+                    is_dir,
+                    dest: dest.path().to_path_buf(),
+                });
+            }
+        }
+
+        // Push in reverse so that subdirectories are visited in alphabetical order.
+        stack.extend(subdirectories.into_iter().rev());
+    }
+
+    Ok(actions)
+}
+
+/// Return the actions that make `dest` match `source` in the given `direction`.
+///
+/// `source` and `dest` are directories, and `dest` may not exist yet. Entries that are
+/// excluded by `filter` are neither copied nor deleted.
+pub fn plan_sync(
+    source: &Path,
+    dest: &Path,
+    direction: SyncDirection,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    filter: &Filter,
+    options: SyncOptions,
+) -> Result<Vec<SyncAction>> {
+    match direction {
+        SyncDirection::Upload => plan_between(
+            &LocalFileSystem,
+            sftp,
+            source,
+            dest,
+            |local, remote| Ok(local_sha256(local)? == remote_sha256(session, remote)?),
+            filter,
+            options,
+        ),
+        SyncDirection::Download => plan_between(
+            sftp,
+            &LocalFileSystem,
+            source,
+            dest,
+            |remote, local| Ok(local_sha256(local)? == remote_sha256(session, remote)?),
+            filter,
+            options,
+        ),
+    }
+}
+
+/// Return the actions that make the directory `dest` of `dest_fs` match the directory
+/// `source` of `source_fs`.
+fn plan_between<S, D, C>(
+    source_fs: &S,
+    dest_fs: &D,
+    source: &Path,
+    dest: &Path,
+    same_contents: C,
+    filter: &Filter,
+    options: SyncOptions,
+) -> Result<Vec<SyncAction>>
+where
+    S: FileSystem,
+    D: FileSystem,
+    C: Fn(&Path, &Path) -> Result<bool>,
+{
+    let tree_filter = RefCell::new(filter.for_tree(source));
+    plan(
+        source,
+        dest,
+        |path| {
+            let entries = tree_filter.borrow_mut().filter_entries(
+                path,
+                source_fs.read_dir(path)?,
+                |path| source_fs.read_to_string(path),
+            )?;
+            Ok(entries.iter().map(Node::new).collect())
+        },
+        |path| {
+            let entries = dest_fs.read_dir(path)?;
+            Ok(entries.iter().map(Node::new).collect())
+        },
+        |path| Ok(dest_fs.stat(path)?.is_some()),
+        same_contents,
+        |path, is_dir| tree_filter.borrow().is_excluded(path, is_dir),
+        options,
+    )
+}
+
+/// Apply the actions returned by `plan_sync` in order.
+pub fn apply_sync(
+    actions: &[SyncAction],
+    direction: SyncDirection,
+    sftp: &ssh2::Sftp,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    match direction {
+        SyncDirection::Upload => apply_between(&LocalFileSystem, sftp, actions, listener),
+        SyncDirection::Download => apply_between(sftp, &LocalFileSystem, actions, listener),
+    }
+}
+
+/// Apply `actions` to `dest_fs` by copying files from `source_fs`.
+fn apply_between(
+    source_fs: &impl FileSystem,
+    dest_fs: &impl FileSystem,
+    actions: &[SyncAction],
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let verb = transfer_verb(source_fs, dest_fs);
+    for action in actions {
+        match action {
+            SyncAction::CreateDirectory { dest } => dest_fs.mkdir(dest)?,
+            SyncAction::Create {
+                source,
+                dest,
+                len,
+                modified,
+            }
+            | SyncAction::Update {
+                source,
+                dest,
+                len,
+                modified,
+            } => {
+                let progress = {
+                    let entry_name = source.file_name().unwrap_or_default().to_string_lossy();
+                    let title = format!("{} \"{}\"", verb, entry_name);
+                    Arc::new(ProgressFile::new(&title, *len))
+                };
+                listener.file_started(Arc::clone(&progress));
+                copy_file_with_retries(source_fs, source, dest_fs, dest, &progress, listener)?;
+                if let Some(modified) = modified {
+                    dest_fs.set_modified(dest, *modified)?;
+                }
+                listener.file_finished(&progress);
+            }
+            SyncAction::Delete { dest, is_dir } => {
+                if *is_dir {
+                    dest_fs.remove_all(dest)?;
+                } else {
+                    dest_fs.remove(dest)?;
+                }
+                listener.deleted(dest);
+            }
+            SyncAction::Skip { path, reason } => {
+                listener.warn(&format!(
+                    "Warning: Skipping {} because {}.",
+                    path.display(),
+                    reason
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the SHA-256 hash of the local file at `path` as a hex string.
+fn local_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Return the SHA-256 hash of the remote file at `path` as a hex string.
+///
+/// The hash is computed on the remote host so that the file does not need to be downloaded.
+fn remote_sha256(session: &ssh2::Session, path: &Path) -> Result<String> {
+    let command = format!("sha256sum -- {}", shell_quote(&path.to_string_lossy()));
+    let output = run_remote_command(session, &command)?;
+    output
+        .split_whitespace()
+        .next()
+        .map(|hash| hash.to_string())
+        .ok_or(ErrorKind::UnexpectedCommandOutput(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, len: u64, modified: u64) -> Node {
+        Node::File {
+            path: PathBuf::from(path),
+            len,
+            modified: Some(modified),
+        }
+    }
+
+    fn dir(path: &str) -> Node {
+        Node::Directory(PathBuf::from(path))
+    }
+
+    fn tree(nodes: Vec<Node>) -> impl Fn(&Path) -> Result<Vec<Node>> {
+        move |path| {
+            Ok(nodes
+                .iter()
+                .filter(|node| node.path().parent() == Some(path))
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let source = tree(vec![
+            file("/src/same.txt", 10, 100),
+            file("/src/newer.txt", 10, 200),
+            file("/src/bigger.txt", 20, 100),
+            file("/src/new.txt", 5, 100),
+            dir("/src/sub"),
+            file("/src/sub/a.txt", 1, 100),
+            Node::Other(PathBuf::from("/src/link")),
+        ]);
+        let dest = tree(vec![
+            file("/dst/same.txt", 10, 100),
+            file("/dst/newer.txt", 10, 100),
+            file("/dst/bigger.txt", 10, 100),
+            file("/dst/extra.txt", 10, 100),
+            dir("/dst/old"),
+        ]);
+        let options = SyncOptions {
+            checksum: false,
+            delete: true,
+        };
+        let actions = plan(
+            Path::new("/src"),
+            Path::new("/dst"),
+            source,
+            dest,
+            |_| Ok(true),
+            |_, _| unreachable!(),
+            |_, _| false,
+            options,
+        )
+        .unwrap();
+
+        let descriptions: Vec<String> = actions.iter().map(|a| a.describe()).collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "update  /dst/bigger.txt (20 B)",
+                "skip    /src/link (it might be a symlink)",
+                "create  /dst/new.txt (5 B)",
+                "update  /dst/newer.txt (10 B)",
+                "mkdir   /dst/sub/",
+                "delete  /dst/extra.txt",
+                "delete  /dst/old/",
+                "create  /dst/sub/a.txt (1 B)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_checksum() {
+        let source = tree(vec![
+            file("/src/a.txt", 10, 100),
+            file("/src/b.txt", 10, 100),
+        ]);
+        let dest = tree(vec![
+            file("/dst/a.txt", 10, 300),
+            file("/dst/b.txt", 10, 100),
+        ]);
+        let options = SyncOptions {
+            checksum: true,
+            delete: false,
+        };
+        let actions = plan(
+            Path::new("/src"),
+            Path::new("/dst"),
+            source,
+            dest,
+            |_| Ok(true),
+            |source, _| Ok(source.ends_with("a.txt")),
+            |_, _| false,
+            options,
+        )
+        .unwrap();
+        assert_eq!(
+            actions,
+            vec![SyncAction::Update {
+                source: PathBuf::from("/src/b.txt"),
+                dest: PathBuf::from("/dst/b.txt"),
+                len: 10,
+                modified: Some(100),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_excluded() {
+        let source = tree(vec![file("/src/a.txt", 1, 100), dir("/src/target")]);
+        let dest = tree(vec![dir("/dst/target"), file("/dst/b.log", 1, 100)]);
+        let options = SyncOptions {
+            checksum: false,
+            delete: true,
+        };
+        let actions = plan(
+            Path::new("/src"),
+            Path::new("/dst"),
+            source,
+            dest,
+            |_| Ok(true),
+            |_, _| unreachable!(),
+            |path, _| path.ends_with("target") || path.ends_with("b.log"),
+            options,
+        )
+        .unwrap();
+        let descriptions: Vec<String> = actions.iter().map(|a| a.describe()).collect();
+        assert_eq!(descriptions, vec!["create  /dst/a.txt (1 B)"]);
+    }
+
+    #[test]
+    fn test_plan_missing_destination() {
+        let source = tree(vec![file("/src/a.txt", 1, 100)]);
+        let dest = tree(vec![]);
+        let actions = plan(
+            Path::new("/src"),
+            Path::new("/dst"),
+            source,
+            dest,
+            |_| Ok(false),
+            |_, _| unreachable!(),
+            |_, _| false,
+            SyncOptions::default(),
+        )
+        .unwrap();
+        let descriptions: Vec<String> = actions.iter().map(|a| a.describe()).collect();
+        assert_eq!(
+            descriptions,
+            vec!["mkdir   /dst/", "create  /dst/a.txt (1 B)"]
+        );
+    }
+}
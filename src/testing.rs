@@ -0,0 +1,152 @@
+//! Helpers for driving the app without a terminal or a server.
+
+use crate::app::Rftp;
+use rftp::utils::assert_buffer_symbols_eq;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Return the key event for `key`, which is either a single character or the name of a key
+/// like `Enter` or `Space`.
+pub fn parse_key(key: &str) -> KeyEvent {
+    let code = match key {
+        "Enter" => KeyCode::Enter,
+        "Space" => KeyCode::Char(' '),
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        _ => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => panic!("unknown key {:?}", key),
+            }
+        }
+    };
+    let modifiers = match code {
+        KeyCode::Char(c) if c.is_ascii_uppercase() => KeyModifiers::SHIFT,
+        _ => KeyModifiers::NONE,
+    };
+    KeyEvent::new(code, modifiers)
+}
+
+/// Feeds synthetic key presses to the app and draws it to an in-memory terminal.
+pub struct Driver {
+    pub rftp: Rftp,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Driver {
+    pub fn new(rftp: Rftp, width: u16, height: u16) -> Self {
+        let mut driver = Driver {
+            rftp,
+            terminal: Terminal::new(TestBackend::new(width, height)).unwrap(),
+        };
+        driver.tick();
+        driver
+    }
+
+    /// Press each of the space separated `keys` in order, with a tick after each one.
+    pub fn press(&mut self, keys: &str) -> &mut Self {
+        for key in keys.split_whitespace() {
+            self.rftp.on_event(parse_key(key)).unwrap();
+            self.tick();
+        }
+        self
+    }
+
+    /// Click the left mouse button at `column` and `row`, then tick.
+    pub fn click(&mut self, column: u16, row: u16) -> &mut Self {
+        self.mouse(MouseEventKind::Down(MouseButton::Left), column, row)
+    }
+
+    /// Scroll the mouse wheel `lines` lines down, or up if negative, at `column` and `row`.
+    pub fn scroll(&mut self, column: u16, row: u16, lines: isize) -> &mut Self {
+        let kind = if lines < 0 {
+            MouseEventKind::ScrollUp
+        } else {
+            MouseEventKind::ScrollDown
+        };
+        for _ in 0..lines.unsigned_abs() {
+            self.mouse(kind, column, row);
+        }
+        self
+    }
+
+    fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) -> &mut Self {
+        let event = MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        self.rftp.on_mouse(event).unwrap();
+        self.tick()
+    }
+
+    /// Resize the terminal to `width` by `height` and draw the app.
+    pub fn resize(&mut self, width: u16, height: u16) -> &mut Self {
+        self.terminal.backend_mut().resize(width, height);
+        self.terminal.autoresize().unwrap();
+        self.tick()
+    }
+
+    /// Do the work of one tick and draw the app.
+    pub fn tick(&mut self) -> &mut Self {
+        self.rftp.tick().unwrap();
+        let rftp = &self.rftp;
+        self.terminal.draw(|frame| rftp.draw(frame)).unwrap();
+        self
+    }
+
+    /// Tick until `text` is on the screen, which is useful when work is done in the
+    /// background.
+    pub fn wait_for(&mut self, text: &str) -> &mut Self {
+        let start = Instant::now();
+        while !self.screen().contains(text) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "timed out waiting for {:?} on the screen:\n{}",
+                text,
+                self.screen()
+            );
+            thread::sleep(Duration::from_millis(5));
+            self.tick();
+        }
+        self
+    }
+
+    /// Return the last drawn screen as lines of text.
+    pub fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        buffer
+            .content()
+            .chunks(width)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Assert that the last drawn screen shows `lines`.
+    pub fn assert_screen(&self, lines: &[&str]) {
+        let expected = Buffer::with_lines(lines.to_vec());
+        let actual = self.terminal.backend().buffer();
+        if actual.area != expected.area || self.screen() != lines.join("\n") {
+            eprintln!("actual screen:\n{}", self.screen());
+        }
+        assert_buffer_symbols_eq(actual, &expected);
+    }
+}
@@ -0,0 +1,551 @@
+use crate::file::*;
+use crate::filesystem::{FileSystem, LocalFileSystem};
+use crate::filter::Filter;
+use crate::progress::ProgressFile;
+use crate::utils::{bytes_to_string, ErrorKind, Result};
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Receives updates from a recursive download or upload.
+pub trait TransferListener {
+    /// Called when `progress` has been created for a file that is about to be transferred.
+    fn file_started(&self, progress: Arc<ProgressFile>);
+
+    /// Called when the file tracked by `progress` has been transferred.
+    fn file_finished(&self, progress: &ProgressFile);
+
+    /// Called each time a chunk of the file tracked by `progress` has been transferred.
+    fn progress(&self, _progress: &ProgressFile) {}
+
+    /// Called when an entry is skipped or something unexpected happens.
+    fn warn(&self, message: &str);
+
+    /// Called when the entry at `path` has been removed from the destination.
+    fn deleted(&self, _path: &Path) {}
+
+    /// Return true if the transfer should stop before its next chunk.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Ignores every update.
+impl TransferListener for () {
+    fn file_started(&self, _progress: Arc<ProgressFile>) {}
+
+    fn file_finished(&self, _progress: &ProgressFile) {}
+
+    fn warn(&self, _message: &str) {}
+}
+
+/// A single step of a plan that is shown to the user before it is applied.
+pub trait PlannedAction {
+    /// Return the name of this kind of action.
+    fn name(&self) -> &'static str;
+
+    /// Return the path that this action applies to.
+    fn path(&self) -> &Path;
+
+    /// Return the number of bytes that this action will transfer.
+    fn bytes(&self) -> u64;
+
+    /// Return a line that describes this action to the user.
+    fn describe(&self) -> String;
+
+    /// Return true if this action changes the destination.
+    fn is_change(&self) -> bool;
+}
+
+/// A single step of a recursive download or upload.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransferAction {
+    /// Create the directory `dest`.
+    CreateDirectory { dest: PathBuf },
+    /// Copy the file `source` to `dest`, which does not exist yet.
+    Create {
+        source: PathBuf,
+        dest: PathBuf,
+        len: u64,
+    },
+    /// Replace the existing file `dest` with the file `source`.
+    Overwrite {
+        source: PathBuf,
+        dest: PathBuf,
+        len: u64,
+    },
+    /// Leave `path` alone.
+    Skip { path: PathBuf, reason: &'static str },
+}
+
+impl PlannedAction for TransferAction {
+    fn name(&self) -> &'static str {
+        match self {
+            TransferAction::CreateDirectory { .. } => "mkdir",
+            TransferAction::Create { .. } => "create",
+            TransferAction::Overwrite { .. } => "overwrite",
+            TransferAction::Skip { .. } => "skip",
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            TransferAction::CreateDirectory { dest }
+            | TransferAction::Create { dest, .. }
+            | TransferAction::Overwrite { dest, .. } => dest,
+            TransferAction::Skip { path, .. } => path,
+        }
+    }
+
+    fn bytes(&self) -> u64 {
+        match self {
+            TransferAction::Create { len, .. } | TransferAction::Overwrite { len, .. } => *len,
+            _ => 0,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            TransferAction::CreateDirectory { dest } => format!("mkdir     {}/", dest.display()),
+            TransferAction::Create { dest, len, .. } => {
+                format!("create    {} ({})", dest.display(), bytes_to_string(*len))
+            }
+            TransferAction::Overwrite { dest, len, .. } => {
+                format!("overwrite {} ({})", dest.display(), bytes_to_string(*len))
+            }
+            TransferAction::Skip { path, reason } => {
+                format!("skip      {} ({})", path.display(), reason)
+            }
+        }
+    }
+
+    fn is_change(&self) -> bool {
+        !matches!(self, TransferAction::Skip { .. })
+    }
+}
+
+/// `CHUNK_SIZE` bytes of data is read from the source and then it is all written to the dest.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// How many times the copy of a file is resumed after the connection was lost.
+const MAX_RETRIES: u32 = 5;
+
+/// How long to wait before resuming a copy the first time. Each retry waits twice as long.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// What already exists at a destination path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Existing {
+    Nothing,
+    File,
+    Directory,
+}
+
+impl Existing {
+    /// Return what exists at `path` in `fs`.
+    fn at(fs: &(impl FileSystem + ?Sized), path: &Path) -> Result<Existing> {
+        Ok(match fs.stat(path)? {
+            None => Existing::Nothing,
+            Some(entry) if entry.is_dir() => Existing::Directory,
+            Some(_) => Existing::File,
+        })
+    }
+}
+
+/// Return the action that copies the file `source` of length `len` to `dest`.
+fn plan_file(source: &Path, dest: PathBuf, len: u64, existing: Existing) -> TransferAction {
+    let source = source.to_path_buf();
+    match existing {
+        Existing::Nothing => TransferAction::Create { source, dest, len },
+        Existing::File => TransferAction::Overwrite { source, dest, len },
+        Existing::Directory => TransferAction::Skip {
+            path: dest,
+            reason: "a directory with the same name exists",
+        },
+    }
+}
+
+/// Return the action that creates the directory `dest`, or `None` if it already exists.
+///
+/// If a file is in the way, the directory and its contents are skipped.
+fn plan_directory(dest: &Path, existing: Existing) -> Option<TransferAction> {
+    match existing {
+        Existing::Nothing => Some(TransferAction::CreateDirectory {
+            dest: dest.to_path_buf(),
+        }),
+        Existing::File => Some(TransferAction::Skip {
+            path: dest.to_path_buf(),
+            reason: "a file with the same name exists",
+        }),
+        Existing::Directory => None,
+    }
+}
+
+/// Return the verb that describes copying files from `source_fs` to `dest_fs`.
+pub fn transfer_verb(
+    source_fs: &(impl FileSystem + ?Sized),
+    dest_fs: &(impl FileSystem + ?Sized),
+) -> &'static str {
+    match (source_fs.is_remote(), dest_fs.is_remote()) {
+        (true, false) => "Downloading",
+        (false, true) => "Uploading",
+        _ => "Copying",
+    }
+}
+
+/// Return the actions that copy the entry `source` of `source_fs` to the path `dest` of
+/// `dest_fs`.
+///
+/// If `source` is a directory, its contents are planned to be copied into `dest` except
+/// for the entries that are excluded by `filter`. Nothing is written to `dest_fs`.
+pub fn plan_transfer<S, D>(
+    source_fs: &S,
+    source: S::Entry,
+    dest_fs: &D,
+    dest: PathBuf,
+    filter: &Filter,
+) -> Result<Vec<TransferAction>>
+where
+    S: FileSystem + ?Sized,
+    D: FileSystem + ?Sized,
+{
+    let mut tree_filter = filter.for_tree(source.path());
+    let mut actions = vec![];
+    // Traverse the source directory in depth-first order. The entries of a directory that
+    // will be created cannot exist yet, so they are not checked.
+    let mut job_queue = VecDeque::from(vec![(source, dest, false)]);
+
+    while let Some((source, dest, is_new)) = job_queue.pop_front() {
+        let existing = if is_new {
+            Existing::Nothing
+        } else {
+            Existing::at(dest_fs, &dest)?
+        };
+        if source.is_parent() {
+            let path = source.path().to_string_lossy().to_string();
+            return Err(if dest_fs.is_remote() {
+                ErrorKind::CannotUploadParent(path)
+            } else {
+                ErrorKind::CannotDownloadParent(path)
+            });
+        } else if source.is_file() {
+            let len = source.len().unwrap_or(0);
+            actions.push(plan_file(source.path(), dest, len, existing));
+        } else if source.is_dir() {
+            if let Some(action) = plan_directory(&dest, existing) {
+                let is_skipped = !action.is_change();
+                actions.push(action);
+                if is_skipped {
+                    continue;
+                }
+            }
+            let source_children = tree_filter.filter_entries(
+                source.path(),
+                source_fs.read_dir(source.path())?,
+                |path| source_fs.read_to_string(path),
+            )?;
+            let is_new = existing == Existing::Nothing;
+            job_queue.extend(source_children.into_iter().map(|source_child| {
+                let dest_child = dest.join(source_child.path().file_name().unwrap());
+                (source_child, dest_child, is_new)
+            }));
+        } else {
+            actions.push(TransferAction::Skip {
+                path: source.path().to_path_buf(),
+                reason: "it might be a symlink",
+            });
+        }
+    }
+
+    Ok(actions)
+}
+
+/// Copy the files that were planned by `plan_transfer` from `source_fs` to `dest_fs`.
+pub fn apply_transfer(
+    source_fs: &(impl FileSystem + ?Sized),
+    dest_fs: &(impl FileSystem + ?Sized),
+    actions: &[TransferAction],
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let verb = transfer_verb(source_fs, dest_fs);
+    for action in actions {
+        if listener.is_cancelled() {
+            return Err(ErrorKind::Cancelled);
+        }
+        match action {
+            TransferAction::CreateDirectory { dest } => dest_fs.mkdir(dest)?,
+            TransferAction::Create { source, dest, len }
+            | TransferAction::Overwrite { source, dest, len } => {
+                let progress = {
+                    let title = format!(
+                        "{} \"{}\"",
+                        verb,
+                        source.file_name().unwrap_or_default().to_string_lossy()
+                    );
+                    Arc::new(ProgressFile::new(&title, *len))
+                };
+                listener.file_started(Arc::clone(&progress));
+                copy_file_with_retries(source_fs, source, dest_fs, dest, &progress, listener)?;
+                listener.file_finished(&progress);
+            }
+            TransferAction::Skip { path, reason } => listener.warn(&format!(
+                "Warning: Skipping {} because {}.",
+                path.display(),
+                reason
+            )),
+        }
+    }
+    Ok(())
+}
+
+/// Copy the file `source` of `source_fs` to `dest` of `dest_fs`, creating or truncating
+/// `dest`, and track the bytes that were copied in `progress`.
+///
+/// If `progress` or `listener` is cancelled, the partial `dest` is removed so that it is not
+/// mistaken for a complete file.
+pub fn copy_file(
+    source_fs: &(impl FileSystem + ?Sized),
+    source: &Path,
+    dest_fs: &(impl FileSystem + ?Sized),
+    dest: &Path,
+    progress: &ProgressFile,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let reader = source_fs.open_read(source)?;
+    let writer = dest_fs.open_write(dest)?;
+    copy_chunks(reader, writer, dest_fs, dest, progress, listener)
+}
+
+/// Copy the file like `copy_file`, but when the connection of either file system is lost,
+/// wait and resume from the bytes that were already copied, up to `MAX_RETRIES` times.
+pub fn copy_file_with_retries(
+    source_fs: &(impl FileSystem + ?Sized),
+    source: &Path,
+    dest_fs: &(impl FileSystem + ?Sized),
+    dest: &Path,
+    progress: &ProgressFile,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let mut result = copy_file(source_fs, source, dest_fs, dest, progress, listener);
+    let mut delay = FIRST_RETRY_DELAY;
+    for _ in 0..MAX_RETRIES {
+        match &result {
+            Err(error)
+                if source_fs.is_connection_lost(error) || dest_fs.is_connection_lost(error) => {}
+            _ => break,
+        }
+        listener.warn(&format!(
+            "Warning: The connection was lost while copying {}, retrying in {} s.",
+            source.display(),
+            delay.as_secs()
+        ));
+        wait_unless_cancelled(delay, progress, listener)?;
+        delay *= 2;
+        result = resume_file(source_fs, source, dest_fs, dest, progress, listener);
+    }
+    result
+}
+
+/// Copy the rest of the file `source` to the end of the partial file `dest`, or all of it if
+/// `dest` cannot be appended to.
+fn resume_file(
+    source_fs: &(impl FileSystem + ?Sized),
+    source: &Path,
+    dest_fs: &(impl FileSystem + ?Sized),
+    dest: &Path,
+    progress: &ProgressFile,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let offset = dest_fs
+        .stat(dest)?
+        .and_then(|entry| entry.len())
+        .filter(|len| *len <= progress.get_total_bytes())
+        .unwrap_or(0);
+    if offset > 0 {
+        if let Ok(writer) = dest_fs.open_append(dest) {
+            let reader = source_fs.open_read_at(source, offset)?;
+            progress.reset(offset);
+            return copy_chunks(reader, writer, dest_fs, dest, progress, listener);
+        }
+    }
+    progress.reset(0);
+    copy_file(source_fs, source, dest_fs, dest, progress, listener)
+}
+
+/// Sleep for `delay`, or return `ErrorKind::Cancelled` as soon as the transfer is cancelled.
+fn wait_unless_cancelled(
+    delay: Duration,
+    progress: &ProgressFile,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let start = Instant::now();
+    while start.elapsed() < delay {
+        if progress.is_cancelled() || listener.is_cancelled() {
+            return Err(ErrorKind::Cancelled);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+/// Copy everything from `reader` to `writer`, which writes to `dest` of `dest_fs`.
+fn copy_chunks(
+    mut reader: Box<dyn Read + '_>,
+    mut writer: Box<dyn Write + '_>,
+    dest_fs: &(impl FileSystem + ?Sized),
+    dest: &Path,
+    progress: &ProgressFile,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    let mut buffer = [0; CHUNK_SIZE];
+
+    loop {
+        if progress.is_cancelled() || listener.is_cancelled() {
+            drop(writer);
+            dest_fs.remove(dest)?;
+            return Err(ErrorKind::Cancelled);
+        }
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        } else {
+            writer.write_all(&buffer[..bytes_read])?;
+            progress.inc(bytes_read as u64);
+            listener.progress(progress);
+        }
+    }
+    writer.flush()?;
+
+    progress.finish();
+    Ok(())
+}
+
+/// Copy the entry `source` of `source_fs` to the path `dest` of `dest_fs`, which must not
+/// exist.
+///
+/// If `source` is a directory, its contents are copied into the new directory `dest`
+/// except for the entries that are excluded by `filter`.
+pub fn transfer_recursive<S, D>(
+    source_fs: &S,
+    source: S::Entry,
+    dest_fs: &D,
+    dest: PathBuf,
+    filter: &Filter,
+    listener: &impl TransferListener,
+) -> Result<()>
+where
+    S: FileSystem + ?Sized,
+    D: FileSystem + ?Sized,
+{
+    if dest_fs.stat(&dest)?.is_some() {
+        let path = dest.to_string_lossy().to_string();
+        return Err(if dest_fs.is_remote() {
+            ErrorKind::RemoteFileExists(path)
+        } else {
+            ErrorKind::LocalFileExists(path)
+        });
+    }
+    let actions = plan_transfer(source_fs, source, dest_fs, dest, filter)?;
+    apply_transfer(source_fs, dest_fs, &actions, listener)
+}
+
+/// Download the remote entry `source` to the local path `dest`, which must not exist.
+pub fn download_recursive(
+    source: RemoteFileEntry,
+    dest: PathBuf,
+    sftp: &ssh2::Sftp,
+    filter: &Filter,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    transfer_recursive(sftp, source, &LocalFileSystem, dest, filter, listener)
+}
+
+/// Upload the local entry `source` to the remote path `dest`, which must not exist.
+pub fn upload_recursive(
+    source: LocalFileEntry,
+    dest: PathBuf,
+    sftp: &ssh2::Sftp,
+    filter: &Filter,
+    listener: &impl TransferListener,
+) -> Result<()> {
+    transfer_recursive(&LocalFileSystem, source, sftp, dest, filter, listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_file() {
+        let source = Path::new("/src/a.txt");
+        let dest = PathBuf::from("/dst/a.txt");
+        assert_eq!(
+            plan_file(source, dest.clone(), 3, Existing::Nothing).describe(),
+            "create    /dst/a.txt (3 B)"
+        );
+        assert_eq!(
+            plan_file(source, dest.clone(), 3, Existing::File).describe(),
+            "overwrite /dst/a.txt (3 B)"
+        );
+        let skip = plan_file(source, dest, 3, Existing::Directory);
+        assert!(!skip.is_change());
+        assert_eq!(skip.bytes(), 0);
+        assert_eq!(
+            plan_directory(Path::new("/dst/b"), Existing::File),
+            Some(TransferAction::Skip {
+                path: PathBuf::from("/dst/b"),
+                reason: "a file with the same name exists",
+            })
+        );
+        assert_eq!(
+            plan_directory(Path::new("/dst/b"), Existing::Directory),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transfer_recursive() {
+        let fs = LocalFileSystem;
+        let root = std::env::temp_dir().join(format!("rftp-test-transfer-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src/sub")).unwrap();
+        std::fs::write(root.join("src/a.txt"), "abc").unwrap();
+        std::fs::write(root.join("src/sub/b.txt"), "defg").unwrap();
+        std::fs::create_dir(root.join("dst")).unwrap();
+        std::fs::write(root.join("dst/a.txt"), "old").unwrap();
+
+        let source = fs.stat(&root.join("src")).unwrap().unwrap();
+        let actions = plan_transfer(
+            &fs,
+            source.clone(),
+            &fs,
+            root.join("dst"),
+            &Filter::default(),
+        )
+        .unwrap();
+        let mut names: Vec<_> = actions.iter().map(|action| action.name()).collect();
+        names.sort();
+        assert_eq!(names, vec!["create", "mkdir", "overwrite"]);
+        assert_eq!(actions.iter().map(|action| action.bytes()).sum::<u64>(), 7);
+
+        let result = transfer_recursive(
+            &fs,
+            source.clone(),
+            &fs,
+            root.join("dst"),
+            &Filter::default(),
+            &(),
+        );
+        assert!(matches!(result, Err(ErrorKind::LocalFileExists(_))));
+
+        transfer_recursive(&fs, source, &fs, root.join("copy"), &Filter::default(), &()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("copy/sub/b.txt")).unwrap(),
+            "defg"
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
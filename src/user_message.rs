@@ -1,6 +1,11 @@
+use rftp::utils::{timestamp_to_string, Result};
+
 use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
 use std::sync::Mutex;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use tui::{
     layout::{Constraint, Direction, Layout},
@@ -14,32 +19,126 @@ const NUM_MAX_MESSAGES: u16 = 5;
 /// The max age of any message.
 const MAX_MESSAGE_AGE: Duration = Duration::from_secs(10);
 
+/// How important a message is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Return the name of this severity.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Return the style that messages of this severity are drawn with.
+    pub fn style(&self) -> Style {
+        match self {
+            Severity::Info => Style::default(),
+            Severity::Warning => Style::default().fg(Color::Yellow),
+            Severity::Error => Style::default().fg(Color::Red),
+        }
+    }
+}
+
+/// A message in the log of the session.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub time: SystemTime,
+    pub severity: Severity,
+    /// The title of the transfer that reported this message, if any.
+    pub source: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:7}",
+            timestamp_to_string(self.time),
+            self.severity.name()
+        )?;
+        if let Some(source) = &self.source {
+            write!(f, " [{}]", source)?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
 pub struct UserMessage {
     messages: Mutex<VecDeque<(Instant, String, Style)>>,
+    /// Every message of this session, which is never truncated.
+    log: Mutex<Vec<LogEntry>>,
+    log_file: Mutex<Option<File>>,
 }
 
 impl UserMessage {
     pub fn new() -> Self {
         UserMessage {
             messages: Mutex::new(VecDeque::new()),
+            log: Mutex::new(vec![]),
+            log_file: Mutex::new(None),
         }
     }
 
+    /// Append every message from now on to the file at `path`.
+    pub fn set_log_file(&self, path: &Path) -> Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *self.log_file.lock().unwrap() = Some(file);
+        Ok(())
+    }
+
     /// Report a message to the user that will last for `MAX_MESSAGE_AGE`.
     ///
     /// Messages are pushed to a queue with a max size of `NUM_MAX_MESSAGES`.
     pub fn report(&self, message: &str) {
-        self.report_with_style(message, Style::default());
+        self.log(Severity::Info, None, message);
     }
 
     /// Report a message to the user that will last for `MAX_MESSAGE_AGE`.
     pub fn warn(&self, message: &str) {
-        self.report_with_style(message, Style::default().fg(Color::Yellow));
+        self.log(Severity::Warning, None, message);
     }
 
     /// Report a message to the user that will last for `MAX_MESSAGE_AGE`.
     pub fn error(&self, message: &str) {
-        self.report_with_style(message, Style::default().fg(Color::Red));
+        self.log(Severity::Error, None, message);
+    }
+
+    /// Report a message from the transfer titled `source` that will last for
+    /// `MAX_MESSAGE_AGE` and add it to the log.
+    pub fn log(&self, severity: Severity, source: Option<&str>, message: &str) {
+        self.report_with_style(message, severity.style());
+
+        let entry = LogEntry {
+            time: SystemTime::now(),
+            severity,
+            source: source.map(str::to_string),
+            message: message.to_string(),
+        };
+        if let Some(file) = self.log_file.lock().unwrap().as_mut() {
+            // The log file is only a copy of the log, so failing to write it is not fatal.
+            writeln!(file, "{}", entry).ok();
+        }
+        self.log.lock().unwrap().push(entry);
+    }
+
+    /// Return every message of this session that is at least as severe as `min_severity`.
+    pub fn get_log(&self, min_severity: Severity) -> Vec<LogEntry> {
+        self.log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.severity >= min_severity)
+            .cloned()
+            .collect()
     }
 
     /// Report a message to the user that will last for `MAX_MESSAGE_AGE`.
@@ -153,4 +252,57 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_log() {
+        let path = std::env::temp_dir().join(format!("rftp-log-{}.log", std::process::id()));
+        std::fs::remove_file(&path).ok();
+
+        let message = UserMessage::new();
+        message.report("Not in the file.");
+        message.set_log_file(&path).unwrap();
+        for i in 0..NUM_MAX_MESSAGES {
+            message.report(&format!("Message {}.", i));
+        }
+        message.log(
+            Severity::Warning,
+            Some("Uploading \"a\""),
+            "Skipping symlink.",
+        );
+        message.error("Error: oops.");
+
+        // The log keeps every message, not only the recent ones.
+        assert_eq!(message.get_log(Severity::Info).len(), 8);
+        let errors: Vec<_> = message
+            .get_log(Severity::Warning)
+            .into_iter()
+            .map(|entry| (entry.source, entry.message))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (
+                    Some("Uploading \"a\"".to_string()),
+                    "Skipping symlink.".to_string()
+                ),
+                (None, "Error: oops.".to_string()),
+            ]
+        );
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<_> = contents.lines().map(|line| &line[21..]).collect();
+        assert_eq!(
+            lines,
+            [
+                "info    Message 0.",
+                "info    Message 1.",
+                "info    Message 2.",
+                "info    Message 3.",
+                "info    Message 4.",
+                "warning [Uploading \"a\"] Skipping symlink.",
+                "error   Error: oops.",
+            ]
+        );
+    }
 }
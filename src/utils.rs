@@ -1,6 +1,6 @@
 use std::io::Read;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tui::buffer::Buffer;
 
@@ -20,9 +20,18 @@ pub enum ErrorKind {
     #[error("unable to parse port number")]
     InvalidPortNumber,
 
+    #[error("invalid number \"{0}\"")]
+    InvalidNumber(String),
+
+    #[error("none of the {0} \"{1}\" are supported")]
+    UnsupportedMethods(String, String),
+
     #[error("possible person in the middle attack")]
     MismatchedFingerprint,
 
+    #[error("the host key of {0} is revoked")]
+    RevokedHostKey(String),
+
     #[error("unable to find home directory")]
     UnableToFindHomeDirectory,
 
@@ -44,9 +53,42 @@ pub enum ErrorKind {
     #[error("remote file {0} already exists")]
     RemoteFileExists(String),
 
+    #[error("invalid remote path \"{0}\", expected [user@]host:path")]
+    InvalidRemotePath(String),
+
+    #[error("invalid destination \"{0}\", expected [user@]host[:port]")]
+    InvalidDestination(String),
+
+    #[error("{0} is a directory, use -r to transfer it recursively")]
+    IsDirectory(String),
+
+    #[error("the transfer was cancelled")]
+    Cancelled,
+
+    #[error("the editor {0}")]
+    EditorFailed(String),
+
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+
+    #[error("sync needs exactly one local path and one remote path")]
+    InvalidSyncPaths,
+
+    #[error("unexpected output from remote command \"{0}\"")]
+    UnexpectedCommandOutput(String),
+
+    #[error("invalid config file {0} on line {1}: {2}")]
+    InvalidConfig(String, usize, String),
+
+    #[error("remote command \"{0}\" failed: {1}")]
+    RemoteCommandFailed(String, String),
+
     #[error("channel closed with exit status {0}")]
     Ssh2ChannelClosed(i32),
 
+    #[error("the connection to {0} was lost and is being restored")]
+    Reconnecting(String),
+
     #[error(transparent)]
     Ssh2Error(#[from] ssh2::Error),
 
@@ -57,22 +99,198 @@ pub enum ErrorKind {
     VarError(#[from] std::env::VarError),
 }
 
+impl ErrorKind {
+    /// Return a short, stable name that identifies the kind of this error.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::UserAuthenticationError(_) => "user-authentication",
+            ErrorKind::HostAuthenticationError(_, _) => "host-authentication",
+            ErrorKind::HostFileCheckError => "host-file-check",
+            ErrorKind::InvalidPortNumber => "invalid-port-number",
+            ErrorKind::InvalidNumber(_) => "invalid-number",
+            ErrorKind::UnsupportedMethods(_, _) => "unsupported-methods",
+            ErrorKind::MismatchedFingerprint => "mismatched-fingerprint",
+            ErrorKind::RevokedHostKey(_) => "revoked-host-key",
+            ErrorKind::UnableToFindHomeDirectory => "home-directory-not-found",
+            ErrorKind::HostKeyNotFound => "host-key-not-found",
+            ErrorKind::HostFingerprintNotFound => "host-fingerprint-not-found",
+            ErrorKind::CannotDownloadParent(_) => "cannot-download-parent",
+            ErrorKind::CannotUploadParent(_) => "cannot-upload-parent",
+            ErrorKind::LocalFileExists(_) => "local-file-exists",
+            ErrorKind::RemoteFileExists(_) => "remote-file-exists",
+            ErrorKind::InvalidRemotePath(_) => "invalid-remote-path",
+            ErrorKind::InvalidDestination(_) => "invalid-destination",
+            ErrorKind::IsDirectory(_) => "is-directory",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::EditorFailed(_) => "editor-failed",
+            ErrorKind::NotADirectory(_) => "not-a-directory",
+            ErrorKind::InvalidSyncPaths => "invalid-sync-paths",
+            ErrorKind::UnexpectedCommandOutput(_) => "unexpected-command-output",
+            ErrorKind::InvalidConfig(_, _, _) => "invalid-config",
+            ErrorKind::RemoteCommandFailed(_, _) => "remote-command-failed",
+            ErrorKind::Ssh2ChannelClosed(_) => "channel-closed",
+            ErrorKind::Reconnecting(_) => "reconnecting",
+            ErrorKind::Ssh2Error(_) => "ssh",
+            ErrorKind::IOError(_) => "io",
+            ErrorKind::VarError(_) => "environment-variable",
+        }
+    }
+
+    /// Return the status that the process should exit with when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::InvalidPortNumber
+            | ErrorKind::InvalidNumber(_)
+            | ErrorKind::UnsupportedMethods(_, _)
+            | ErrorKind::InvalidRemotePath(_)
+            | ErrorKind::InvalidDestination(_)
+            | ErrorKind::IsDirectory(_)
+            | ErrorKind::NotADirectory(_)
+            | ErrorKind::InvalidSyncPaths
+            | ErrorKind::InvalidConfig(_, _, _)
+            | ErrorKind::VarError(_) => 2,
+            ErrorKind::UserAuthenticationError(_) => 3,
+            ErrorKind::HostAuthenticationError(_, _)
+            | ErrorKind::HostFileCheckError
+            | ErrorKind::MismatchedFingerprint
+            | ErrorKind::RevokedHostKey(_)
+            | ErrorKind::HostKeyNotFound
+            | ErrorKind::HostFingerprintNotFound => 4,
+            ErrorKind::LocalFileExists(_) | ErrorKind::RemoteFileExists(_) => 5,
+            ErrorKind::CannotDownloadParent(_) | ErrorKind::CannotUploadParent(_) => 6,
+            ErrorKind::Ssh2ChannelClosed(_)
+            | ErrorKind::Reconnecting(_)
+            | ErrorKind::UnexpectedCommandOutput(_)
+            | ErrorKind::RemoteCommandFailed(_, _)
+            | ErrorKind::Ssh2Error(_) => 7,
+            ErrorKind::IOError(_) => 8,
+            ErrorKind::UnableToFindHomeDirectory
+            | ErrorKind::Cancelled
+            | ErrorKind::EditorFailed(_) => 1,
+        }
+    }
+
+    /// Return true if this error means that the connection to the host was lost or stopped
+    /// answering.
+    pub fn is_disconnect(&self) -> bool {
+        match self {
+            ErrorKind::Ssh2Error(error) => matches!(
+                error.code(),
+                ssh2::ErrorCode::Session(
+                    libssh2_sys::LIBSSH2_ERROR_SOCKET_SEND
+                        | libssh2_sys::LIBSSH2_ERROR_SOCKET_RECV
+                        | libssh2_sys::LIBSSH2_ERROR_SOCKET_DISCONNECT
+                        | libssh2_sys::LIBSSH2_ERROR_SOCKET_TIMEOUT
+                        | libssh2_sys::LIBSSH2_ERROR_TIMEOUT
+                )
+            ),
+            ErrorKind::IOError(error) => matches!(
+                error.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            ErrorKind::Reconnecting(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Return true if this error could mean that the connection was lost, which only asking
+    /// the server tells for sure. An answer of the server like "no such file" cannot.
+    pub fn may_be_disconnect(&self) -> bool {
+        match self {
+            ErrorKind::Ssh2Error(error) => match error.code() {
+                ssh2::ErrorCode::Session(_) => true,
+                ssh2::ErrorCode::SFTP(code) => matches!(
+                    code,
+                    libssh2_sys::LIBSSH2_FX_NO_CONNECTION | libssh2_sys::LIBSSH2_FX_CONNECTION_LOST
+                ),
+            },
+            ErrorKind::IOError(error) => matches!(
+                error.kind(),
+                std::io::ErrorKind::Other
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::Interrupted
+            ),
+            error => error.is_disconnect(),
+        }
+    }
+}
+
 /// Return the path to the host home directory.
 pub fn get_remote_home_dir(session: &ssh2::Session) -> Result<PathBuf> {
+    let result = run_remote_command(session, "pwd")?;
+    Ok(PathBuf::from(result.trim()))
+}
+
+/// Run `command` on the remote host and return what it printed to stdout.
+pub fn run_remote_command(session: &ssh2::Session, command: &str) -> Result<String> {
     let mut channel = session.channel_session()?;
-    channel.exec("pwd")?;
+    channel.exec(command)?;
     let mut result = String::new();
     channel.read_to_string(&mut result)?;
-    let result = result.trim();
     channel.wait_close()?;
     let exit_status = channel.exit_status()?;
     if exit_status == 0 {
-        Ok(PathBuf::from(result))
+        Ok(result)
     } else {
         Err(ErrorKind::Ssh2ChannelClosed(exit_status))
     }
 }
 
+/// What a remote command printed and how it exited.
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    /// What the command printed to stdout and stderr, in the order it arrived.
+    pub output: Vec<u8>,
+    pub exit_status: i32,
+}
+
+/// Run `command` on the remote host and return what it printed and its exit status, even if
+/// it failed.
+pub fn run_remote_command_output(session: &ssh2::Session, command: &str) -> Result<CommandOutput> {
+    let mut channel = session.channel_session()?;
+    // Reading stdout to the end would hang once a command fills the window with stderr.
+    channel.handle_extended_data(ssh2::ExtendedData::Merge)?;
+    channel.exec(command)?;
+    let mut output = CommandOutput::default();
+    channel.read_to_end(&mut output.output)?;
+    channel.wait_close()?;
+    output.exit_status = channel.exit_status()?;
+    Ok(output)
+}
+
+/// Copy `from` and everything below it to `to` on the remote host with `cp`, which keeps
+/// the data on the server.
+///
+/// libssh2 cannot send the sftp `copy-data` extension, so this is the fast way to copy.
+/// Return false if the host has no `cp`, so that the caller can stream the copy instead.
+pub fn remote_copy(session: &ssh2::Session, from: &Path, to: &Path) -> Result<bool> {
+    let command = format!(
+        "cp -R -p -- {} {}",
+        shell_quote(&from.to_string_lossy()),
+        shell_quote(&to.to_string_lossy())
+    );
+    let output = run_remote_command_output(session, &command)?;
+    match output.exit_status {
+        0 => Ok(true),
+        // The shell could not find or run `cp`.
+        126 | 127 => Ok(false),
+        _ => Err(ErrorKind::RemoteCommandFailed(
+            command,
+            String::from_utf8_lossy(&output.output).trim().to_string(),
+        )),
+    }
+}
+
+/// Quote `s` so that a POSIX shell treats it as a single word.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
 /// Returns a `String` that represents a `Duration` in hours, minutes, and seconds.
 pub fn duration_to_string(t: Duration) -> String {
     let seconds = t.as_secs();
@@ -85,6 +303,36 @@ pub fn duration_to_string(t: Duration) -> String {
     }
 }
 
+/// Returns a `String` that represents a point in time in UTC, like `2021-03-04T05:06:07Z`.
+pub fn timestamp_to_string(t: SystemTime) -> String {
+    let seconds = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Convert the days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
 /// Returns a `String` that represents a bitrate.
 pub fn bitrate_to_string(rate: u64) -> String {
     if rate < 1_000 {
@@ -110,6 +358,44 @@ pub fn bytes_to_string(num_bytes: u64) -> String {
     }
 }
 
+/// Returns a `String` that represents the type and permission bits of `mode` like `ls -l`,
+/// such as `drwxr-xr-x`.
+pub fn mode_to_string(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+    let mut string = String::from(kind);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = mode >> shift;
+        string.push(if bits & 4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+/// Parse permission bits written in octal like `chmod`, such as `755` or `0644`.
+pub fn parse_mode(s: &str) -> Option<u32> {
+    let s = s.trim();
+    if s.is_empty() || s.len() > 5 {
+        return None;
+    }
+    u32::from_str_radix(s, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
+
 #[allow(dead_code)]
 pub fn assert_buffer_symbols_eq(left: &Buffer, right: &Buffer) {
     assert_eq!(left.area, right.area);
@@ -151,6 +437,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp_to_string(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            timestamp_to_string(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3723)),
+            "2000-02-29T01:02:03Z"
+        );
+        assert_eq!(
+            timestamp_to_string(UNIX_EPOCH + Duration::from_secs(1_614_834_367)),
+            "2021-03-04T05:06:07Z"
+        );
+    }
+
     #[test]
     fn test_bitrate() {
         assert_eq!(bitrate_to_string(4), "4 bit/s".to_string());
@@ -159,6 +458,67 @@ mod tests {
         assert_eq!(bitrate_to_string(7e9 as u64), "7.0 Gbit/s".to_string());
     }
 
+    #[test]
+    fn test_is_disconnect() {
+        let timeout = ssh2::Error::new(
+            ssh2::ErrorCode::Session(libssh2_sys::LIBSSH2_ERROR_TIMEOUT),
+            "Timed out waiting on socket",
+        );
+        assert!(ErrorKind::from(timeout).is_disconnect());
+        let denied = ssh2::Error::new(
+            ssh2::ErrorCode::SFTP(libssh2_sys::LIBSSH2_FX_PERMISSION_DENIED),
+            "permission denied",
+        );
+        assert!(!ErrorKind::from(denied).is_disconnect());
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(ErrorKind::from(reset).is_disconnect());
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(!ErrorKind::from(missing).is_disconnect());
+        assert!(!ErrorKind::Cancelled.is_disconnect());
+        assert!(ErrorKind::Reconnecting("example.com".to_string()).is_disconnect());
+    }
+
+    #[test]
+    fn test_may_be_disconnect() {
+        let closed = ssh2::Error::new(
+            ssh2::ErrorCode::Session(libssh2_sys::LIBSSH2_ERROR_CHANNEL_CLOSED),
+            "channel closed",
+        );
+        assert!(ErrorKind::from(closed).may_be_disconnect());
+        let denied = ssh2::Error::new(
+            ssh2::ErrorCode::SFTP(libssh2_sys::LIBSSH2_FX_PERMISSION_DENIED),
+            "permission denied",
+        );
+        assert!(!ErrorKind::from(denied).may_be_disconnect());
+        let other = std::io::Error::other("unknown error");
+        assert!(ErrorKind::from(other).may_be_disconnect());
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(!ErrorKind::from(missing).may_be_disconnect());
+        assert!(!ErrorKind::Cancelled.may_be_disconnect());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("a b"), "'a b'".to_string());
+        assert_eq!(shell_quote("it's"), "'it'\\''s'".to_string());
+    }
+
+    #[test]
+    fn test_mode() {
+        assert_eq!(mode_to_string(0o100644), "-rw-r--r--".to_string());
+        assert_eq!(mode_to_string(0o040755), "drwxr-xr-x".to_string());
+        assert_eq!(mode_to_string(0o120777), "lrwxrwxrwx".to_string());
+        assert_eq!(mode_to_string(0o104755), "-rwsr-xr-x".to_string());
+        assert_eq!(mode_to_string(0o041770), "drwxrwx--T".to_string());
+        assert_eq!(parse_mode("755"), Some(0o755));
+        assert_eq!(parse_mode(" 0644 "), Some(0o644));
+        assert_eq!(parse_mode("4755"), Some(0o4755));
+        assert_eq!(parse_mode("788"), None);
+        assert_eq!(parse_mode("17777"), None);
+        assert_eq!(parse_mode(""), None);
+        assert_eq!(parse_mode("u+x"), None);
+    }
+
     #[test]
     fn test_bytes() {
         assert_eq!(bytes_to_string(849), "849 B".to_string());
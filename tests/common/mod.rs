@@ -0,0 +1,45 @@
+//! Helpers for testing transfers without a server.
+
+#![allow(dead_code)]
+
+#[cfg(feature = "e2e-tests")]
+pub mod sshd;
+
+use rftp::progress::ProgressFile;
+use rftp::TransferListener;
+
+use std::sync::{Arc, Mutex};
+
+/// Records every update of a transfer.
+#[derive(Default)]
+pub struct RecordingListener {
+    pub finished: Mutex<Vec<String>>,
+    pub warnings: Mutex<Vec<String>>,
+    pub progress_updates: Mutex<usize>,
+    /// Cancel the transfer once there were this many progress updates.
+    pub cancel_after: Option<usize>,
+}
+
+impl TransferListener for RecordingListener {
+    fn file_started(&self, _progress: Arc<ProgressFile>) {}
+
+    fn file_finished(&self, progress: &ProgressFile) {
+        self.finished
+            .lock()
+            .unwrap()
+            .push(progress.get_title().to_string());
+    }
+
+    fn progress(&self, _progress: &ProgressFile) {
+        *self.progress_updates.lock().unwrap() += 1;
+    }
+
+    fn warn(&self, message: &str) {
+        self.warnings.lock().unwrap().push(message.to_string());
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_after
+            .is_some_and(|after| *self.progress_updates.lock().unwrap() >= after)
+    }
+}
@@ -0,0 +1,219 @@
+//! A throwaway OpenSSH server on a random localhost port.
+//!
+//! The server runs as the current user with a freshly generated host key and user key, so it
+//! needs no root access and no network. Set `RFTP_SSHD` to the path of `sshd` if it is not in
+//! one of the usual places.
+
+use rftp::ConnectOptions;
+
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Only one server runs at a time because the agent is found through the environment.
+static LOCK: Mutex<()> = Mutex::new(());
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A running `sshd` and `ssh-agent` that are killed when this is dropped.
+pub struct Sshd {
+    dir: PathBuf,
+    port: u16,
+    username: String,
+    sshd: Child,
+    agent: Child,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Sshd {
+    /// Start a server that accepts the generated user key, which is loaded into an agent.
+    pub fn start() -> Sshd {
+        Sshd::start_with_config("")
+    }
+
+    /// Start a server with `extra_config` appended to its `sshd_config`.
+    pub fn start_with_config(extra_config: &str) -> Sshd {
+        let lock = LOCK.lock().unwrap_or_else(|error| error.into_inner());
+        let sshd_path = sshd_path();
+        let dir = std::env::temp_dir().join(format!(
+            "rftp-e2e-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("files")).unwrap();
+
+        generate_key(&dir.join("host_key"));
+        generate_key(&dir.join("user_key"));
+        fs::copy(dir.join("user_key.pub"), dir.join("authorized_keys")).unwrap();
+        fs::write(dir.join("known_hosts"), "").unwrap();
+
+        let port = free_port();
+        let config = format!(
+            "Port {port}\n\
+             ListenAddress 127.0.0.1\n\
+             HostKey {dir}/host_key\n\
+             PidFile {dir}/sshd.pid\n\
+             AuthorizedKeysFile {dir}/authorized_keys\n\
+             StrictModes no\n\
+             UsePAM no\n\
+             Subsystem sftp internal-sftp\n\
+             {extra_config}\n",
+            port = port,
+            dir = dir.display(),
+            extra_config = extra_config,
+        );
+        fs::write(dir.join("sshd_config"), config).unwrap();
+
+        let sshd = Command::new(sshd_path)
+            .arg("-D")
+            .arg("-e")
+            .arg("-f")
+            .arg(dir.join("sshd_config"))
+            .stdout(Stdio::null())
+            .stderr(fs::File::create(dir.join("sshd.log")).unwrap())
+            .spawn()
+            .expect("failed to start sshd");
+
+        let agent_socket = dir.join("agent.sock");
+        let agent = Command::new("ssh-agent")
+            .arg("-D")
+            .arg("-a")
+            .arg(&agent_socket)
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("failed to start ssh-agent");
+        wait_for(|| agent_socket.exists());
+        let status = Command::new("ssh-add")
+            .arg(dir.join("user_key"))
+            .env("SSH_AUTH_SOCK", &agent_socket)
+            .stderr(Stdio::null())
+            .status()
+            .expect("failed to run ssh-add");
+        assert!(status.success(), "ssh-add failed");
+        std::env::set_var("SSH_AUTH_SOCK", &agent_socket);
+
+        wait_for(|| TcpStream::connect(("127.0.0.1", port)).is_ok());
+
+        Sshd {
+            dir,
+            port,
+            username: current_username(),
+            sshd,
+            agent,
+            _lock: lock,
+        }
+    }
+
+    /// Return the options that connect to this server and trust its host key.
+    pub fn connect_options(&self) -> ConnectOptions {
+        self.trust_host_key();
+        ConnectOptions {
+            port: Some(self.port),
+            known_hosts: Some(self.known_hosts()),
+            ..ConnectOptions::new(&self.username)
+        }
+    }
+
+    /// Return the known_hosts file that the options of this server use.
+    pub fn known_hosts(&self) -> PathBuf {
+        self.dir.join("known_hosts")
+    }
+
+    /// Write the host key of this server to its known_hosts file.
+    pub fn trust_host_key(&self) {
+        self.write_known_host(&self.dir.join("host_key.pub"));
+    }
+
+    /// Write the public key `key_path` to the known_hosts file as the key of this server.
+    pub fn write_known_host(&self, key_path: &Path) {
+        let key = fs::read_to_string(key_path).unwrap();
+        let mut fields = key.split_whitespace();
+        let line = format!(
+            "[127.0.0.1]:{} {} {}\n",
+            self.port,
+            fields.next().unwrap(),
+            fields.next().unwrap()
+        );
+        fs::write(self.known_hosts(), line).unwrap();
+    }
+
+    /// Return a directory that can be used for the files of a test, which the server can
+    /// read and write as well.
+    pub fn files(&self) -> PathBuf {
+        self.dir.join("files")
+    }
+
+    /// Return a directory that only holds files of this server.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Sshd {
+    fn drop(&mut self) {
+        self.sshd.kill().ok();
+        self.sshd.wait().ok();
+        self.agent.kill().ok();
+        self.agent.wait().ok();
+        if thread::panicking() {
+            if let Ok(log) = fs::read_to_string(self.dir.join("sshd.log")) {
+                eprintln!("sshd log:\n{}", log);
+            }
+        }
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// Generate an ed25519 key pair without a passphrase at `path` and `path.pub`.
+pub fn generate_key(path: &Path) {
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(path)
+        .status()
+        .expect("failed to run ssh-keygen");
+    assert!(status.success(), "ssh-keygen failed");
+}
+
+fn sshd_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("RFTP_SSHD") {
+        return PathBuf::from(path);
+    }
+    ["/usr/sbin/sshd", "/usr/local/sbin/sshd", "/usr/bin/sshd"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .expect("sshd was not found, set RFTP_SSHD to its path")
+}
+
+fn current_username() -> String {
+    let output = Command::new("id").arg("-un").output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Return a port on localhost that nothing listens on right now.
+fn free_port() -> u16 {
+    TcpListener::bind(("127.0.0.1", 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn wait_for(condition: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "timed out waiting for the server"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
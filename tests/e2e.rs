@@ -0,0 +1,340 @@
+//! End-to-end tests against a real `sshd`.
+//!
+//! Run them with `cargo test --features e2e-tests --test e2e`. They need `sshd`, `ssh-keygen`,
+//! `ssh-agent` and `ssh-add`, but no network.
+
+#![cfg(feature = "e2e-tests")]
+
+mod common;
+
+use common::sshd::{generate_key, Sshd};
+use rftp::connect::create_session;
+use rftp::connection::Connection;
+use rftp::known_hosts::StrictHostKeyChecking;
+use rftp::utils::remote_copy;
+use rftp::{Client, ConnectOptions, ErrorKind, FileEntry, FileSystem, Filter};
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_connect_with_agent() {
+    let sshd = Sshd::start();
+
+    let session = create_session("127.0.0.1", &sshd.connect_options()).unwrap();
+    assert!(session.authenticated());
+
+    let client = Client::connect("127.0.0.1", &sshd.connect_options()).unwrap();
+    assert!(client.home_dir().unwrap().is_absolute());
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn test_mismatched_host_key() {
+    let sshd = Sshd::start();
+    let options = sshd.connect_options();
+    let other_key = sshd.dir().join("other_key");
+    generate_key(&other_key);
+    sshd.write_known_host(&other_key.with_extension("pub"));
+
+    match create_session("127.0.0.1", &options) {
+        Err(ErrorKind::MismatchedFingerprint) => {}
+        result => panic!("expected a mismatched fingerprint, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn test_mismatched_host_key_without_checking() {
+    let sshd = Sshd::start_with_config("PubkeyAuthentication no\nPasswordAuthentication yes");
+    let options = ConnectOptions {
+        strict_host_key_checking: StrictHostKeyChecking::No,
+        password: Some("secret".to_string()),
+        ..sshd.connect_options()
+    };
+    let other_key = sshd.dir().join("other_key");
+    generate_key(&other_key);
+    sshd.write_known_host(&other_key.with_extension("pub"));
+
+    // The changed key is accepted, but the password is not sent to a server that may not be
+    // the host.
+    assert!(matches!(
+        create_session("127.0.0.1", &options),
+        Err(ErrorKind::UserAuthenticationError(_))
+    ));
+    let log = fs::read_to_string(sshd.dir().join("sshd.log")).unwrap();
+    assert!(!log.contains("Failed password"), "sshd log:\n{}", log);
+}
+
+#[test]
+fn test_mismatched_host_key_without_asking() {
+    let sshd = Sshd::start();
+    let options = ConnectOptions {
+        strict_host_key_checking: StrictHostKeyChecking::No,
+        interactive: false,
+        ..sshd.connect_options()
+    };
+    let other_key = sshd.dir().join("other_key");
+    generate_key(&other_key);
+    sshd.write_known_host(&other_key.with_extension("pub"));
+
+    // Connecting again in the background refuses a changed key even if checking is off.
+    match create_session("127.0.0.1", &options) {
+        Err(ErrorKind::MismatchedFingerprint) => {}
+        result => panic!("expected a mismatched fingerprint, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn test_missing_known_hosts_file() {
+    let sshd = Sshd::start();
+    let options = ConnectOptions {
+        known_hosts: Some(sshd.dir().join("missing")),
+        strict_host_key_checking: StrictHostKeyChecking::Yes,
+        ..sshd.connect_options()
+    };
+
+    assert!(matches!(
+        create_session("127.0.0.1", &options),
+        Err(ErrorKind::HostAuthenticationError(_, _))
+    ));
+    assert!(!sshd.dir().join("missing").exists());
+}
+
+#[test]
+fn test_unknown_host_key_without_asking() {
+    let sshd = Sshd::start();
+    let options = ConnectOptions {
+        known_hosts: Some(sshd.dir().join("missing")),
+        interactive: false,
+        ..sshd.connect_options()
+    };
+
+    // The default of asking the user fails instead of waiting for an answer.
+    assert!(matches!(
+        create_session("127.0.0.1", &options),
+        Err(ErrorKind::HostAuthenticationError(_, _))
+    ));
+    assert!(!sshd.dir().join("missing").exists());
+}
+
+#[test]
+fn test_accept_new_host_key() {
+    let sshd = Sshd::start();
+    let known_hosts = sshd.dir().join("ssh").join("known_hosts");
+    let options = ConnectOptions {
+        known_hosts: Some(known_hosts.clone()),
+        strict_host_key_checking: StrictHostKeyChecking::AcceptNew,
+        hash_known_hosts: true,
+        ..sshd.connect_options()
+    };
+    create_session("127.0.0.1", &options).unwrap();
+    let contents = fs::read_to_string(&known_hosts).unwrap();
+    assert!(contents.starts_with("|1|"));
+    assert!(!contents.contains("127.0.0.1"));
+
+    // The hashed `[127.0.0.1]:port` entry is found without asking again.
+    let options = ConnectOptions {
+        strict_host_key_checking: StrictHostKeyChecking::Yes,
+        ..options
+    };
+    create_session("127.0.0.1", &options).unwrap();
+    assert_eq!(fs::read_to_string(&known_hosts).unwrap(), contents);
+}
+
+#[test]
+fn test_revoked_host_key() {
+    let sshd = Sshd::start();
+    let options = sshd.connect_options();
+    let key = fs::read_to_string(sshd.dir().join("host_key.pub")).unwrap();
+    let mut known_hosts = fs::read_to_string(sshd.known_hosts()).unwrap();
+    known_hosts.push_str(&format!("@revoked * {}", key));
+    fs::write(sshd.known_hosts(), known_hosts).unwrap();
+
+    assert!(matches!(
+        create_session("127.0.0.1", &options),
+        Err(ErrorKind::RevokedHostKey(_))
+    ));
+}
+
+#[test]
+fn test_wrong_password() {
+    let sshd = Sshd::start_with_config("PubkeyAuthentication no\nPasswordAuthentication yes");
+    let options = ConnectOptions {
+        password: Some("not the password".to_string()),
+        ..sshd.connect_options()
+    };
+
+    match create_session("127.0.0.1", &options) {
+        Err(error @ ErrorKind::UserAuthenticationError(_)) => assert_eq!(error.exit_code(), 3),
+        result => panic!("expected the login to fail, got {:?}", result.err()),
+    }
+}
+
+#[test]
+fn test_put_and_get_recursive() {
+    let sshd = Sshd::start();
+    let client = Client::connect("127.0.0.1", &sshd.connect_options()).unwrap();
+    let files = sshd.files();
+    fs::create_dir_all(files.join("local/src/nested")).unwrap();
+    fs::write(files.join("local/src/a.txt"), "alpha").unwrap();
+    fs::write(files.join("local/src/nested/b.txt"), "beta".repeat(10_000)).unwrap();
+    fs::write(files.join("local/src/debug.log"), "noise").unwrap();
+    let mut filter = Filter::default();
+    filter.exclude("*.log");
+
+    client
+        .put_recursive(files.join("local/src"), files.join("remote"), &filter, &())
+        .unwrap();
+    let mut names: Vec<_> = client
+        .list_dir(files.join("remote"))
+        .unwrap()
+        .iter()
+        .map(|entry| entry.file_name_lossy().unwrap().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.txt", "nested"]);
+
+    client
+        .get_recursive(
+            files.join("remote"),
+            files.join("downloaded"),
+            &Filter::default(),
+            &(),
+        )
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(files.join("downloaded/nested/b.txt")).unwrap(),
+        "beta".repeat(10_000)
+    );
+
+    match client.put(
+        files.join("local/src/a.txt"),
+        files.join("remote/a.txt"),
+        &(),
+    ) {
+        Err(ErrorKind::RemoteFileExists(_)) => {}
+        result => panic!("expected the file to exist, got {:?}", result.err()),
+    }
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn test_copy_and_move_on_server() {
+    let sshd = Sshd::start();
+    let client = Client::connect("127.0.0.1", &sshd.connect_options()).unwrap();
+    let files = sshd.files();
+    fs::create_dir_all(files.join("remote/dir")).unwrap();
+    fs::write(files.join("remote/dir/a.txt"), "alpha").unwrap();
+
+    assert!(remote_copy(
+        client.session(),
+        &files.join("remote/dir"),
+        &files.join("cp")
+    )
+    .unwrap());
+    assert_eq!(fs::read_to_string(files.join("cp/a.txt")).unwrap(), "alpha");
+
+    client
+        .sftp()
+        .copy_all(&files.join("remote/dir"), &files.join("streamed"))
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(files.join("streamed/a.txt")).unwrap(),
+        "alpha"
+    );
+
+    FileSystem::rename(client.sftp(), &files.join("streamed"), &files.join("moved")).unwrap();
+    assert!(!files.join("streamed").exists());
+    assert_eq!(
+        fs::read_to_string(files.join("moved/a.txt")).unwrap(),
+        "alpha"
+    );
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn test_reconnect_after_lost_connection() {
+    let sshd = Sshd::start();
+    let files = sshd.files();
+    fs::create_dir_all(files.join("remote")).unwrap();
+    fs::write(files.join("remote/a.txt"), "alpha").unwrap();
+
+    let connection = Connection::connect("127.0.0.1", sshd.connect_options()).unwrap();
+    let reconnected = Arc::new(AtomicBool::new(false));
+    {
+        let reconnected = Arc::clone(&reconnected);
+        connection.set_on_reconnect(move |result| {
+            assert!(result.is_ok());
+            reconnected.store(true, Ordering::Relaxed);
+        });
+    }
+    assert_eq!(connection.read_dir(&files.join("remote")).unwrap().len(), 1);
+
+    // Drop the connection under it, like a network that went away.
+    connection
+        .session()
+        .disconnect(None, "testing", None)
+        .unwrap();
+    let error = connection
+        .read_to_string(&files.join("remote/a.txt"))
+        .unwrap_err();
+    assert!(error.is_disconnect());
+
+    // The connection is restored in the background.
+    let start = Instant::now();
+    while !reconnected.load(Ordering::Relaxed) {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        connection
+            .read_to_string(&files.join("remote/a.txt"))
+            .unwrap(),
+        "alpha"
+    );
+}
+
+#[test]
+fn test_keepalive() {
+    let sshd = Sshd::start();
+    let options = ConnectOptions {
+        server_alive_interval: Some(Duration::from_secs(1)),
+        ..sshd.connect_options()
+    };
+    let connection = Connection::connect("127.0.0.1", options).unwrap();
+    std::thread::sleep(Duration::from_millis(1100));
+    connection.keepalive().unwrap();
+    assert!(connection.read_dir(&sshd.files()).is_ok());
+}
+
+#[test]
+fn test_method_prefs() {
+    let sshd = Sshd::start();
+    let options = ConnectOptions {
+        compression: false,
+        ciphers: Some("aes256-ctr,aes128-ctr".to_string()),
+        macs: Some("hmac-sha2-256".to_string()),
+        ..sshd.connect_options()
+    };
+    let session = create_session("127.0.0.1", &options).unwrap();
+    assert_eq!(
+        session.methods(ssh2::MethodType::CryptCs),
+        Some("aes256-ctr")
+    );
+    assert_eq!(
+        session.methods(ssh2::MethodType::MacSc),
+        Some("hmac-sha2-256")
+    );
+    assert_eq!(session.methods(ssh2::MethodType::CompCs), Some("none"));
+
+    let options = ConnectOptions {
+        ciphers: Some("rot13".to_string()),
+        ..sshd.connect_options()
+    };
+    assert!(matches!(
+        create_session("127.0.0.1", &options),
+        Err(ErrorKind::UnsupportedMethods(_, _))
+    ));
+}
@@ -0,0 +1,338 @@
+mod common;
+
+use common::RecordingListener;
+use rftp::memory::{Fault, MemoryFileSystem};
+use rftp::transfer::{apply_transfer, plan_transfer, transfer_recursive};
+use rftp::{ErrorKind, FileSystem, Filter, LocalFileEntry, PlannedAction, TransferAction};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Return a local file system with a small project below `/src`.
+fn project() -> MemoryFileSystem<LocalFileEntry> {
+    let fs = MemoryFileSystem::local();
+    fs.add_file("/src/README.md", "hello")
+        .add_file("/src/lib/a.rs", "fn a() {}")
+        .add_file("/src/lib/b.rs", "fn b() {}")
+        .add_dir("/src/empty");
+    fs
+}
+
+fn transfer(
+    source_fs: &impl FileSystem,
+    source: &str,
+    dest_fs: &impl FileSystem,
+    dest: &str,
+    listener: &RecordingListener,
+) -> rftp::Result<()> {
+    let source = source_fs.stat(Path::new(source))?.unwrap();
+    transfer_recursive(
+        source_fs,
+        source,
+        dest_fs,
+        PathBuf::from(dest),
+        &Filter::default(),
+        listener,
+    )
+}
+
+fn io_error_kind(error: ErrorKind) -> io::ErrorKind {
+    match error {
+        ErrorKind::IOError(error) => error.kind(),
+        error => panic!("expected an io error, got {:?}", error),
+    }
+}
+
+#[test]
+fn test_upload_directory() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    let listener = RecordingListener::default();
+
+    transfer(&local, "/src", &remote, "/dst", &listener).unwrap();
+
+    assert_eq!(
+        remote.paths(),
+        [
+            "/dst",
+            "/dst/README.md",
+            "/dst/empty",
+            "/dst/lib",
+            "/dst/lib/a.rs",
+            "/dst/lib/b.rs"
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(remote.contents("/dst/lib/a.rs").unwrap(), "fn a() {}");
+    let mut finished = listener.finished.into_inner().unwrap();
+    finished.sort();
+    assert_eq!(
+        finished,
+        [
+            "Uploading \"README.md\"",
+            "Uploading \"a.rs\"",
+            "Uploading \"b.rs\""
+        ]
+    );
+    assert!(listener.warnings.into_inner().unwrap().is_empty());
+}
+
+#[test]
+fn test_download_directory() {
+    let remote = MemoryFileSystem::remote();
+    remote
+        .add_file("/home/me/notes/todo.txt", "milk")
+        .add_file("/home/me/notes/.hidden", "secret");
+    let local = MemoryFileSystem::local();
+    local.add_dir("/tmp");
+    let listener = RecordingListener::default();
+
+    transfer(&remote, "/home/me/notes", &local, "/tmp/notes", &listener).unwrap();
+
+    assert_eq!(local.contents("/tmp/notes/todo.txt").unwrap(), "milk");
+    assert_eq!(local.contents("/tmp/notes/.hidden").unwrap(), "secret");
+    assert_eq!(listener.finished.lock().unwrap().len(), 2);
+    assert!(listener.finished.lock().unwrap()[0].starts_with("Downloading"));
+}
+
+#[test]
+fn test_transfer_into_missing_directory() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    let listener = RecordingListener::default();
+
+    let error = transfer(&local, "/src", &remote, "/missing/dst", &listener).unwrap_err();
+    assert_eq!(io_error_kind(error), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_existing_destination() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    remote.add_file("/dst/README.md", "old");
+    let listener = RecordingListener::default();
+
+    // A recursive transfer never replaces anything.
+    match transfer(&local, "/src", &remote, "/dst", &listener) {
+        Err(ErrorKind::RemoteFileExists(path)) => assert_eq!(path, "/dst"),
+        result => panic!("expected the destination to exist, got {:?}", result),
+    }
+    match transfer(&remote, "/dst", &local, "/src", &listener) {
+        Err(ErrorKind::LocalFileExists(path)) => assert_eq!(path, "/src"),
+        result => panic!("expected the destination to exist, got {:?}", result),
+    }
+    assert_eq!(remote.contents("/dst/README.md").unwrap(), "old");
+}
+
+#[test]
+fn test_merge_into_existing_directory() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    remote
+        .add_file("/dst/README.md", "old")
+        .add_file("/dst/lib/a.rs/keep.txt", "keep")
+        .add_file("/dst/empty", "not a directory")
+        .add_file("/dst/untouched.txt", "untouched");
+    let source = local.stat(Path::new("/src")).unwrap().unwrap();
+
+    let actions = plan_transfer(
+        &local,
+        source,
+        &remote,
+        PathBuf::from("/dst"),
+        &Filter::default(),
+    )
+    .unwrap();
+    let mut lines: Vec<_> = actions.iter().map(PlannedAction::describe).collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "create    /dst/lib/b.rs (9 B)",
+            "overwrite /dst/README.md (5 B)",
+            "skip      /dst/empty (a file with the same name exists)",
+            "skip      /dst/lib/a.rs (a directory with the same name exists)",
+        ]
+    );
+
+    let listener = RecordingListener::default();
+    apply_transfer(&local, &remote, &actions, &listener).unwrap();
+    assert_eq!(remote.contents("/dst/README.md").unwrap(), "hello");
+    assert_eq!(remote.contents("/dst/lib/b.rs").unwrap(), "fn b() {}");
+    assert_eq!(remote.contents("/dst/lib/a.rs/keep.txt").unwrap(), "keep");
+    assert_eq!(remote.contents("/dst/empty").unwrap(), "not a directory");
+    assert_eq!(remote.contents("/dst/untouched.txt").unwrap(), "untouched");
+    assert_eq!(listener.warnings.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_skip_symlinks() {
+    let local = project();
+    local
+        .add_symlink("/src/link", "/src/README.md")
+        .add_symlink("/src/lib/dangling", "/nowhere");
+    let remote = MemoryFileSystem::remote();
+    let listener = RecordingListener::default();
+
+    transfer(&local, "/src", &remote, "/dst", &listener).unwrap();
+
+    assert!(remote.contents("/dst/link").is_none());
+    assert!(!remote.paths().contains(&PathBuf::from("/dst/lib/dangling")));
+    let mut warnings = listener.warnings.into_inner().unwrap();
+    warnings.sort();
+    assert_eq!(
+        warnings,
+        [
+            "Warning: Skipping /src/lib/dangling because it might be a symlink.",
+            "Warning: Skipping /src/link because it might be a symlink.",
+        ]
+    );
+}
+
+#[test]
+fn test_filter() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    let source = local.stat(Path::new("/src")).unwrap().unwrap();
+    let mut filter = Filter::default();
+    filter.exclude("lib/");
+
+    let actions = plan_transfer(&local, source, &remote, PathBuf::from("/dst"), &filter).unwrap();
+    apply_transfer(&local, &remote, &actions, &RecordingListener::default()).unwrap();
+
+    assert_eq!(
+        remote.paths(),
+        ["/dst", "/dst/README.md", "/dst/empty"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_short_reads() {
+    let contents = "0123456789".repeat(1000);
+    let local = MemoryFileSystem::local();
+    local.add_file("/big.txt", &contents).short_reads(7);
+    let remote = MemoryFileSystem::remote();
+    let listener = RecordingListener::default();
+
+    transfer(&local, "/big.txt", &remote, "/big.txt", &listener).unwrap();
+
+    assert_eq!(remote.contents("/big.txt").unwrap(), contents);
+    assert_eq!(
+        *listener.progress_updates.lock().unwrap(),
+        contents.len().div_ceil(7)
+    );
+}
+
+#[test]
+fn test_permission_denied() {
+    let local = project();
+    local.inject("/src/lib/b.rs", Fault::PermissionDenied);
+    let remote = MemoryFileSystem::remote();
+    let listener = RecordingListener::default();
+
+    let error = transfer(&local, "/src", &remote, "/dst", &listener).unwrap_err();
+    assert_eq!(io_error_kind(error), io::ErrorKind::PermissionDenied);
+
+    // Reading a directory that is not allowed fails before anything is written.
+    let local = project();
+    local.inject("/src/lib", Fault::PermissionDenied);
+    let remote = MemoryFileSystem::remote();
+    let error = transfer(&local, "/src", &remote, "/dst", &listener).unwrap_err();
+    assert_eq!(io_error_kind(error), io::ErrorKind::PermissionDenied);
+    assert!(remote.paths().is_empty());
+
+    // So does a destination that cannot be written to.
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    remote.inject("/dst", Fault::PermissionDenied);
+    let error = transfer(&local, "/src/README.md", &remote, "/dst", &listener).unwrap_err();
+    assert_eq!(io_error_kind(error), io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn test_disconnect_mid_file() {
+    let remote = MemoryFileSystem::remote();
+    remote
+        .add_file("/data/1.bin", &"a".repeat(100))
+        .add_file("/data/2.bin", &"b".repeat(100_000))
+        .add_file("/data/3.bin", &"c".repeat(100))
+        .inject("/data/2.bin", Fault::Disconnect { after: 50_000 });
+    let local = MemoryFileSystem::local();
+    let listener = RecordingListener::default();
+
+    let error = transfer(&remote, "/data", &local, "/data", &listener).unwrap_err();
+    assert_eq!(io_error_kind(error), io::ErrorKind::ConnectionAborted);
+
+    // The transfer stops at the broken file and leaves what was received so far.
+    assert_eq!(local.contents("/data/1.bin").unwrap().len(), 100);
+    assert_eq!(local.contents("/data/2.bin").unwrap().len(), 50_000);
+    assert!(local.contents("/data/3.bin").is_none());
+    assert_eq!(
+        *listener.finished.lock().unwrap(),
+        ["Downloading \"1.bin\""]
+    );
+
+    // The connection stays broken.
+    match remote.read_dir(Path::new("/data")) {
+        Err(error) => assert_eq!(io_error_kind(error), io::ErrorKind::ConnectionAborted),
+        Ok(_) => panic!("expected the connection to stay broken"),
+    }
+}
+
+#[test]
+fn test_cancel() {
+    let remote = MemoryFileSystem::remote();
+    remote
+        .add_file("/data/1.bin", &"a".repeat(100))
+        .add_file("/data/2.bin", &"b".repeat(100_000))
+        .add_file("/data/3.bin", &"c".repeat(100));
+    let local = MemoryFileSystem::local();
+    let listener = RecordingListener {
+        cancel_after: Some(3),
+        ..RecordingListener::default()
+    };
+
+    match transfer(&remote, "/data", &local, "/data", &listener) {
+        Err(ErrorKind::Cancelled) => {}
+        result => panic!("expected the transfer to be cancelled, got {:?}", result),
+    }
+
+    // The partially received file is removed and nothing else is started.
+    assert_eq!(local.contents("/data/1.bin").unwrap().len(), 100);
+    assert!(local.contents("/data/2.bin").is_none());
+    assert!(local.contents("/data/3.bin").is_none());
+    assert_eq!(
+        *listener.finished.lock().unwrap(),
+        ["Downloading \"1.bin\""]
+    );
+}
+
+#[test]
+fn test_plan_does_not_write() {
+    let local = project();
+    let remote = MemoryFileSystem::remote();
+    let source = local.stat(Path::new("/src")).unwrap().unwrap();
+
+    let actions = plan_transfer(
+        &local,
+        source,
+        &remote,
+        PathBuf::from("/dst"),
+        &Filter::default(),
+    )
+    .unwrap();
+
+    assert!(remote.paths().is_empty());
+    assert_eq!(
+        actions.first(),
+        Some(&TransferAction::CreateDirectory {
+            dest: PathBuf::from("/dst")
+        })
+    );
+    assert_eq!(actions.iter().map(PlannedAction::bytes).sum::<u64>(), 23);
+}